@@ -0,0 +1,312 @@
+//! Paid access to the premium views.
+//!
+//! A premium view is a payable call, so a consumer contract gets the data in
+//! its callback. The access fee is paid either with the attached deposit,
+//! from the consumer's prepaid allowance, or with the token credit (see the
+//! [`crate::token`]). The fees in NEAR are the revenue of the owner, while
+//! those in the token make the reward pool of the reporters.
+//!
+//! Optionally, the premium views are only served to the allowlisted
+//! consumers.
+
+use near_sdk::json_types::U128;
+use near_sdk::serde_json::json;
+use near_sdk::{env, log, near_bindgen, AccountId, NearToken, Promise};
+
+#[cfg(feature = "archive")]
+use crate::asset::HistoryEntry;
+use crate::asset::{AttributedEntry, History};
+use crate::error::{ensure, ContractError};
+use crate::events;
+#[cfg(feature = "archive")]
+use crate::DEFAULT_PAGE_LIMIT;
+use crate::{Balance, LogLevel, PriceHistory, PriceHistoryExt};
+
+#[near_bindgen]
+impl PriceHistory {
+    /// Returns the fee charged for a premium view.
+    pub fn get_access_fee(&self) -> U128 {
+        self.access_fee.into()
+    }
+
+    /// Sets the fee charged for a premium view; zero makes them free.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract.
+    pub fn set_access_fee(&mut self, fee: U128) {
+        self.assert_owner("change the access fee");
+        let previous = std::mem::replace(&mut self.access_fee, fee.0);
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Access fee changed to {} yoctoNEAR", fee.0);
+        }
+        events::emit_config_changes(
+            None,
+            &json!({ "access_fee": U128(previous) }),
+            &json!({ "access_fee": fee }),
+        );
+    }
+
+    /// Adds the attached deposit to the allowance of the `account_id`, or of
+    /// the caller if no account is provided. A new allowance is charged for
+    /// its own storage out of the deposit.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the deposit doesn't cover the storage of a new
+    /// allowance.
+    #[payable]
+    pub fn deposit_allowance(&mut self, account_id: Option<AccountId>) -> U128 {
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let deposit = env::attached_deposit().as_yoctonear();
+        let initial_usage = env::storage_usage();
+        let mut allowance = self.allowances.get(&account_id).unwrap_or(0) + deposit;
+        self.allowances.insert(&account_id, &allowance);
+        let cost = Balance::from(env::storage_usage().saturating_sub(initial_usage))
+            * env::storage_byte_cost().as_yoctonear();
+        ensure(
+            cost <= deposit,
+            ContractError::InsufficientDeposit {
+                funds: "attached deposit",
+                required: cost,
+                available: deposit,
+            },
+        );
+        if cost != 0 {
+            allowance -= cost;
+            self.allowances.insert(&account_id, &allowance);
+        }
+        allowance.into()
+    }
+
+    /// Returns the prepaid allowance of the account.
+    pub fn get_allowance(&self, account_id: AccountId) -> U128 {
+        self.allowances.get(&account_id).unwrap_or(0).into()
+    }
+
+    /// Withdraws the `amount`, or the whole allowance if no amount is
+    /// provided, from the caller's allowance, e.g. once the contract is
+    /// decommissioned, and returns the rest. Withdrawing the whole allowance
+    /// refunds its storage as well.
+    ///
+    /// # Panics
+    ///
+    /// Will panic unless exactly one yoctoNEAR is attached, or when the
+    /// `amount` exceeds the allowance.
+    #[payable]
+    pub fn withdraw_allowance(&mut self, amount: Option<U128>) -> U128 {
+        Self::assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let allowance = self.allowances.get(&account_id).unwrap_or(0);
+        let amount = amount.map_or(allowance, |amount| amount.0);
+        ensure(
+            amount <= allowance,
+            ContractError::OutOfBounds("The amount exceeds the allowance"),
+        );
+        let remaining = allowance - amount;
+        let mut refund = amount;
+        if remaining == 0 {
+            let initial_usage = env::storage_usage();
+            self.allowances.remove(&account_id);
+            refund += Balance::from(initial_usage.saturating_sub(env::storage_usage()))
+                * env::storage_byte_cost().as_yoctonear();
+        } else {
+            self.allowances.insert(&account_id, &remaining);
+        }
+        if refund > 0 {
+            Promise::new(account_id)
+                .transfer(NearToken::from_yoctonear(refund))
+                .detach();
+        }
+        remaining.into()
+    }
+
+    /// Returns the access fees collected in NEAR, the revenue of the owner
+    /// transferred to the beneficiary of [`Self::decommission`].
+    pub fn get_fee_revenue(&self) -> U128 {
+        self.fee_revenue.into()
+    }
+
+    /// Returns the amount of the payment token accrued to the reward pool.
+    pub fn get_token_reward_pool(&self) -> U128 {
+        self.token_reward_pool.into()
+    }
+
+    /// Enables or disables serving the premium views to the allowlisted
+    /// consumers only.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract.
+    pub fn set_consumer_allowlist(&mut self, enabled: bool) {
+        self.assert_owner("manage consumers");
+        let previous = std::mem::replace(&mut self.consumer_allowlist, enabled);
+        if self.log_level.allows(LogLevel::Info) {
+            let state = if enabled { "enabled" } else { "disabled" };
+            log!("Consumer allowlist {}", state);
+        }
+        events::emit_config_changes(
+            None,
+            &json!({ "consumer_allowlist": previous }),
+            &json!({ "consumer_allowlist": enabled }),
+        );
+    }
+
+    /// Allows the account to consume the premium views.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract.
+    pub fn add_consumer(&mut self, account_id: AccountId) {
+        self.assert_owner("manage consumers");
+        if !self.consumers.insert(&account_id) {
+            return;
+        }
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Consumer {} added", account_id);
+        }
+        events::emit_config_changes(
+            None,
+            &json!({ "consumers": { account_id.as_str(): false } }),
+            &json!({ "consumers": { account_id.as_str(): true } }),
+        );
+    }
+
+    /// Revokes the account's permission to consume the premium views.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract.
+    pub fn remove_consumer(&mut self, account_id: AccountId) {
+        self.assert_owner("manage consumers");
+        if !self.consumers.remove(&account_id) {
+            return;
+        }
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Consumer {} removed", account_id);
+        }
+        events::emit_config_changes(
+            None,
+            &json!({ "consumers": { account_id.as_str(): true } }),
+            &json!({ "consumers": { account_id.as_str(): false } }),
+        );
+    }
+
+    /// Checks whether the account may consume the premium views.
+    pub fn is_consumer(&self, account_id: AccountId) -> bool {
+        !self.consumer_allowlist || self.consumers.contains(&account_id)
+    }
+
+    /// A premium view: returns every retained record of the asset, from the
+    /// oldest to the newest, beyond the configured depth.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the caller is not an allowed consumer, when the asset
+    /// is not registered, or when neither the attached deposit with the
+    /// caller's allowance nor the caller's token credit cover the access fee.
+    #[payable]
+    pub fn get_full_history(&mut self, symbol: String) -> History {
+        let consumer = self.assert_consumer();
+        let history = self.asset(&symbol).full_history();
+        self.charge_access_fee(&consumer);
+        history
+    }
+
+    /// A premium view: returns every retained price of the asset along with
+    /// the account which has reported it, from the oldest to the newest, so
+    /// a bad value can be attributed to its reporter.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the caller is not an allowed consumer, when the asset
+    /// is not registered, or when neither the attached deposit with the
+    /// caller's allowance nor the caller's token credit cover the access fee.
+    #[payable]
+    pub fn get_history_with_reporters(&mut self, symbol: String) -> Vec<AttributedEntry> {
+        let consumer = self.assert_consumer();
+        let history = self.asset(&symbol).attributed_history();
+        self.charge_access_fee(&consumer);
+        history
+    }
+
+    /// A premium view: returns up to `limit` (50 by default) archived records
+    /// of the asset, from the oldest to the newest, starting from the
+    /// `from_index`.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the caller is not an allowed consumer, when the asset
+    /// is not registered, or when neither the attached deposit with the
+    /// caller's allowance nor the caller's token credit cover the access fee.
+    #[cfg(feature = "archive")]
+    #[payable]
+    pub fn get_archive(
+        &mut self,
+        symbol: String,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<HistoryEntry> {
+        let consumer = self.assert_consumer();
+        let records = self
+            .asset(&symbol)
+            .archive
+            .records(from_index.unwrap_or(0), limit.unwrap_or(DEFAULT_PAGE_LIMIT));
+        self.charge_access_fee(&consumer);
+        records.iter().map(HistoryEntry::from).collect()
+    }
+}
+
+impl PriceHistory {
+    /// Returns the caller.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the caller is not an allowed consumer.
+    pub(crate) fn assert_consumer(&self) -> AccountId {
+        let account_id = env::predecessor_account_id();
+        ensure(
+            !self.consumer_allowlist || self.consumers.contains(&account_id),
+            ContractError::Unauthorized("consume the data"),
+        );
+        account_id
+    }
+
+    /// Charges the access fee from the attached deposit or, if it's not
+    /// enough, from the allowance of the `account_id`, falling back to the
+    /// token credit. An excess deposit is added to the allowance.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when neither the deposit with the allowance nor the token
+    /// credit cover the fee.
+    fn charge_access_fee(&mut self, account_id: &AccountId) {
+        let deposit: Balance = env::attached_deposit().as_yoctonear();
+        let fee = self.access_fee;
+        let allowance = self.allowances.get(account_id).unwrap_or(0);
+        let total = deposit + allowance;
+        if total < fee {
+            ensure(
+                self.charge_token_fee(account_id),
+                ContractError::InsufficientDeposit {
+                    funds: "deposit, allowance or credit",
+                    required: fee,
+                    available: total,
+                },
+            );
+            if deposit > 0 {
+                self.allowances.insert(account_id, &total);
+            }
+            return;
+        }
+        let remaining = total - fee;
+        if remaining != allowance {
+            self.allowances.insert(account_id, &remaining);
+        }
+        self.fee_revenue += fee;
+    }
+}
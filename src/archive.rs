@@ -0,0 +1,581 @@
+//! A long-term archive of the price records, beyond the retained history.
+//!
+//! The records are sharded by [`SHARD_LEN`], each shard is stored under its
+//! own key and the timestamp of its first record is kept in the index, so no
+//! read approaches the size limits of a value and the oldest records are
+//! pruned by whole shards.
+//!
+//! A shard either keeps the records as they are, or, in the compact
+//! encoding, the prices as the `i64` deltas from the previous ones at the
+//! precision of the decimals of the asset and the whole seconds elapsed
+//! since them as `u32`, which takes about half the space of a full record.
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::asset::PriceRecord;
+use crate::fixed::{Fixed, Rounding};
+use crate::price::Price;
+
+/// Amount of records per shard.
+pub const SHARD_LEN: u64 = 1024;
+
+/// Nanoseconds per second, the precision of the timestamps of the deltas.
+const SECOND: u64 = 1_000_000_000;
+
+/// How the archive stores the records.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    BorshDeserialize,
+    BorshSerialize,
+    Serialize,
+    Deserialize,
+)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum ArchiveEncoding {
+    /// Every record as it is.
+    #[default]
+    Full,
+    /// The prices as the deltas from the previous ones, rounded to the
+    /// decimals of the asset, and the timestamps rounded to the seconds.
+    Compact,
+}
+
+/// An entry of a compact shard.
+#[derive(Debug, Clone, Copy, PartialEq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+enum Entry {
+    /// A record which can't be represented as a delta, like the first one.
+    Full(PriceRecord),
+    /// The change of the price since the previous record, scaled to the
+    /// decimals of the shard, and the seconds elapsed since it.
+    Delta { price: i64, elapsed: u32 },
+}
+
+/// Up to [`SHARD_LEN`] consecutive records.
+#[derive(Debug, Clone, PartialEq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+enum Shard {
+    Full(Vec<PriceRecord>),
+    Compact {
+        decimals: u8,
+        entries: Vec<Entry>,
+        /// The newest record as decoded, so a push doesn't decode the
+        /// entries.
+        newest: Option<PriceRecord>,
+    },
+}
+
+impl Shard {
+    fn new(encoding: ArchiveEncoding, decimals: u8) -> Self {
+        match encoding {
+            ArchiveEncoding::Full => Shard::Full(Vec::new()),
+            ArchiveEncoding::Compact => Shard::Compact {
+                decimals,
+                entries: Vec::new(),
+                newest: None,
+            },
+        }
+    }
+
+    fn push(&mut self, record: PriceRecord) {
+        match self {
+            Shard::Full(records) => records.push(record),
+            Shard::Compact {
+                decimals,
+                entries,
+                newest,
+            } => {
+                let entry = newest
+                    .and_then(|previous| encode(*decimals, previous, record))
+                    .unwrap_or(Entry::Full(record));
+                *newest = newest.map_or(Some(record), |previous| apply(*decimals, previous, entry));
+                entries.push(entry);
+            }
+        }
+    }
+
+    /// Replaces the records of the shard, keeping its encoding.
+    fn replace(&mut self, records: &[PriceRecord]) {
+        match self {
+            Shard::Full(stored) => *stored = records.to_vec(),
+            Shard::Compact {
+                decimals,
+                entries,
+                newest,
+            } => {
+                let mut previous = None;
+                *entries = records
+                    .iter()
+                    .map(|&record| {
+                        let entry = previous
+                            .and_then(|previous| encode(*decimals, previous, record))
+                            .unwrap_or(Entry::Full(record));
+                        previous = previous
+                            .map_or(Some(record), |previous| apply(*decimals, previous, entry));
+                        entry
+                    })
+                    .collect();
+                *newest = previous;
+            }
+        }
+    }
+
+    fn records(self) -> Vec<PriceRecord> {
+        match self {
+            Shard::Full(records) => records,
+            Shard::Compact {
+                decimals, entries, ..
+            } => decode(decimals, &entries),
+        }
+    }
+}
+
+/// Represents the `record` as a delta from the `previous` one, if it's
+/// decoded back at the precision of the `decimals` and to the nearest
+/// second.
+fn encode(decimals: u8, previous: PriceRecord, record: PriceRecord) -> Option<Entry> {
+    let base = i128::try_from(
+        previous
+            .price
+            .fixed()
+            .rescale(decimals, Rounding::Nearest)?,
+    )
+    .ok()?;
+    let scaled = i128::try_from(record.price.fixed().rescale(decimals, Rounding::Nearest)?).ok()?;
+    let entry = Entry::Delta {
+        price: i64::try_from(scaled - base).ok()?,
+        elapsed: u32::try_from(
+            record
+                .timestamp
+                .checked_sub(previous.timestamp)?
+                .saturating_add(SECOND / 2)
+                / SECOND,
+        )
+        .ok()?,
+    };
+    apply(decimals, previous, entry).map(|_| entry)
+}
+
+/// Decodes the record represented by the `entry` after the `previous` one.
+fn apply(decimals: u8, previous: PriceRecord, entry: Entry) -> Option<PriceRecord> {
+    match entry {
+        Entry::Full(record) => Some(record),
+        Entry::Delta { price, elapsed } => {
+            let base = previous
+                .price
+                .fixed()
+                .rescale(decimals, Rounding::Nearest)?;
+            let scaled = base.checked_add_signed(i128::from(price))?;
+            Some(PriceRecord {
+                price: Price::from_fixed(Fixed::from_scaled(scaled, decimals)?).ok()?,
+                timestamp: previous
+                    .timestamp
+                    .checked_add(u64::from(elapsed).checked_mul(SECOND)?)?,
+            })
+        }
+    }
+}
+
+fn decode(decimals: u8, entries: &[Entry]) -> Vec<PriceRecord> {
+    let mut records: Vec<PriceRecord> = Vec::with_capacity(entries.len());
+    for &entry in entries {
+        let previous = records.last().copied().unwrap_or_default();
+        // Hint: the deltas are only stored when decoded successfully.
+        if let Some(record) = apply(decimals, previous, entry) {
+            records.push(record);
+        }
+    }
+    records
+}
+
+/// The sharded archive.
+#[derive(BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Archive {
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::LookupMap>",
+            definitions = "crate::schema::definitions::<crate::schema::LookupMap>",
+        )))
+    )]
+    shards: LookupMap<u64, Shard>,
+    /// Timestamps of the first records of the shards.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::LookupMap>",
+            definitions = "crate::schema::definitions::<crate::schema::LookupMap>",
+        )))
+    )]
+    index: LookupMap<u64, u64>,
+    /// Position of the oldest retained record.
+    start: u64,
+    /// Position past the newest record.
+    end: u64,
+}
+
+impl Archive {
+    /// Creates an empty archive, stored under the `prefix`.
+    pub fn new(prefix: Vec<u8>) -> Self {
+        Self {
+            shards: LookupMap::new([&prefix[..], b"s"].concat()),
+            index: LookupMap::new([&prefix[..], b"i"].concat()),
+            start: 0,
+            end: 0,
+        }
+    }
+
+    /// Returns the amount of the retained records.
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    /// Returns the amount of the stored shards.
+    pub fn shards(&self) -> u64 {
+        if self.start == self.end {
+            0
+        } else {
+            (self.end - 1) / SHARD_LEN - self.start / SHARD_LEN + 1
+        }
+    }
+
+    /// Returns the amount of bytes the stored shards take, reading all of
+    /// them.
+    pub fn shards_len(&self) -> u64 {
+        (self.start / SHARD_LEN..self.end.div_ceil(SHARD_LEN))
+            .filter_map(|shard| self.shards.get(&shard))
+            .map(|shard| borsh::to_vec(&shard).map_or(0, |shard| shard.len() as u64))
+            .sum()
+    }
+
+    /// Appends the `record`, which mustn't predate the previous ones. A new
+    /// shard is stored with the `encoding`, the prices of which are rounded
+    /// to the `decimals` in the compact encoding.
+    pub fn push(&mut self, record: PriceRecord, encoding: ArchiveEncoding, decimals: u8) {
+        let id = self.end / SHARD_LEN;
+        let mut shard = self.shards.get(&id).unwrap_or_else(|| {
+            self.index.insert(&id, &record.timestamp);
+            Shard::new(encoding, decimals)
+        });
+        shard.push(record);
+        self.shards.insert(&id, &shard);
+        self.end += 1;
+    }
+
+    /// Inserts the `record` after the retained ones which don't postdate it,
+    /// or before all of them. The shards from the one of the `record` on are
+    /// rewritten, each passing its newest record on to the next one, so the
+    /// older the `record` is, the more it costs. A new shard is stored with
+    /// the `encoding` and the `decimals`, as in the [`Self::push`].
+    pub fn insert(&mut self, record: PriceRecord, encoding: ArchiveEncoding, decimals: u8) {
+        let first = self.start / SHARD_LEN;
+        let mut id = (first..self.end.div_ceil(SHARD_LEN))
+            .rev()
+            .find(|id| {
+                self.index
+                    .get(id)
+                    .is_some_and(|timestamp| timestamp <= record.timestamp)
+            })
+            .unwrap_or(first);
+        let mut carried = record;
+        let mut backfilled = false;
+        while let Some(mut shard) = self.shards.get(&id) {
+            let mut records = shard.clone().records();
+            // Hint: a record passed on is older than the ones of the shard.
+            let position = if backfilled {
+                0
+            } else {
+                records.partition_point(|record| record.timestamp <= carried.timestamp)
+            };
+            backfilled = true;
+            records.insert(position, carried);
+            let overflow = (records.len() as u64 > SHARD_LEN)
+                .then(|| records.pop())
+                .flatten();
+            shard.replace(&records);
+            self.shards.insert(&id, &shard);
+            self.index.insert(&id, &records[0].timestamp);
+            match overflow {
+                Some(record) => {
+                    carried = record;
+                    id += 1;
+                }
+                None => {
+                    self.end += 1;
+                    return;
+                }
+            }
+        }
+        // Hint: the newest shard is full, or there are none.
+        self.push(carried, encoding, decimals);
+    }
+
+    /// Returns up to `limit` records, from the oldest to the newest,
+    /// starting from the `from_index`-th retained one.
+    pub fn records(&self, from_index: u64, limit: u64) -> Vec<PriceRecord> {
+        let mut position = self.start.saturating_add(from_index).min(self.end);
+        let end = position.saturating_add(limit).min(self.end);
+        let mut records = Vec::new();
+        while position < end {
+            let offset = position % SHARD_LEN;
+            let count = (end - position).min(SHARD_LEN - offset);
+            let shard = self
+                .shards
+                .get(&(position / SHARD_LEN))
+                .map_or_else(Vec::new, Shard::records);
+            records.extend(shard.into_iter().skip(offset as usize).take(count as usize));
+            position += count;
+        }
+        records
+    }
+
+    /// Returns the newest retained record, if any.
+    pub fn newest(&self) -> Option<PriceRecord> {
+        self.records(self.len().checked_sub(1)?, 1).pop()
+    }
+
+    /// Returns every retained record, from the oldest to the newest. The
+    /// shards are read as the iterator advances.
+    pub fn iter(&self) -> impl Iterator<Item = PriceRecord> + '_ {
+        self.iter_from(0)
+    }
+
+    /// Returns the retained records from the newest shard the first record
+    /// of which predates the `timestamp`, or from the oldest shard, so the
+    /// records since the `timestamp` and the one before them are all there.
+    /// The shard is found by a binary search of the index, and the shards
+    /// are read as the iterator advances.
+    pub fn iter_from(&self, timestamp: u64) -> impl Iterator<Item = PriceRecord> + '_ {
+        let first = self.start / SHARD_LEN;
+        let (mut low, mut high) = (first, self.end.div_ceil(SHARD_LEN));
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if self
+                .index
+                .get(&middle)
+                .is_some_and(|first| first < timestamp)
+            {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        // Hint: the shards before the `low` one are those predating the
+        // `timestamp`.
+        let from = (low.saturating_sub(1).max(first) * SHARD_LEN).max(self.start);
+        std::iter::successors(Some(from), |position| {
+            Some((position / SHARD_LEN + 1) * SHARD_LEN)
+        })
+        .take_while(move |position| *position < self.end)
+        .flat_map(move |position| {
+            self.records(position - self.start, SHARD_LEN - position % SHARD_LEN)
+        })
+    }
+
+    /// Drops the shards all the records of which predate the `timestamp`,
+    /// and returns the amount of the dropped records. The newest shard is
+    /// always kept.
+    pub fn prune_before(&mut self, timestamp: u64) -> u64 {
+        let mut dropped = 0;
+        loop {
+            let shard = self.start / SHARD_LEN;
+            let next = (shard + 1) * SHARD_LEN;
+            // Hint: the records of a shard don't postdate the first record
+            // of the next one.
+            match self.index.get(&(shard + 1)) {
+                Some(first) if first < timestamp => {}
+                _ => break,
+            }
+            self.shards.remove(&shard);
+            self.index.remove(&shard);
+            dropped += next - self.start;
+            self.start = next;
+        }
+        dropped
+    }
+
+    /// Removes every record from the storage.
+    pub fn clear(&mut self) {
+        if self.start != self.end {
+            for shard in self.start / SHARD_LEN..=(self.end - 1) / SHARD_LEN {
+                self.shards.remove(&shard);
+                self.index.remove(&shard);
+            }
+        }
+        self.start = self.end;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+    use crate::price::Price;
+
+    fn record(timestamp: u64) -> PriceRecord {
+        PriceRecord {
+            price: Price::new(timestamp as f64).unwrap(),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn compact() {
+        let mut full = Archive::new(b"f".to_vec());
+        let mut compact = Archive::new(b"c".to_vec());
+        let records = [
+            record(1),
+            PriceRecord {
+                price: Price::new(1.25).unwrap(),
+                timestamp: 7 * SECOND + 1,
+            },
+            // Too big a change for a delta.
+            PriceRecord {
+                price: Price::new(1e14).unwrap(),
+                timestamp: 8 * SECOND,
+            },
+            PriceRecord {
+                price: Price::new(50.).unwrap(),
+                timestamp: 50 * SECOND,
+            },
+        ];
+        for record in records {
+            full.push(record, ArchiveEncoding::Full, 8);
+            compact.push(record, ArchiveEncoding::Compact, 8);
+        }
+        assert_eq!(compact.records(0, 10), records);
+
+        // The prices are rounded to the decimals, the timestamps to the
+        // seconds.
+        compact.push(
+            PriceRecord {
+                price: Price::new(50.123_456_789).unwrap(),
+                timestamp: 60 * SECOND + SECOND / 2 - 1,
+            },
+            ArchiveEncoding::Compact,
+            8,
+        );
+        assert_eq!(
+            compact.records(4, 1)[0],
+            PriceRecord {
+                price: Price::from_fixed(Fixed::from_scaled(5_012_345_679, 8).unwrap()).unwrap(),
+                timestamp: 60 * SECOND,
+            }
+        );
+    }
+
+    #[test]
+    fn compact_len() {
+        let mut full = Archive::new(b"f".to_vec());
+        let mut compact = Archive::new(b"c".to_vec());
+        for minute in 0..100 {
+            let record = PriceRecord {
+                price: Price::new(40_000. + minute as f64 / 4.).unwrap(),
+                timestamp: minute * 60 * SECOND,
+            };
+            full.push(record, ArchiveEncoding::Full, 8);
+            compact.push(record, ArchiveEncoding::Compact, 8);
+        }
+        assert_eq!(compact.iter().count(), 100);
+        // 13 bytes a delta against 24 a record.
+        assert_eq!(full.shards_len(), 2405);
+        assert_eq!(compact.shards_len(), 1343);
+        assert!(compact.shards_len() * 100 < full.shards_len() * 56);
+    }
+
+    #[test]
+    fn insert() {
+        let record = |second: u64| PriceRecord {
+            price: Price::new(second as f64).unwrap(),
+            timestamp: second * SECOND,
+        };
+        let mut archive = Archive::new(b"a".to_vec());
+        archive.insert(record(10), ArchiveEncoding::Compact, 0);
+        for timestamp in 0..SHARD_LEN {
+            if timestamp % 64 == 0 {
+                testing_env!(VMContextBuilder::new().build());
+            }
+            archive.push(record(2 * timestamp + 20), ArchiveEncoding::Compact, 0);
+        }
+        // Within the first shard, which passes its newest record on.
+        archive.insert(record(21), ArchiveEncoding::Compact, 0);
+        // Before every record.
+        archive.insert(record(1), ArchiveEncoding::Compact, 0);
+        // After every record.
+        archive.insert(record(5000), ArchiveEncoding::Compact, 0);
+        assert_eq!(archive.len(), SHARD_LEN + 4);
+        assert_eq!(archive.shards(), 2);
+        let timestamps: Vec<_> = archive
+            .iter()
+            .map(|record| record.timestamp / SECOND)
+            .collect();
+        let mut expected: Vec<_> = (0..SHARD_LEN).map(|timestamp| 2 * timestamp + 20).collect();
+        expected.extend([1, 10, 21, 5000]);
+        expected.sort_unstable();
+        assert_eq!(timestamps, expected);
+        assert!(archive
+            .iter()
+            .all(|stored| stored == record(stored.timestamp / SECOND)));
+    }
+
+    #[test]
+    fn shards() {
+        let mut archive = Archive::new(b"a".to_vec());
+        assert_eq!(archive.shards(), 0);
+        for timestamp in 0..2 * SHARD_LEN + 10 {
+            // Hint: a fresh context every now and then, so the gas isn't
+            // exhausted.
+            if timestamp % 64 == 0 {
+                testing_env!(VMContextBuilder::new().build());
+            }
+            archive.push(record(timestamp), ArchiveEncoding::Full, 0);
+        }
+        assert_eq!(archive.len(), 2 * SHARD_LEN + 10);
+        assert_eq!(archive.shards(), 3);
+
+        // A page spanning two shards.
+        let page = archive.records(SHARD_LEN - 1, 3);
+        let timestamps: Vec<_> = page.iter().map(|record| record.timestamp).collect();
+        assert_eq!(timestamps, vec![SHARD_LEN - 1, SHARD_LEN, SHARD_LEN + 1]);
+        assert_eq!(archive.records(2 * SHARD_LEN + 9, 10).len(), 1);
+
+        // Only the whole shards are dropped.
+        assert_eq!(archive.prune_before(SHARD_LEN + 5), SHARD_LEN);
+        assert_eq!(archive.records(0, 1), vec![record(SHARD_LEN)]);
+        assert!(archive
+            .iter()
+            .eq((SHARD_LEN..2 * SHARD_LEN + 10).map(record)));
+        // The search skips the shards before the one of the previous record.
+        assert_eq!(archive.iter_from(SHARD_LEN).next(), Some(record(SHARD_LEN)));
+        assert_eq!(
+            archive.iter_from(2 * SHARD_LEN + 1).next(),
+            Some(record(2 * SHARD_LEN))
+        );
+        assert_eq!(
+            archive.iter_from(2 * SHARD_LEN).next(),
+            Some(record(SHARD_LEN))
+        );
+        assert_eq!(archive.iter_from(u64::MAX).count(), 10);
+        assert_eq!(archive.prune_before(u64::MAX), SHARD_LEN);
+        assert_eq!(archive.shards(), 1);
+
+        archive.clear();
+        assert_eq!(archive.len(), 0);
+        assert!(archive.records(0, 10).is_empty());
+    }
+}
@@ -0,0 +1,2604 @@
+//! Per-asset data.
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap, UnorderedSet, Vector};
+use near_sdk::json_types::U64;
+use near_sdk::serde::{Deserialize, Deserializer, Serialize};
+use near_sdk::{AccountId, FunctionError, IntoStorageKey};
+
+use crate::archive::{Archive, ArchiveEncoding};
+use crate::cumulative::{Accumulator, CumulativePrice};
+use crate::error::ContractError;
+use crate::extremum::{Extremum, Kind};
+use crate::finality::{Answer, Finality};
+use crate::fixed::{self, Fixed, Rounding, WideFixed};
+use crate::kalman::{Kalman, KalmanConfig, SmoothedPrice};
+use crate::observation::{Observation, Observations};
+use crate::price::{Price, MAX_PRICE};
+#[cfg(feature = "serde")]
+use crate::ring::Latest;
+use crate::ring::Ring;
+use crate::round::{self, PendingRound, Submission};
+
+/// Maximum amount of prices to calculate the average of.
+pub const MAX_HISTORY_DEPTH: usize = 4096;
+
+/// Amount of prices kept per asset, unless a deeper history is configured:
+/// enough for the MACD of 12, 26 and 9 records, which takes 26 + 9 - 1.
+pub const RETAINED_HISTORY: u64 = 34;
+
+/// Amount of prices to calculate the average of, unless configured otherwise.
+pub const DEFAULT_HISTORY_DEPTH: u16 = 5;
+
+/// Maximum amount of records a scan for the gaps reads, so it stays within
+/// the gas of a view however long the range is.
+pub const MAX_SCANNED_RECORDS: usize = 4096;
+
+/// Maximum deviation an asset is configured with, in basis points: ten
+/// times the reference, well past any sane move of a price.
+pub const MAX_DEVIATION_BPS: u32 = 100_000;
+
+/// Maximum amount of blocks a new average waits for before it becomes the
+/// official answer, about a day of blocks.
+pub const MAX_FINALITY_BLOCKS: u32 = 86_400;
+
+/// Maximum amount of the upstream sources of an asset.
+pub const MAX_SOURCES: u64 = 16;
+
+/// Seconds in a year of 365.25 days, the volatility is annualized with.
+pub const SECONDS_PER_YEAR: f64 = 31_557_600.;
+
+/// Maximum length of the identifier of a source.
+pub const MAX_SOURCE_ID_LEN: usize = 32;
+
+/// Maximum supported amount of decimals: `10^24` is the
+/// same scale as used for yoctoNEAR, and keeps any sane price well within
+/// `u128`.
+pub const MAX_DECIMALS: u8 = 24;
+
+/// Human-readable description of an asset.
+#[derive(Debug, Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct AssetMetadata {
+    /// A full name, like "Bitcoin".
+    pub name: String,
+    /// The base currency of the pair, like "BTC".
+    pub base: String,
+    /// The quote currency of the pair, like "USD".
+    pub quote: String,
+    /// Where the prices come from, like "CoinMarketCap, hourly".
+    pub source: String,
+}
+
+/// An asset to register, see the [`crate::PriceHistory::register_asset`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AssetRegistration {
+    pub symbol: String,
+    pub decimals: u8,
+    pub metadata: AssetMetadata,
+    /// The default one when not provided.
+    pub config: Option<AssetConfig>,
+}
+
+/// Tunables of an asset.
+#[derive(Debug, Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct AssetConfig {
+    /// Amount of the latest prices to calculate the average of, up to 4096.
+    pub depth: u16,
+    /// The average is considered stale when no price has been recorded
+    /// during this amount of nanoseconds. Zero disables the check.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<u64>",
+            definitions = "crate::schema::definitions::<u64>",
+        )))
+    )]
+    pub heartbeat: U64,
+    /// Maximum deviation of a new price from the current average, in basis
+    /// points, up to 100000. Zero disables the check.
+    pub max_deviation_bps: u32,
+    /// Whether every record is also kept in the archive, until pruned.
+    #[serde(default)]
+    pub archive: bool,
+    /// When set, a price which repeats the latest one within this amount of
+    /// basis points, below 10000, only refreshes the timestamp of the latest
+    /// record.
+    #[serde(default)]
+    pub dedup_tolerance_bps: Option<u32>,
+    /// How the archive stores the records.
+    #[serde(default)]
+    pub archive_encoding: ArchiveEncoding,
+    /// Whether a second record within the same block is rejected, so a
+    /// retrying reporter can't take several slots of the window.
+    #[serde(default)]
+    pub one_per_block: bool,
+    /// Maximum amount of nanoseconds a timestamp supplied by the reporter
+    /// may lag behind the block, below the heartbeat if any. Zero only
+    /// accepts the block timestamp.
+    #[serde(default)]
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<u64>",
+            definitions = "crate::schema::definitions::<u64>",
+        )))
+    )]
+    pub max_timestamp_skew: U64,
+    /// A `price_deviation` event is emitted when a record moves the average
+    /// more than this amount of basis points, up to 100000. Zero disables
+    /// the events.
+    #[serde(default)]
+    pub alert_deviation_bps: u32,
+    /// Amount of the latest prices to calculate the short-window average of,
+    /// less than the depth. A `moving_average_cross` event is emitted
+    /// whenever it crosses the average.
+    #[serde(default)]
+    pub short_depth: Option<u16>,
+    /// Minimum amount of nanoseconds between two submissions of the same
+    /// reporter, so a fast one can't crowd the others out of the window,
+    /// below the heartbeat if any. Zero disables the check.
+    #[serde(default)]
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<u64>",
+            definitions = "crate::schema::definitions::<u64>",
+        )))
+    )]
+    pub min_report_interval: U64,
+    /// Nanoseconds the archived records are kept for before the shards
+    /// predating them are pruned by [`crate::PriceHistory::poke`]. Zero keeps
+    /// them until the owner prunes them.
+    #[serde(default)]
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<u64>",
+            definitions = "crate::schema::definitions::<u64>",
+        )))
+    )]
+    pub archive_retention: U64,
+    /// Whether a zero price is accepted, for an asset which may legitimately
+    /// hit zero; otherwise a zero is rejected, so it never drags the average
+    /// down.
+    #[serde(default)]
+    pub allow_zero: bool,
+    /// An upstream source is quarantined, i.e. left out of the cross-source
+    /// answers until reinstated, once this many of its prices in a row
+    /// deviate from the consensus of the other sources more than the
+    /// `quarantine_deviation_bps`, which is then required, up to 100000.
+    /// Zero disables the quarantine.
+    #[serde(default)]
+    pub quarantine_rounds: u16,
+    #[serde(default)]
+    pub quarantine_deviation_bps: u32,
+    /// Blocks a new average waits for before it becomes the official
+    /// answer, unless disputed in the meantime, up to 86400. Zero disables
+    /// the delay.
+    #[serde(default)]
+    pub finality_blocks: u32,
+    /// Whether the log returns between the consecutive records are stored
+    /// along with them, so the volatility isn't recalculated from the prices.
+    #[serde(default)]
+    pub log_returns: bool,
+    /// When set, the smoothed price is estimated by a Kalman filter updated
+    /// on every record.
+    #[serde(default)]
+    pub kalman: Option<KalmanConfig>,
+}
+
+impl Default for AssetConfig {
+    fn default() -> Self {
+        Self {
+            depth: DEFAULT_HISTORY_DEPTH,
+            heartbeat: U64(0),
+            max_deviation_bps: 0,
+            archive: false,
+            dedup_tolerance_bps: None,
+            archive_encoding: ArchiveEncoding::Full,
+            one_per_block: false,
+            max_timestamp_skew: U64(0),
+            alert_deviation_bps: 0,
+            short_depth: None,
+            min_report_interval: U64(0),
+            archive_retention: U64(0),
+            allow_zero: false,
+            quarantine_rounds: 0,
+            quarantine_deviation_bps: 0,
+            finality_blocks: 0,
+            log_returns: false,
+            kalman: None,
+        }
+    }
+}
+
+/// Changes to some of the tunables of an asset, the missing ones are kept.
+/// An explicit `null` clears an optional tunable.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde", default)]
+pub struct AssetConfigPatch {
+    pub depth: Option<u16>,
+    pub heartbeat: Option<U64>,
+    pub max_deviation_bps: Option<u32>,
+    pub archive: Option<bool>,
+    #[serde(
+        deserialize_with = "double_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub dedup_tolerance_bps: Option<Option<u32>>,
+    pub archive_encoding: Option<ArchiveEncoding>,
+    pub one_per_block: Option<bool>,
+    pub max_timestamp_skew: Option<U64>,
+    pub alert_deviation_bps: Option<u32>,
+    #[serde(
+        deserialize_with = "double_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub short_depth: Option<Option<u16>>,
+    pub min_report_interval: Option<U64>,
+    pub archive_retention: Option<U64>,
+    pub allow_zero: Option<bool>,
+    pub quarantine_rounds: Option<u16>,
+    pub quarantine_deviation_bps: Option<u32>,
+    pub finality_blocks: Option<u32>,
+    pub log_returns: Option<bool>,
+    #[serde(
+        deserialize_with = "double_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub kalman: Option<Option<KalmanConfig>>,
+}
+
+/// Tells an explicit `null` from a missing field.
+fn double_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+impl AssetConfig {
+    /// Returns the configuration with the `patch` applied.
+    pub fn patched(&self, patch: AssetConfigPatch) -> Self {
+        Self {
+            depth: patch.depth.unwrap_or(self.depth),
+            heartbeat: patch.heartbeat.unwrap_or(self.heartbeat),
+            max_deviation_bps: patch.max_deviation_bps.unwrap_or(self.max_deviation_bps),
+            archive: patch.archive.unwrap_or(self.archive),
+            dedup_tolerance_bps: patch
+                .dedup_tolerance_bps
+                .unwrap_or(self.dedup_tolerance_bps),
+            archive_encoding: patch.archive_encoding.unwrap_or(self.archive_encoding),
+            one_per_block: patch.one_per_block.unwrap_or(self.one_per_block),
+            max_timestamp_skew: patch.max_timestamp_skew.unwrap_or(self.max_timestamp_skew),
+            alert_deviation_bps: patch
+                .alert_deviation_bps
+                .unwrap_or(self.alert_deviation_bps),
+            short_depth: patch.short_depth.unwrap_or(self.short_depth),
+            min_report_interval: patch
+                .min_report_interval
+                .unwrap_or(self.min_report_interval),
+            archive_retention: patch.archive_retention.unwrap_or(self.archive_retention),
+            allow_zero: patch.allow_zero.unwrap_or(self.allow_zero),
+            quarantine_rounds: patch.quarantine_rounds.unwrap_or(self.quarantine_rounds),
+            quarantine_deviation_bps: patch
+                .quarantine_deviation_bps
+                .unwrap_or(self.quarantine_deviation_bps),
+            finality_blocks: patch.finality_blocks.unwrap_or(self.finality_blocks),
+            log_returns: patch.log_returns.unwrap_or(self.log_returns),
+            kalman: patch.kalman.unwrap_or(self.kalman),
+        }
+    }
+
+    /// Checks whether the configuration is valid.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let heartbeat = self.heartbeat.0;
+        if self.depth == 0 || usize::from(self.depth) > MAX_HISTORY_DEPTH {
+            Err("History depth must be within 1..=4096")
+        } else if matches!(self.short_depth, Some(short) if short == 0 || short >= self.depth) {
+            Err("Short depth must be within 1..depth")
+        } else if self.max_deviation_bps > MAX_DEVIATION_BPS
+            || self.alert_deviation_bps > MAX_DEVIATION_BPS
+            || self.quarantine_deviation_bps > MAX_DEVIATION_BPS
+        {
+            Err("Deviations must be within 0..=100000 bps")
+        } else if self.quarantine_rounds != 0 && self.quarantine_deviation_bps == 0 {
+            Err("The quarantine needs a deviation")
+        } else if matches!(self.dedup_tolerance_bps, Some(tolerance) if tolerance >= 10_000) {
+            Err("Dedup tolerance must be within 0..10000 bps")
+        } else if heartbeat != 0 && self.max_timestamp_skew.0 >= heartbeat {
+            Err("The timestamp skew must be below the heartbeat")
+        } else if heartbeat != 0 && self.min_report_interval.0 >= heartbeat {
+            Err("The report interval must be below the heartbeat")
+        } else if self.finality_blocks > MAX_FINALITY_BLOCKS {
+            Err("Finality must be within 0..=86400 blocks")
+        } else if self.archive && !cfg!(feature = "archive") {
+            Err("The archive is not supported by this build")
+        } else if let Some(kalman) = self.kalman {
+            kalman.validate()
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A recorded price, serialized as a [`HistoryEntry`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize),
+    serde(crate = "near_sdk::serde", into = "HistoryEntry")
+)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct PriceRecord {
+    pub price: Price,
+    /// Block timestamp of the record, in nanoseconds.
+    pub timestamp: u64,
+}
+
+/// The log return between two consecutive records.
+#[derive(Debug, Clone, Copy, PartialEq, BorshDeserialize, BorshSerialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct LogReturn {
+    /// Natural logarithm of the ratio of the prices.
+    pub value: f64,
+    /// Nanoseconds between the records.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<u64>",
+            definitions = "crate::schema::definitions::<u64>",
+        )))
+    )]
+    pub elapsed: U64,
+}
+
+impl LogReturn {
+    /// Returns the log return from the `previous` record to the `record`,
+    /// unless either price is zero.
+    pub fn between(previous: PriceRecord, record: PriceRecord) -> Option<Self> {
+        let ratio = record.price.get() / previous.price.get();
+        (ratio.is_finite() && ratio > 0.).then(|| Self {
+            value: ratio.ln(),
+            elapsed: record.timestamp.saturating_sub(previous.timestamp).into(),
+        })
+    }
+}
+
+/// The average as of the start of an epoch.
+#[derive(Debug, Default, Clone, Copy, PartialEq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Snapshot {
+    pub epoch: u64,
+    pub average: Fixed,
+    /// Timestamp of the first record of the epoch, in nanoseconds.
+    pub timestamp: u64,
+}
+
+/// The average as of the start of an epoch, as returned by the views.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EpochSnapshot {
+    pub epoch: U64,
+    pub average: f64,
+    /// Timestamp of the first record of the epoch, in nanoseconds.
+    pub timestamp: U64,
+}
+
+impl From<Snapshot> for EpochSnapshot {
+    fn from(snapshot: Snapshot) -> Self {
+        Self {
+            epoch: snapshot.epoch.into(),
+            average: snapshot.average.to_f64(),
+            timestamp: snapshot.timestamp.into(),
+        }
+    }
+}
+
+/// Summary of a registered asset, as returned by the views.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AssetInfo {
+    pub symbol: String,
+    pub decimals: u8,
+    pub metadata: AssetMetadata,
+    /// Whether the asset is not accepting any records.
+    pub frozen: bool,
+}
+
+/// How much an answer can be relied upon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Quality {
+    Full,
+    /// The latest record has been aggregated from fewer submissions than the
+    /// quorum requires, or the asset is frozen.
+    Degraded,
+    /// No price has been recorded within the heartbeat.
+    Stale,
+}
+
+/// The average price of an asset along with its freshness.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AverageInfo {
+    pub symbol: String,
+    /// The average, if enough historical data has been collected.
+    pub average: Option<f64>,
+    /// Timestamp of the latest record, in nanoseconds.
+    pub updated_at: Option<U64>,
+    /// Whether no price has been recorded within the heartbeat.
+    pub stale: bool,
+    pub quality: Quality,
+}
+
+/// The latest price of an asset.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LatestPrice {
+    pub symbol: String,
+    pub price: Option<Price>,
+    /// Timestamp of the price, in nanoseconds.
+    pub timestamp: Option<U64>,
+    /// Combined standard error of the submissions of the price, if any came
+    /// with a confidence.
+    pub confidence: Option<f64>,
+    /// Whether no price has been recorded within the heartbeat.
+    pub stale: bool,
+    pub quality: Quality,
+}
+
+/// Prices of an asset on either side of the average, as returned by the
+/// views.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceBand {
+    pub symbol: String,
+    /// The average less the deviations, e.g. for valuing a collateral.
+    pub conservative: f64,
+    /// The average.
+    pub mid: f64,
+    /// The average plus the deviations, e.g. for valuing a debt.
+    pub aggressive: f64,
+    /// Standard deviation of the prices within the configured depth.
+    pub volatility: f64,
+}
+
+/// A price extrapolated by the linear trend of the window, as returned by
+/// the views. An estimate, not a recorded price.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Forecast {
+    pub symbol: String,
+    /// The moment of the estimate, in nanoseconds.
+    pub timestamp: U64,
+    /// The estimated price.
+    pub estimate: f64,
+    /// The estimate less the deviations of the prediction error.
+    pub lower: f64,
+    /// The estimate plus the deviations of the prediction error.
+    pub upper: f64,
+    /// Change of the trend per second.
+    pub slope: f64,
+    /// Standard error of the prediction.
+    pub standard_error: f64,
+}
+
+/// The instantaneous and the smoothed answers of an asset, as returned by
+/// the views.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Answers {
+    pub symbol: String,
+    /// The latest price.
+    pub spot: Option<Price>,
+    /// Timestamp of the latest price, in nanoseconds.
+    pub updated_at: Option<U64>,
+    /// Average of the prices within the configured depth.
+    pub average: Option<f64>,
+    /// Time-weighted average price over the `twap_period`, unless the
+    /// observations don't reach that far back.
+    pub twap: Option<f64>,
+    /// Seconds the `twap` is calculated over.
+    pub twap_period: u64,
+    /// Whether no price has been recorded within the heartbeat.
+    pub stale: bool,
+    pub quality: Quality,
+}
+
+/// Statistics of the prices within the configured depth, as returned by the
+/// views.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceStatistics {
+    pub symbol: String,
+    pub average: f64,
+    pub min: Price,
+    pub max: Price,
+    /// Population variance of the prices.
+    pub variance: f64,
+    /// Whether no price has been recorded within the heartbeat.
+    pub stale: bool,
+    pub quality: Quality,
+}
+
+/// Running totals of the prices within the configured depth, updated as the
+/// prices enter and leave the window.
+#[derive(Debug, Default, Clone, Copy, PartialEq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Statistics {
+    count: u16,
+    sum: Fixed,
+    sum_of_squares: WideFixed,
+}
+
+impl Statistics {
+    /// Calculates the statistics of the `prices`.
+    fn of(prices: impl Iterator<Item = Price>) -> Self {
+        prices.fold(Self::default(), |mut statistics, price| {
+            statistics.add(price);
+            statistics
+        })
+    }
+
+    /// # Panics
+    ///
+    /// Will panic when the sum overflows, which the limits of the prices and
+    /// the depth rule out.
+    fn add(&mut self, price: Price) {
+        // Hint: a full window of the maximum prices fits, so does any sum.
+        static_assertions::const_assert!(match (MAX_PRICE as u128).checked_mul(Fixed::ONE.raw()) {
+            Some(max) => max.checked_mul(MAX_HISTORY_DEPTH as u128).is_some(),
+            None => false,
+        });
+        self.count += 1;
+        self.sum = self.sum.checked_add(price.fixed()).unwrap_or_else(|| {
+            ContractError::OutOfBounds("The sum of the window overflows").panic()
+        });
+        // Hint: a 256-bit sum of the squares never saturates either.
+        self.sum_of_squares = self
+            .sum_of_squares
+            .saturating_add(WideFixed::square(price.fixed()));
+    }
+
+    /// # Panics
+    ///
+    /// Will panic when the `price` hasn't been added.
+    fn remove(&mut self, price: Price) {
+        self.count -= 1;
+        self.sum = self
+            .sum
+            .checked_sub(price.fixed())
+            .unwrap_or_else(|| ContractError::OutOfBounds("The price isn't in the window").panic());
+        self.sum_of_squares = self
+            .sum_of_squares
+            .saturating_sub(WideFixed::square(price.fixed()));
+    }
+
+    fn average(&self) -> Option<Fixed> {
+        self.sum
+            .checked_div_int(u128::from(self.count), Rounding::Nearest)
+    }
+
+    /// Returns the population variance: the mean of the squares less the
+    /// square of the mean.
+    fn variance(&self) -> Option<WideFixed> {
+        let mean_of_squares =
+            self.sum_of_squares
+                .mul_div(1, u64::from(self.count), Rounding::Nearest)?;
+        Some(mean_of_squares.saturating_sub(WideFixed::square(self.average()?)))
+    }
+}
+
+/// Gas used by the records of an asset, up to storing them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct GasUsage {
+    pub latest: u64,
+    pub peak: u64,
+}
+
+impl GasUsage {
+    /// Accounts for the `gas` of a record.
+    pub fn record(&mut self, gas: u64) {
+        self.latest = gas;
+        self.peak = self.peak.max(gas);
+    }
+}
+
+/// A crossing of the average by the short-window average.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum Crossover {
+    /// The short-window average has risen above the average.
+    Golden,
+    /// The short-window average has fallen below the average.
+    Death,
+}
+
+/// A recorded price, as returned by the views.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HistoryEntry {
+    pub price: Price,
+    /// Timestamp of the price, in nanoseconds.
+    pub timestamp: U64,
+}
+
+impl From<&PriceRecord> for HistoryEntry {
+    fn from(record: &PriceRecord) -> Self {
+        Self {
+            price: record.price,
+            timestamp: record.timestamp.into(),
+        }
+    }
+}
+
+impl From<PriceRecord> for HistoryEntry {
+    fn from(record: PriceRecord) -> Self {
+        Self::from(&record)
+    }
+}
+
+/// The latest price of an upstream source, as returned by the views.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SourcePrice {
+    pub source_id: String,
+    pub price: Price,
+    /// Timestamp of the price, in nanoseconds.
+    pub timestamp: U64,
+}
+
+/// The latest price of an upstream source compared to the cross-source
+/// median, as returned by the views.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SourceDivergence {
+    pub source_id: String,
+    pub price: Price,
+    /// Timestamp of the price, in nanoseconds.
+    pub timestamp: U64,
+    /// Nanoseconds since the price, as of the block.
+    pub age: U64,
+    /// Deviation of the price from the weighted median of the sources not
+    /// quarantined, in basis points.
+    pub deviation_bps: u32,
+    /// Whether the source is left out of the cross-source answers.
+    pub quarantined: bool,
+}
+
+/// The retained records of an asset, as returned by the views.
+#[cfg(feature = "serde")]
+pub type History = Latest<PriceRecord>;
+/// The retained records of an asset, as returned by the views.
+#[cfg(not(feature = "serde"))]
+pub type History = Vec<HistoryEntry>;
+
+/// The order of the records returned by the views.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum Order {
+    /// From the oldest to the newest, the order they have been recorded in.
+    #[default]
+    OldestFirst,
+    /// From the newest to the oldest.
+    NewestFirst,
+}
+
+/// An interval during which no price has been recorded, as returned by the
+/// views.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Gap {
+    /// Timestamp of the record before the gap, in nanoseconds.
+    pub from: U64,
+    /// Timestamp of the record after the gap, in nanoseconds.
+    pub to: U64,
+}
+
+/// The price as of a moment, as returned by the views.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceAtTime {
+    pub price: Price,
+    /// Timestamp of the price, in nanoseconds.
+    pub timestamp: U64,
+    /// Whether the requested moment is outside of the recorded ones, so the
+    /// price is that of the nearest record instead of an interpolated one.
+    pub nearest: bool,
+}
+
+/// The return of an asset over a period, as returned by the views.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PeriodReturn {
+    /// The price as of the start of the period.
+    pub start: PriceAtTime,
+    /// The price as of the end of the period.
+    pub end: PriceAtTime,
+    /// The change of the price per the starting one, e.g. `0.1` for 10%.
+    pub simple: f64,
+    /// Natural logarithm of the ratio of the prices.
+    pub log: f64,
+}
+
+/// The moving average convergence divergence of an asset, as returned by the
+/// views.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Macd {
+    /// The fast exponential moving average less the slow one.
+    pub macd: f64,
+    /// The exponential moving average of the MACD.
+    pub signal: f64,
+    /// The MACD less the signal.
+    pub histogram: f64,
+}
+
+/// A recorded price along with the account which has reported it, as
+/// returned by the views.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AttributedEntry {
+    pub price: Price,
+    /// Timestamp of the price, in nanoseconds.
+    pub timestamp: U64,
+    pub reporter: AccountId,
+}
+
+/// A registered asset along with its price history.
+#[derive(BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Asset {
+    /// Amount of decimals of the fixed-point representation of the prices.
+    pub decimals: u8,
+    /// Only needed by the views, so stored separately.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::LazyOption>",
+            definitions = "crate::schema::definitions::<crate::schema::LazyOption>",
+        )))
+    )]
+    metadata: LazyOption<AssetMetadata>,
+    config: AssetConfig,
+    /// Whether the asset is not accepting any records.
+    pub frozen: bool,
+    /// The account which has paid for the storage of the asset.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<String>",
+            definitions = "crate::schema::definitions::<String>",
+        )))
+    )]
+    pub storage_payer: AccountId,
+    /// Stored in its own slots, so only the accessed records are loaded.
+    price_history: Ring<PriceRecord>,
+    /// Accounts which have reported the records of the `price_history`, slot
+    /// by slot.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::ring::Ring<String>>",
+            definitions = "crate::schema::definitions::<crate::ring::Ring<String>>",
+        )))
+    )]
+    reporters: Ring<AccountId>,
+    /// Submissions the records of the `price_history` have been aggregated
+    /// from, slot by slot.
+    submissions: Ring<Vec<Submission>>,
+    /// Amount of valid records in the `price_history`.
+    recorded: u16,
+    /// Number of prices accepted over the lifetime of the asset, the round
+    /// of the newest one, which never decreases.
+    round: u64,
+    /// Never reset, unlike the history.
+    pub accumulator: Accumulator,
+    /// Never reset, unlike the history. Stored separately, since only the
+    /// records and the observation views need it.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::LazyOption>",
+            definitions = "crate::schema::definitions::<crate::schema::LazyOption>",
+        )))
+    )]
+    observations: LazyOption<Observations>,
+    /// Statistics of the window, updated whenever it changes, so neither the
+    /// views nor the records go through the history.
+    statistics: Statistics,
+    lows: Extremum,
+    highs: Extremum,
+    /// Records beyond the retained history, if configured.
+    pub archive: Archive,
+    /// Height of the block the latest record has been accepted in.
+    last_block: Option<u64>,
+    /// Statistics of the short window, if configured.
+    short_statistics: Statistics,
+    /// Whether the short-window average has been above the average, as of
+    /// the latest record when they differed.
+    short_above: Option<bool>,
+    /// Snapshots of the average, ordered by the epochs.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::Vector>",
+            definitions = "crate::schema::definitions::<crate::schema::Vector>",
+        )))
+    )]
+    snapshots: Vector<Snapshot>,
+    /// The epoch of the latest record.
+    epoch: Option<u64>,
+    /// Submissions waiting for the quorum.
+    pub pending: PendingRound,
+    /// Timestamp of the latest record as of which a missed heartbeat has
+    /// been reported.
+    missed_heartbeat: Option<u64>,
+    /// Gas used by the records, for the profiling.
+    pub record_gas: GasUsage,
+    /// The latest price of every upstream source, by its identifier.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::UnorderedMap>",
+            definitions = "crate::schema::definitions::<crate::schema::UnorderedMap>",
+        )))
+    )]
+    sources: UnorderedMap<String, PriceRecord>,
+    /// Prices in a row of every source deviating from the consensus.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::LookupMap>",
+            definitions = "crate::schema::definitions::<crate::schema::LookupMap>",
+        )))
+    )]
+    source_strikes: LookupMap<String, u16>,
+    /// Sources left out of the cross-source answers until reinstated.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::UnorderedSet>",
+            definitions = "crate::schema::definitions::<crate::schema::UnorderedSet>",
+        )))
+    )]
+    quarantined: UnorderedSet<String>,
+    /// The official answer, if delayed.
+    finality: Finality,
+    /// Log returns between the latest records, if configured.
+    log_returns: Ring<LogReturn>,
+    /// Amount of the valid log returns, all of them between the latest
+    /// consecutive records.
+    log_returns_recorded: u16,
+    /// The smoothed price, if configured.
+    kalman: Option<Kalman>,
+    /// Timestamp of the latest bridged record, kept through the resets so an
+    /// attestation is never recorded twice.
+    bridged_at: u64,
+    /// Rounds of the records of the `price_history`, slot by slot, since the
+    /// rewound ones are never reused.
+    rounds: Ring<u64>,
+    /// Timestamp of the latest submission of every reporter, while the
+    /// submissions are rate limited.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::UnorderedMap>",
+            definitions = "crate::schema::definitions::<crate::schema::UnorderedMap>",
+        )))
+    )]
+    reported_at: UnorderedMap<AccountId, u64>,
+}
+
+impl Asset {
+    /// Creates an asset with an empty history; the history and the rarely
+    /// needed data are stored under the `prefix`.
+    pub fn new<S: IntoStorageKey>(
+        decimals: u8,
+        metadata: AssetMetadata,
+        config: AssetConfig,
+        storage_payer: AccountId,
+        prefix: S,
+    ) -> Self {
+        let prefix = prefix.into_storage_key();
+        let capacity = RETAINED_HISTORY.max(u64::from(config.depth));
+        Self {
+            decimals,
+            metadata: LazyOption::new([&prefix[..], b"m"].concat(), Some(&metadata)),
+            config,
+            frozen: false,
+            storage_payer,
+            price_history: Ring::new([&prefix[..], b"r"].concat(), capacity),
+            reporters: Ring::new([&prefix[..], b"p"].concat(), capacity),
+            submissions: Ring::new([&prefix[..], b"s"].concat(), capacity),
+            recorded: 0,
+            round: 0,
+            accumulator: Accumulator::default(),
+            observations: LazyOption::new(
+                [&prefix[..], b"o"].concat(),
+                Some(&Observations::default()),
+            ),
+            statistics: Statistics::default(),
+            lows: Extremum::new(Kind::Min, [&prefix[..], b"l"].concat()),
+            highs: Extremum::new(Kind::Max, [&prefix[..], b"h"].concat()),
+            archive: Archive::new([&prefix[..], b"a"].concat()),
+            last_block: None,
+            short_statistics: Statistics::default(),
+            short_above: None,
+            snapshots: Vector::new([&prefix[..], b"e"].concat()),
+            epoch: None,
+            pending: PendingRound::default(),
+            missed_heartbeat: None,
+            record_gas: GasUsage::default(),
+            sources: UnorderedMap::new([&prefix[..], b"c"].concat()),
+            source_strikes: LookupMap::new([&prefix[..], b"k"].concat()),
+            quarantined: UnorderedSet::new([&prefix[..], b"q"].concat()),
+            finality: Finality::default(),
+            log_returns: Ring::new([&prefix[..], b"g"].concat(), capacity),
+            log_returns_recorded: 0,
+            kalman: None,
+            bridged_at: 0,
+            rounds: Ring::new([&prefix[..], b"n"].concat(), capacity),
+            reported_at: UnorderedMap::new([&prefix[..], b"t"].concat()),
+        }
+    }
+
+    /// Returns the metadata.
+    pub fn metadata(&self) -> AssetMetadata {
+        self.metadata.get().unwrap_or_else(|| unreachable!())
+    }
+
+    /// Returns the observations of the cumulative price.
+    pub fn observations(&self) -> Observations {
+        self.observations.get().unwrap_or_default()
+    }
+
+    /// Raises the maximum amount of the observations.
+    pub fn grow_observations(&mut self, cardinality: u16) {
+        let mut observations = self.observations();
+        observations.grow(cardinality);
+        self.observations.set(&observations);
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> &AssetConfig {
+        &self.config
+    }
+
+    /// Replaces the configuration.
+    pub fn set_config(&mut self, config: AssetConfig) {
+        self.price_history.reserve(u64::from(config.depth));
+        self.reporters.reserve(u64::from(config.depth));
+        self.submissions.reserve(u64::from(config.depth));
+        self.log_returns.reserve(u64::from(config.depth));
+        self.rounds.reserve(u64::from(config.depth));
+        if config.kalman.is_none() {
+            self.kalman = None;
+        }
+        self.config = config;
+        self.refresh_statistics();
+    }
+
+    /// Returns the summary of the asset.
+    pub fn info(&self, symbol: String) -> AssetInfo {
+        AssetInfo {
+            symbol,
+            decimals: self.decimals,
+            metadata: self.metadata(),
+            frozen: self.frozen,
+        }
+    }
+
+    /// Returns the average price as of the `now` timestamp, with the
+    /// `min_submissions` of the quorum.
+    pub fn average_info(&self, symbol: String, min_submissions: u16, now: u64) -> AverageInfo {
+        AverageInfo {
+            symbol,
+            average: self.average().map(Fixed::to_f64),
+            updated_at: self.latest().map(|record| record.timestamp.into()),
+            stale: self.is_stale(now),
+            quality: self.quality(min_submissions, now),
+        }
+    }
+
+    /// Returns the latest price as of the `now` timestamp, with the
+    /// `min_submissions` of the quorum.
+    pub fn latest_price(&self, symbol: String, min_submissions: u16, now: u64) -> LatestPrice {
+        let latest = self.latest();
+        LatestPrice {
+            symbol,
+            price: latest.map(|record| record.price),
+            timestamp: latest.map(|record| record.timestamp.into()),
+            confidence: self.confidence().map(Fixed::to_f64),
+            stale: self.is_stale(now),
+            quality: self.quality(min_submissions, now),
+        }
+    }
+
+    /// Returns the combined standard error of the submissions of the latest
+    /// retained record, if any came with a confidence.
+    pub fn confidence(&self) -> Option<Fixed> {
+        if self.recorded == 0 {
+            return None;
+        }
+        round::confidence(&self.submissions.get(0)?)
+    }
+
+    /// Rates the answers as of the `now` timestamp, with the
+    /// `min_submissions` of the quorum.
+    pub fn quality(&self, min_submissions: u16, now: u64) -> Quality {
+        // Hint: an asset without records has no answers to degrade.
+        let below_quorum = self.recorded != 0
+            && self
+                .submissions
+                .get(0)
+                .is_some_and(|submissions| submissions.len() < usize::from(min_submissions));
+        if self.is_stale(now) {
+            Quality::Stale
+        } else if self.frozen || below_quorum {
+            Quality::Degraded
+        } else {
+            Quality::Full
+        }
+    }
+
+    /// Adds the record, aggregated from the `submissions`, to the history and
+    /// returns the new round number.
+    pub fn push(
+        &mut self,
+        record: PriceRecord,
+        reporter: &AccountId,
+        submissions: Vec<Submission>,
+    ) -> u64 {
+        let depth = self.depth_so_far();
+        let oldest = depth
+            .checked_sub(1)
+            .and_then(|age| self.price_history.get(age as u64));
+        let short_depth = self.short_depth_so_far();
+        let short_oldest = short_depth
+            .checked_sub(1)
+            .and_then(|age| self.price_history.get(age as u64));
+        let log_return = self
+            .latest()
+            .filter(|_| self.config.log_returns)
+            .and_then(|previous| LogReturn::between(previous, record));
+        self.price_history.push(&record);
+        self.reporters.push(reporter);
+        self.submissions.push(&submissions);
+        // Hint: a gap breaks the log returns, so they're always consecutive.
+        match log_return {
+            Some(log_return) => {
+                self.log_returns.push(&log_return);
+                self.log_returns_recorded =
+                    (self.log_returns_recorded + 1).min(self.log_returns.len() as u16);
+            }
+            None => self.log_returns_recorded = 0,
+        }
+        self.kalman = self.config.kalman.map(|config| {
+            let value = record.price.get();
+            match self.kalman {
+                Some(mut kalman) => {
+                    kalman.update(config, value, record.timestamp);
+                    kalman
+                }
+                None => Kalman::new(config, value, record.timestamp),
+            }
+        });
+        // Hint: no overflow check required until the const assert holds true,
+        // since the history never exceeds the maximum depth.
+        static_assertions::const_assert!(MAX_HISTORY_DEPTH < u16::MAX as usize);
+        self.recorded = (self.recorded + 1).min(self.price_history.len() as u16);
+
+        // Hint: unless the window has grown, its oldest record has left it.
+        if let Some(oldest) = oldest.filter(|_| self.depth_so_far() == depth) {
+            self.statistics.remove(oldest.price);
+        }
+        self.statistics.add(record.price);
+        if let Some(oldest) = short_oldest.filter(|_| self.short_depth_so_far() == short_depth) {
+            self.short_statistics.remove(oldest.price);
+        }
+        if self.config.short_depth.is_some() {
+            self.short_statistics.add(record.price);
+        }
+        self.round += 1;
+        self.rounds.push(&self.round);
+        let oldest_round = self.oldest_round();
+        for extremum in [&mut self.lows, &mut self.highs] {
+            extremum.push(record.price, self.round);
+            extremum.evict_before(oldest_round);
+        }
+        #[cfg(feature = "archive")]
+        if self.config.archive {
+            // Hint: after a reset or a rewind, the archive may keep records
+            // newer than the history.
+            if self
+                .archive
+                .newest()
+                .is_some_and(|newest| newest.timestamp > record.timestamp)
+            {
+                self.archive
+                    .insert(record, self.config.archive_encoding, self.decimals);
+            } else {
+                self.archive
+                    .push(record, self.config.archive_encoding, self.decimals);
+            }
+        }
+        self.accumulator.record(record.price, record.timestamp);
+        let mut observations = self.observations();
+        observations.write(Observation {
+            timestamp: record.timestamp,
+            price_cumulative: self.accumulator.cumulative_at(record.timestamp),
+        });
+        self.observations.set(&observations);
+        self.round
+    }
+
+    /// Checks whether the `price` repeats the latest one within the
+    /// configured tolerance, if any.
+    pub fn is_repeated(&self, price: Price) -> bool {
+        match (self.config.dedup_tolerance_bps, self.latest()) {
+            (Some(tolerance_bps), Some(latest)) => {
+                within_bps(price.fixed(), latest.price.fixed(), tolerance_bps)
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks whether a submission is accepted in the block of the
+    /// `block_height`: unless only one record per block is allowed and the
+    /// block already has one. The submissions to a pending round don't count.
+    pub fn accepts_block(&self, block_height: u64) -> bool {
+        !self.config.one_per_block || self.last_block != Some(block_height)
+    }
+
+    /// Remembers the block of a new record.
+    pub fn record_block(&mut self, block_height: u64) {
+        self.last_block = Some(block_height);
+    }
+
+    /// Persists a snapshot of the average, if any, when the `epoch` of a new
+    /// record differs from that of the previous one.
+    pub fn snapshot_epoch(&mut self, epoch: u64, timestamp: u64) {
+        if self.epoch.replace(epoch) == Some(epoch) {
+            return;
+        }
+        if let Some(average) = self.average() {
+            self.snapshots.push(&Snapshot {
+                epoch,
+                average,
+                timestamp,
+            });
+        }
+    }
+
+    /// Returns the snapshot of the average as of the start of the `epoch`,
+    /// looking it up with a binary search.
+    pub fn epoch_snapshot(&self, epoch: u64) -> Option<Snapshot> {
+        let (mut low, mut high) = (0, self.snapshots.len());
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let snapshot = self.snapshots.get(middle)?;
+            match snapshot.epoch.cmp(&epoch) {
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+                std::cmp::Ordering::Equal => return Some(snapshot),
+            }
+        }
+        None
+    }
+
+    /// Returns the amount of the stored epoch snapshots.
+    pub fn snapshots_len(&self) -> u64 {
+        self.snapshots.len()
+    }
+
+    /// Moves the latest record, if any, to the `timestamp`, keeping its
+    /// price, instead of adding a repeated one.
+    pub fn refresh(&mut self, timestamp: u64) {
+        let mut latest = match self.latest() {
+            Some(latest) => latest,
+            None => return,
+        };
+        let elapsed = timestamp.saturating_sub(latest.timestamp);
+        latest.timestamp = timestamp;
+        self.price_history.replace_newest(&latest);
+        if let Some(mut log_return) = self
+            .log_returns
+            .get(0)
+            .filter(|_| self.log_returns_recorded != 0)
+        {
+            log_return.elapsed = (log_return.elapsed.0 + elapsed).into();
+            self.log_returns.replace_newest(&log_return);
+        }
+        self.accumulator.record(latest.price, timestamp);
+        let mut observations = self.observations();
+        observations.write(Observation {
+            timestamp,
+            price_cumulative: self.accumulator.cumulative_at(timestamp),
+        });
+        self.observations.set(&observations);
+    }
+
+    /// Forgets the history and returns the amount of discarded samples.
+    pub fn reset(&mut self) -> usize {
+        let discarded = self.depth_so_far();
+        self.recorded = 0;
+        self.log_returns_recorded = 0;
+        self.kalman = None;
+        self.refresh_statistics();
+        discarded
+    }
+
+    /// Drops the `count` newest records, restoring the window from the older
+    /// retained ones, as of the `now` timestamp. The rounds of the dropped
+    /// records aren't reused. The archive,
+    /// the cumulative price and its observations keep the dropped records.
+    ///
+    /// Returns `false`, changing nothing, when fewer records are retained.
+    pub fn rewind(&mut self, count: u16, now: u64) -> bool {
+        if count > self.recorded {
+            return false;
+        }
+        self.price_history.rewind(u64::from(count));
+        self.reporters.rewind(u64::from(count));
+        self.submissions.rewind(u64::from(count));
+        let log_returns = count.min(self.log_returns_recorded);
+        self.log_returns.rewind(u64::from(log_returns));
+        self.log_returns_recorded -= log_returns;
+        // Hint: the filter can't be rewound, it restarts at the next record.
+        self.kalman = None;
+        self.rounds.rewind(u64::from(count));
+        self.recorded -= count;
+        self.refresh_statistics();
+        // Hint: from now on the cumulative price grows by the restored
+        // latest price.
+        if let Some(latest) = self.latest() {
+            self.accumulator.record(latest.price, now);
+        }
+        true
+    }
+
+    /// Returns the round of the oldest record of the window, or the next one
+    /// if the window is empty.
+    fn oldest_round(&self) -> u64 {
+        match self.depth_so_far().checked_sub(1) {
+            Some(age) => self.rounds.get(age as u64).unwrap_or(self.round + 1),
+            None => self.round + 1,
+        }
+    }
+
+    /// Returns the depth of the recorded history, up to the short depth, if
+    /// configured.
+    fn short_depth_so_far(&self) -> usize {
+        self.config
+            .short_depth
+            .map_or(0, |short_depth| usize::from(self.recorded.min(short_depth)))
+    }
+
+    /// Calculates the short-window average, if configured and enough
+    /// historical data has been collected.
+    pub fn short_average(&self) -> Option<Fixed> {
+        let short_depth = self.config.short_depth?;
+        if self.short_depth_so_far() != usize::from(short_depth) {
+            return None;
+        }
+        self.short_statistics.average()
+    }
+
+    /// Checks whether the short-window average has crossed the average
+    /// since the previous check when they differed.
+    pub fn crossover(&mut self) -> Option<Crossover> {
+        let above = match self.short_average()?.cmp(&self.average()?) {
+            std::cmp::Ordering::Greater => true,
+            std::cmp::Ordering::Less => false,
+            std::cmp::Ordering::Equal => return None,
+        };
+        match self.short_above.replace(above) {
+            Some(previous) if previous != above => Some(if above {
+                Crossover::Golden
+            } else {
+                Crossover::Death
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the depth of the recorded history, up to the configured
+    /// depth.
+    pub fn depth_so_far(&self) -> usize {
+        usize::from(self.recorded.min(self.config.depth))
+    }
+
+    /// Returns the latest record, if any.
+    pub fn latest(&self) -> Option<PriceRecord> {
+        if self.recorded == 0 {
+            None
+        } else {
+            self.price_history.get(0)
+        }
+    }
+
+    /// Returns the amount of the stored records, including the discarded
+    /// ones.
+    pub fn history_len(&self) -> u64 {
+        self.price_history.len()
+    }
+
+    /// Returns the amount of the stored candidates for the extremes of the
+    /// window.
+    pub fn extreme_candidates(&self) -> u64 {
+        self.lows.len() + self.highs.len()
+    }
+
+    /// Removes everything stored under the prefix of the asset.
+    pub fn release(&mut self) {
+        self.price_history.clear();
+        self.reporters.clear();
+        self.submissions.clear();
+        self.rounds.clear();
+        self.log_returns.clear();
+        self.log_returns_recorded = 0;
+        self.kalman = None;
+        self.recorded = 0;
+        self.refresh_statistics();
+        self.metadata.remove();
+        self.observations.remove();
+        self.archive.clear();
+        self.snapshots.clear();
+        for source_id in self.sources.keys() {
+            self.source_strikes.remove(&source_id);
+        }
+        self.sources.clear();
+        self.quarantined.clear();
+        self.reported_at.clear();
+    }
+
+    /// Checks whether no price has been recorded within the heartbeat as of
+    /// the `now` timestamp.
+    pub fn is_stale(&self, now: u64) -> bool {
+        let heartbeat = self.config.heartbeat.0;
+        if heartbeat == 0 {
+            return false;
+        }
+        self.latest()
+            .is_none_or(|record| now.saturating_sub(record.timestamp) > heartbeat)
+    }
+
+    /// Returns the latest record if it has missed the heartbeat as of the
+    /// `now` timestamp and the miss has not been reported yet, and marks it
+    /// as reported.
+    pub fn report_missed_heartbeat(&mut self, now: u64) -> Option<PriceRecord> {
+        let latest = self.latest()?;
+        if !self.is_stale(now) || self.missed_heartbeat == Some(latest.timestamp) {
+            return None;
+        }
+        self.missed_heartbeat = Some(latest.timestamp);
+        Some(latest)
+    }
+
+    /// Inserts the `record` into the archive in the chronological order,
+    /// leaving the window as it is.
+    ///
+    /// Returns `false`, changing nothing, when the archive isn't kept.
+    #[cfg(feature = "archive")]
+    pub fn backfill(&mut self, record: PriceRecord) -> bool {
+        if !self.config.archive {
+            return false;
+        }
+        self.archive
+            .insert(record, self.config.archive_encoding, self.decimals);
+        true
+    }
+
+    /// Drops the archived records older than the configured retention as of
+    /// the `now` timestamp, by whole shards, and returns their amount.
+    pub fn prune_expired(&mut self, now: u64) -> u64 {
+        match self.config.archive_retention.0 {
+            0 => 0,
+            retention => self.archive.prune_before(now.saturating_sub(retention)),
+        }
+    }
+
+    /// Returns the latest records within the configured depth, from the
+    /// oldest to the newest. Only the records since the latest reset are
+    /// returned, so a partial window never includes the stale slots.
+    pub fn window(&self) -> impl Iterator<Item = PriceRecord> + '_ {
+        self.price_history.latest(self.depth_so_far() as u64)
+    }
+
+    /// Returns the time-weighted average price over the `seconds` before the
+    /// `now` timestamp, or `None` if the period is empty or predates the
+    /// oldest observation.
+    pub fn twap(&self, now: u64, seconds: u64) -> Option<Fixed> {
+        let period = seconds
+            .checked_mul(1_000_000_000)
+            .filter(|&period| period != 0)?;
+        let start = self
+            .observations()
+            .observe(now.checked_sub(period)?, &self.accumulator)?;
+        self.accumulator
+            .cumulative_at(now)
+            .saturating_sub(start)
+            .mul_div(1, period, Rounding::Nearest)?
+            .to_fixed()
+    }
+
+    /// Returns the instantaneous and the smoothed answers as of the `now`
+    /// timestamp, with the `min_submissions` of the quorum and the TWAP over
+    /// the `twap_period` seconds.
+    pub fn answers(
+        &self,
+        symbol: String,
+        min_submissions: u16,
+        now: u64,
+        twap_period: u64,
+    ) -> Answers {
+        let latest = self.latest();
+        Answers {
+            symbol,
+            spot: latest.map(|record| record.price),
+            updated_at: latest.map(|record| record.timestamp.into()),
+            average: self.average().map(Fixed::to_f64),
+            twap: self.twap(now, twap_period).map(Fixed::to_f64),
+            twap_period,
+            stale: self.is_stale(now),
+            quality: self.quality(min_submissions, now),
+        }
+    }
+
+    /// Returns the cumulative prices as of `seconds_ago` before the `now`
+    /// timestamp, or `None` if any of them predates the oldest observation.
+    pub fn observe(&self, now: u64, seconds_ago: &[u64]) -> Option<Vec<CumulativePrice>> {
+        let observations = self.observations();
+        seconds_ago
+            .iter()
+            .map(|&seconds| {
+                let target = now.checked_sub(seconds.checked_mul(1_000_000_000)?)?;
+                Some(CumulativePrice {
+                    price_cumulative: observations.observe(target, &self.accumulator)?.to_f64(),
+                    timestamp: target.into(),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the records of the window in the `order`.
+    pub fn history(&self, order: Order) -> Vec<HistoryEntry> {
+        let depth = self.depth_so_far() as u64;
+        let records: Box<dyn Iterator<Item = PriceRecord> + '_> = match order {
+            Order::OldestFirst => Box::new(self.price_history.latest(depth)),
+            Order::NewestFirst => Box::new(self.price_history.latest_rev(depth)),
+        };
+        records.map(|record| HistoryEntry::from(&record)).collect()
+    }
+
+    /// Keeps the `record` as the latest price of the source, comparing it
+    /// to the consensus of the other sources, as weighed by the `weight`.
+    ///
+    /// Returns the deviation from the consensus, in basis points, when the
+    /// source has just been quarantined. Fails when the identifier is empty
+    /// or too long, or when the source is new while there are already
+    /// [`MAX_SOURCES`] of them.
+    pub fn record_source(
+        &mut self,
+        source_id: &String,
+        record: PriceRecord,
+        weight: impl Fn(&String) -> u32,
+    ) -> Result<Option<u32>, ContractError> {
+        if source_id.is_empty() || source_id.len() > MAX_SOURCE_ID_LEN {
+            return Err(ContractError::OutOfBounds(
+                "The source identifier must be 1 to 32 bytes long",
+            ));
+        }
+        if self.sources.get(source_id).is_none() && self.sources.len() >= MAX_SOURCES {
+            return Err(ContractError::OutOfBounds("Too many sources"));
+        }
+        self.sources.insert(source_id, &record);
+        let rounds = self.config.quarantine_rounds;
+        if rounds == 0 || self.quarantined.contains(source_id) {
+            return Ok(None);
+        }
+        let consensus = weighted_median(self.weighted_prices(0, |other| {
+            if other == source_id {
+                0
+            } else {
+                weight(other)
+            }
+        }));
+        let deviation = consensus
+            .map(|consensus| deviation_bps(record.price.fixed(), consensus.fixed()))
+            .filter(|&deviation| deviation > self.config.quarantine_deviation_bps);
+        let Some(deviation) = deviation else {
+            self.source_strikes.remove(source_id);
+            return Ok(None);
+        };
+        let strikes = self.source_strikes.get(source_id).unwrap_or(0) + 1;
+        if strikes < rounds {
+            self.source_strikes.insert(source_id, &strikes);
+            return Ok(None);
+        }
+        self.source_strikes.remove(source_id);
+        self.quarantined.insert(source_id);
+        Ok(Some(deviation))
+    }
+
+    /// Lets the quarantined source back into the cross-source answers.
+    ///
+    /// Returns `false` when the source isn't quarantined.
+    pub fn reinstate_source(&mut self, source_id: &String) -> bool {
+        self.quarantined.remove(source_id)
+    }
+
+    /// Returns the quarantined sources.
+    pub fn quarantined_sources(&self) -> Vec<String> {
+        self.quarantined.to_vec()
+    }
+
+    /// Returns the latest price of every source, in no particular order.
+    pub fn source_prices(&self) -> Vec<SourcePrice> {
+        self.sources
+            .iter()
+            .map(|(source_id, record)| SourcePrice {
+                source_id,
+                price: record.price,
+                timestamp: record.timestamp.into(),
+            })
+            .collect()
+    }
+
+    /// Compares the latest price of every source to the weighted median of
+    /// those not quarantined, as of the `now` timestamp, in no particular
+    /// order.
+    pub fn source_divergence(
+        &self,
+        now: u64,
+        weight: impl Fn(&String) -> u32,
+    ) -> Vec<SourceDivergence> {
+        let Some(median) = self.cross_source_median(0, weight) else {
+            return Vec::new();
+        };
+        self.sources
+            .iter()
+            .map(|(source_id, record)| SourceDivergence {
+                quarantined: self.quarantined.contains(&source_id),
+                source_id,
+                price: record.price,
+                timestamp: record.timestamp.into(),
+                age: now.saturating_sub(record.timestamp).into(),
+                deviation_bps: deviation_bps(record.price.fixed(), median.fixed()),
+            })
+            .collect()
+    }
+
+    /// Returns the latest prices of the sources which have reported since
+    /// the `since` timestamp and aren't quarantined, from the lowest, with
+    /// their weights.
+    fn weighted_prices(&self, since: u64, weight: impl Fn(&String) -> u32) -> Vec<(Price, u32)> {
+        let mut prices: Vec<(Price, u32)> = self
+            .sources
+            .iter()
+            .filter(|(source_id, record)| {
+                record.timestamp >= since && !self.quarantined.contains(source_id)
+            })
+            .map(|(source_id, record)| (record.price, weight(&source_id)))
+            .collect();
+        prices.sort_unstable();
+        prices
+    }
+
+    /// Returns the weighted median of the latest prices of the sources which
+    /// have reported since the `since` timestamp, unless there are none or
+    /// all of them weigh nothing.
+    pub fn cross_source_median(
+        &self,
+        since: u64,
+        weight: impl Fn(&String) -> u32,
+    ) -> Option<Price> {
+        weighted_median(self.weighted_prices(since, weight))
+    }
+
+    /// Returns the weighted mean of the latest prices of the sources which
+    /// have reported since the `since` timestamp, unless there are none or
+    /// all of them weigh nothing.
+    pub fn cross_source_mean(&self, since: u64, weight: impl Fn(&String) -> u32) -> Option<Price> {
+        let prices = self.weighted_prices(since, weight);
+        let total: u64 = prices.iter().map(|&(_, weight)| u64::from(weight)).sum();
+        let sum = prices
+            .iter()
+            .fold(WideFixed::default(), |sum, &(price, weight)| {
+                sum.saturating_add(WideFixed::product(price.fixed(), u64::from(weight)))
+            });
+        // Hint: the mean never exceeds the highest price.
+        let mean = sum.mul_div(1, total, Rounding::Nearest)?.to_fixed()?;
+        Price::from_fixed(mean).ok()
+    }
+
+    /// Returns every retained record, from the oldest to the newest.
+    #[cfg(feature = "serde")]
+    pub fn full_history(self) -> History {
+        self.price_history.into_latest(u64::from(self.recorded))
+    }
+
+    /// Returns every retained record, from the oldest to the newest.
+    #[cfg(not(feature = "serde"))]
+    pub fn full_history(&self) -> History {
+        self.price_history
+            .latest(u64::from(self.recorded))
+            .map(|record| HistoryEntry::from(&record))
+            .collect()
+    }
+
+    /// Returns up to `limit` oldest intervals between the consecutive records
+    /// longer than the `expected_interval`, which end after the `from` and
+    /// begin before the `to` timestamps. The archive is scanned if it has
+    /// any records, the retained history otherwise, only as far as the range
+    /// and the `limit` go, and no further than [`MAX_SCANNED_RECORDS`].
+    pub fn gaps(&self, expected_interval: u64, from: u64, to: u64, limit: usize) -> Vec<Gap> {
+        let mut gaps = Vec::new();
+        let mut records = self.records_from(from).take(MAX_SCANNED_RECORDS);
+        let mut previous = None;
+        while gaps.len() < limit {
+            let Some(record) = records.next() else {
+                break;
+            };
+            let Some(start) = previous.replace(record.timestamp) else {
+                continue;
+            };
+            if start >= to {
+                break;
+            }
+            if record.timestamp > from && record.timestamp.saturating_sub(start) > expected_interval
+            {
+                gaps.push(Gap {
+                    from: start.into(),
+                    to: record.timestamp.into(),
+                });
+            }
+        }
+        gaps
+    }
+
+    /// Returns the price as of the `timestamp`, linearly interpolated between
+    /// the surrounding records, or that of the nearest record when there are
+    /// none on either side. The archive is searched, from the shard of the
+    /// record before the `timestamp` up to the first one not predating it,
+    /// if it has any records, the retained history otherwise.
+    pub fn price_at(&self, timestamp: u64) -> Option<PriceAtTime> {
+        let mut previous: Option<PriceRecord> = None;
+        for record in self.records_from(timestamp) {
+            if record.timestamp < timestamp {
+                previous = Some(record);
+                continue;
+            }
+            return Some(match previous {
+                Some(previous) if record.timestamp != timestamp => PriceAtTime {
+                    price: interpolate(previous, record, timestamp),
+                    timestamp: timestamp.into(),
+                    nearest: false,
+                },
+                _ => PriceAtTime {
+                    price: record.price,
+                    timestamp: record.timestamp.into(),
+                    nearest: record.timestamp != timestamp,
+                },
+            });
+        }
+        previous.map(|record| PriceAtTime {
+            price: record.price,
+            timestamp: record.timestamp.into(),
+            nearest: true,
+        })
+    }
+
+    /// Returns the return over the `window` nanoseconds before the `now`
+    /// timestamp, of the prices as of its ends, unless nothing has been
+    /// recorded or the starting price is zero.
+    pub fn period_return(&self, now: u64, window: u64) -> Option<PeriodReturn> {
+        let start = self.price_at(now.saturating_sub(window))?;
+        let end = self.price_at(now)?;
+        let ratio = end.price.get() / start.price.get();
+        if !ratio.is_finite() {
+            return None;
+        }
+        Some(PeriodReturn {
+            simple: ratio - 1.,
+            log: ratio.ln(),
+            start,
+            end,
+        })
+    }
+
+    /// Returns the price estimated by the Kalman filter, if configured and
+    /// recorded since.
+    pub fn smoothed_price(&self) -> Option<SmoothedPrice> {
+        self.kalman.map(SmoothedPrice::from)
+    }
+
+    /// Returns the MACD of the retained records as of the latest one, the
+    /// exponential moving averages over the `fast`, the `slow` and the
+    /// `signal` records seeded with the simple ones, or `None` unless at
+    /// least `slow + signal - 1` records are retained.
+    pub fn macd(&self, fast: u16, slow: u16, signal: u16) -> Option<Macd> {
+        let prices: Vec<_> = self
+            .price_history
+            .latest(u64::from(self.recorded))
+            .map(|record| record.price.get())
+            .collect();
+        let fast = ema(&prices, fast);
+        let slow = ema(&prices, slow);
+        // Hint: both end at the latest record, the slow one starts later.
+        let line: Vec<_> = fast[fast.len() - slow.len()..]
+            .iter()
+            .zip(&slow)
+            .map(|(fast, slow)| fast - slow)
+            .collect();
+        let signal = *ema(&line, signal).last()?;
+        let macd = *line.last()?;
+        Some(Macd {
+            macd,
+            signal,
+            histogram: macd - signal,
+        })
+    }
+
+    /// Returns the records of the archive if it has any, the retained
+    /// history otherwise, from the oldest to the newest. The archive is read
+    /// from the shard of the record before the `timestamp` on, see the
+    /// [`Archive::iter_from`].
+    fn records_from(&self, timestamp: u64) -> Box<dyn Iterator<Item = PriceRecord> + '_> {
+        if self.archive.len() != 0 {
+            Box::new(self.archive.iter_from(timestamp))
+        } else {
+            Box::new(self.price_history.latest(u64::from(self.recorded)))
+        }
+    }
+
+    /// Returns every retained record along with its reporter, from the oldest
+    /// to the newest.
+    pub fn attributed_history(&self) -> Vec<AttributedEntry> {
+        let recorded = u64::from(self.recorded);
+        self.price_history
+            .latest(recorded)
+            .zip(self.reporters.latest(recorded))
+            .map(|(record, reporter)| AttributedEntry {
+                price: record.price,
+                timestamp: record.timestamp.into(),
+                reporter,
+            })
+            .collect()
+    }
+
+    /// Returns the submissions the retained record of the `round` has been
+    /// aggregated from.
+    pub fn round_submissions(&self, round: u64) -> Option<Vec<Submission>> {
+        // Hint: the older the record, the lower its round, so the search
+        // reads a few slots only.
+        let (mut newer, mut older) = (0, u64::from(self.recorded));
+        while newer < older {
+            let age = newer + (older - newer) / 2;
+            if self.rounds.get(age)? > round {
+                newer = age + 1;
+            } else {
+                older = age;
+            }
+        }
+        if newer == u64::from(self.recorded) || self.rounds.get(newer)? != round {
+            return None;
+        }
+        self.submissions.get(newer)
+    }
+
+    /// Returns the amount of the stored slots of the submissions and the
+    /// bytes the submissions take, reading all of them.
+    pub fn submissions_len(&self) -> (u64, u64) {
+        let bytes = self
+            .submissions
+            .latest(self.submissions.len())
+            .map(|submissions| borsh::to_vec(&submissions).map_or(0, |value| value.len() as u64))
+            .sum();
+        (self.submissions.len(), bytes)
+    }
+
+    /// Returns the amount of bytes the accounts of the reporters take,
+    /// reading all of them.
+    pub fn reporters_len(&self) -> u64 {
+        self.reporters
+            .latest(self.reporters.len())
+            .map(|reporter| borsh::to_vec(&reporter).map_or(0, |value| value.len() as u64))
+            .sum()
+    }
+
+    /// Calculates the average price, if enough historical data has been
+    /// collected.
+    pub fn average(&self) -> Option<Fixed> {
+        if self.depth_so_far() != usize::from(self.config.depth) {
+            return None;
+        }
+        self.statistics.average()
+    }
+
+    /// Returns the average of the prices within the configured depth, those
+    /// below the lower `percent` of them and above the upper one clamped to
+    /// the bounds, if enough historical data has been collected.
+    pub fn winsorized_mean(&self, percent: u8) -> Option<Fixed> {
+        self.average()?;
+        let mut prices: Vec<_> = self.window().map(|record| record.price.fixed()).collect();
+        prices.sort_unstable();
+        let count = prices.len();
+        let clamped = count * usize::from(percent) / 100;
+        let (low, high) = (prices[clamped], prices[count - 1 - clamped]);
+        prices
+            .into_iter()
+            .map(|price| WideFixed::product(price.clamp(low, high), 1))
+            .fold(WideFixed::default(), WideFixed::saturating_add)
+            .mul_div(1, count as u64, Rounding::Nearest)?
+            .to_fixed()
+    }
+
+    /// Returns the standard deviation of the prices within the configured
+    /// depth, if enough historical data has been collected.
+    pub fn volatility(&self) -> Option<Fixed> {
+        self.average()?;
+        Some(self.statistics.variance()?.sqrt())
+    }
+
+    /// Returns the log returns between the records of the window, from the
+    /// oldest to the newest: the stored ones, if they cover the window, or
+    /// calculated from the prices otherwise, unless a price is zero.
+    pub fn window_log_returns(&self) -> Option<Vec<LogReturn>> {
+        let count = self.depth_so_far().saturating_sub(1);
+        if usize::from(self.log_returns_recorded) >= count {
+            return Some(self.log_returns.latest(count as u64).collect());
+        }
+        let mut previous: Option<PriceRecord> = None;
+        let mut log_returns = Vec::with_capacity(count);
+        for record in self.window() {
+            if let Some(previous) = previous {
+                log_returns.push(LogReturn::between(previous, record)?);
+            }
+            previous = Some(record);
+        }
+        Some(log_returns)
+    }
+
+    /// Returns the annualized volatility of the window: the root of the sum
+    /// of the squared log returns between the consecutive records per the
+    /// years they span, or `None` without two records apart in time or with
+    /// a zero price.
+    pub fn annualized_volatility(&self) -> Option<f64> {
+        let log_returns = self.window_log_returns()?;
+        let elapsed: u64 = log_returns
+            .iter()
+            .map(|log_return| log_return.elapsed.0)
+            .sum();
+        if elapsed == 0 {
+            return None;
+        }
+        let sum_of_squares: f64 = log_returns
+            .iter()
+            .map(|log_return| log_return.value.powi(2))
+            .sum();
+        let years = elapsed as f64 / 1e9 / SECONDS_PER_YEAR;
+        Some((sum_of_squares / years).sqrt())
+    }
+
+    /// Returns the beta of the asset versus the `reference` one: the
+    /// covariance of their log returns per the variance of those of the
+    /// reference. The returns are taken between the moments of the records of
+    /// either window within the period both of them span, the prices being
+    /// interpolated in between, so `None` is returned if the windows overlap
+    /// at a single moment, if the reference hasn't changed, or with a zero
+    /// price.
+    pub fn beta(&self, reference: &Asset) -> Option<f64> {
+        let window: Vec<_> = self.window().collect();
+        let reference: Vec<_> = reference.window().collect();
+        let start = window.first()?.timestamp.max(reference.first()?.timestamp);
+        let end = window.last()?.timestamp.min(reference.last()?.timestamp);
+        let mut moments: Vec<_> = window
+            .iter()
+            .chain(&reference)
+            .map(|record| record.timestamp)
+            .filter(|timestamp| (start..=end).contains(timestamp))
+            .collect();
+        moments.sort_unstable();
+        moments.dedup();
+        let log_returns = |records: &[PriceRecord]| -> Option<Vec<f64>> {
+            let prices: Vec<_> = moments
+                .iter()
+                .map(|&timestamp| window_price(records, timestamp).get())
+                .collect();
+            prices
+                .windows(2)
+                .map(|pair| {
+                    let ratio = pair[1] / pair[0];
+                    (ratio.is_finite() && ratio > 0.).then(|| ratio.ln())
+                })
+                .collect()
+        };
+        let returns = log_returns(&window)?;
+        let reference = log_returns(&reference)?;
+        if returns.is_empty() {
+            return None;
+        }
+        let count = returns.len() as f64;
+        let mean = returns.iter().sum::<f64>() / count;
+        let reference_mean = reference.iter().sum::<f64>() / count;
+        let (covariance, variance) = returns.iter().zip(&reference).fold(
+            (0., 0.),
+            |(covariance, variance), (value, reference)| {
+                (
+                    covariance + (value - mean) * (reference - reference_mean),
+                    variance + (reference - reference_mean).powi(2),
+                )
+            },
+        );
+        (variance > 0.).then(|| covariance / variance)
+    }
+
+    /// Returns the band of the `deviations` standard deviations around the
+    /// average, if enough historical data has been collected. The
+    /// conservative price never goes below zero.
+    pub fn price_band(&self, symbol: String, deviations: u8) -> Option<PriceBand> {
+        let mid = self.average()?;
+        let volatility = self.volatility()?;
+        let width = volatility.checked_mul_int(u128::from(deviations))?;
+        Some(PriceBand {
+            symbol,
+            conservative: mid.checked_sub(width).unwrap_or(Fixed::ZERO).to_f64(),
+            mid: mid.to_f64(),
+            aggressive: mid.checked_add(width)?.to_f64(),
+            volatility: volatility.to_f64(),
+        })
+    }
+
+    /// Returns the price as of the `timestamp` extrapolated by the least
+    /// squares line of the prices of the window over time, within the
+    /// `deviations` standard errors of the prediction, or `None` without at
+    /// least three records apart in time.
+    pub fn forecast(&self, symbol: String, timestamp: u64, deviations: u8) -> Option<Forecast> {
+        let window: Vec<_> = self.window().collect();
+        let latest = window.last()?.timestamp;
+        // Hint: the seconds since the latest record keep the precision.
+        let seconds = |timestamp: u64| (timestamp as f64 - latest as f64) / 1e9;
+        let points: Vec<_> = window
+            .iter()
+            .map(|record| (seconds(record.timestamp), record.price.get()))
+            .collect();
+        if points.len() < 3 {
+            return None;
+        }
+        let count = points.len() as f64;
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / count;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / count;
+        let spread: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        if spread == 0. {
+            return None;
+        }
+        let slope = points
+            .iter()
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum::<f64>()
+            / spread;
+        let intercept = mean_y - slope * mean_x;
+        let residuals: f64 = points
+            .iter()
+            .map(|(x, y)| (y - intercept - slope * x).powi(2))
+            .sum();
+        let x = seconds(timestamp);
+        let standard_error =
+            (residuals / (count - 2.) * (1. + 1. / count + (x - mean_x).powi(2) / spread)).sqrt();
+        let estimate = intercept + slope * x;
+        let width = standard_error * f64::from(deviations);
+        // Hint: a price is never negative, whatever the trend.
+        Some(Forecast {
+            symbol,
+            timestamp: timestamp.into(),
+            estimate: estimate.max(0.),
+            lower: (estimate - width).max(0.),
+            upper: (estimate + width).max(0.),
+            slope,
+            standard_error,
+        })
+    }
+
+    /// Returns the statistics of the window as of the `now` timestamp, with
+    /// the `min_submissions` of the quorum, if enough historical data has
+    /// been collected.
+    pub fn statistics(
+        &self,
+        symbol: String,
+        min_submissions: u16,
+        now: u64,
+    ) -> Option<PriceStatistics> {
+        let average = self.average()?;
+        Some(PriceStatistics {
+            symbol,
+            average: average.to_f64(),
+            min: self.lows.get()?,
+            max: self.highs.get()?,
+            variance: self.statistics.variance()?.to_f64(),
+            stale: self.is_stale(now),
+            quality: self.quality(min_submissions, now),
+        })
+    }
+
+    /// Recalculates the statistics of the window.
+    fn refresh_statistics(&mut self) {
+        let window: Vec<_> = self.window().map(|record| record.price).collect();
+        self.statistics = Statistics::of(window.iter().copied());
+        let short_window = &window[window.len() - self.short_depth_so_far().min(window.len())..];
+        self.short_statistics = Statistics::of(short_window.iter().copied());
+        self.short_above = None;
+        let rounds: Vec<_> = self.rounds.latest(window.len() as u64).collect();
+        for extremum in [&mut self.lows, &mut self.highs] {
+            extremum.clear();
+            for (&round, &price) in rounds.iter().zip(&window) {
+                extremum.push(price, round);
+            }
+        }
+    }
+
+    /// Checks whether the `reporter` has submitted a price within the
+    /// configured interval before the `timestamp`, whether or not its
+    /// submission has been the median of the round.
+    pub fn reported_recently(&self, reporter: &AccountId, timestamp: u64) -> bool {
+        let interval = self.config.min_report_interval.0;
+        interval != 0
+            && self
+                .reported_at
+                .get(reporter)
+                .is_some_and(|reported_at| reported_at.saturating_add(interval) > timestamp)
+    }
+
+    /// Remembers the `timestamp` of the submission of the `reporter`, while
+    /// the submissions are rate limited.
+    pub fn set_reported(&mut self, reporter: &AccountId, timestamp: u64) {
+        if self.config.min_report_interval.0 != 0 {
+            self.reported_at.insert(reporter, &timestamp);
+        }
+    }
+
+    /// Checks whether the `timestamp` supplied by a reporter lags behind the
+    /// `now` block timestamp within the configured skew, and doesn't
+    /// predate the latest record, neither the retained nor the archived one.
+    pub fn accepts_timestamp(&self, timestamp: u64, now: u64) -> bool {
+        timestamp <= now
+            && now - timestamp <= self.config.max_timestamp_skew.0
+            && self
+                .latest()
+                .is_none_or(|latest| latest.timestamp <= timestamp)
+            && self
+                .archive
+                .newest()
+                .is_none_or(|newest| newest.timestamp <= timestamp)
+    }
+
+    /// Checks whether the `timestamp` of an attestation is newer than that
+    /// of every bridged record.
+    pub fn accepts_bridged(&self, timestamp: u64) -> bool {
+        timestamp > self.bridged_at
+    }
+
+    /// Keeps the `timestamp` of the bridged record.
+    pub fn set_bridged(&mut self, timestamp: u64) {
+        self.bridged_at = timestamp;
+    }
+
+    /// Proposes the average as the official answer in the block of the
+    /// `height`, if the finality is delayed.
+    pub fn propose_answer(&mut self, height: u64) {
+        let delay = u64::from(self.config.finality_blocks);
+        if delay == 0 {
+            return;
+        }
+        if let (Some(average), Some(latest)) = (self.average(), self.latest()) {
+            let answer = Answer {
+                average,
+                timestamp: latest.timestamp,
+                height,
+            };
+            self.finality.propose(answer, height, delay);
+        }
+    }
+
+    /// Returns the official answer as of the block of the `height`: the one
+    /// which has survived the delay, or the average with its latest record
+    /// when the finality isn't delayed.
+    pub fn official_answer(&self, height: u64) -> Option<Answer> {
+        let delay = u64::from(self.config.finality_blocks);
+        if delay == 0 {
+            return Some(Answer {
+                average: self.average()?,
+                timestamp: self.latest()?.timestamp,
+                height: self.last_block.unwrap_or(height),
+            });
+        }
+        self.finality.official(height, delay)
+    }
+
+    /// Returns the answer still to survive the delay as of the block of the
+    /// `height`, if any.
+    pub fn proposed_answer(&self, height: u64) -> Option<Answer> {
+        self.finality
+            .proposed(height, u64::from(self.config.finality_blocks))
+    }
+
+    /// Drops the answer still to survive the delay as of the block of the
+    /// `height`, and returns it, if any.
+    pub fn dispute_answer(&mut self, height: u64) -> Option<Answer> {
+        self.finality
+            .dispute(height, u64::from(self.config.finality_blocks))
+    }
+
+    /// Checks whether the `price` is non-zero, unless zero is allowed.
+    pub fn accepts_price(&self, price: Price) -> bool {
+        self.config.allow_zero || price.fixed() != Fixed::ZERO
+    }
+
+    /// Checks whether the `price` deviates from the current average within
+    /// the configured limit.
+    pub fn within_deviation(&self, price: Price) -> bool {
+        let max_deviation_bps = self.config.max_deviation_bps;
+        match self.average() {
+            Some(average) if max_deviation_bps != 0 => {
+                within_bps(price.fixed(), average, max_deviation_bps)
+            }
+            _ => true,
+        }
+    }
+
+    /// Returns the change from the `previous` average to the `average` in
+    /// basis points, if it's more than configured for the alerts.
+    pub fn alert_deviation(&self, previous: Fixed, average: Fixed) -> Option<u32> {
+        let alert_deviation_bps = self.config.alert_deviation_bps;
+        if alert_deviation_bps == 0 || within_bps(average, previous, alert_deviation_bps) {
+            return None;
+        }
+        Some(deviation_bps(average, previous))
+    }
+
+    /// Converts the `value` into the asset's fixed-point representation.
+    ///
+    /// Returns `None` when the value can't be represented.
+    pub fn to_fixed(&self, value: Fixed) -> Option<u128> {
+        value.rescale(self.decimals, Rounding::Nearest)
+    }
+}
+
+/// Checks whether the `value` deviates from the `reference` within the
+/// `bps` basis points.
+pub fn within_bps(value: Fixed, reference: Fixed, bps: u32) -> bool {
+    let deviation = value.abs_diff(reference).raw();
+    deviation.saturating_mul(10_000) <= reference.raw().saturating_mul(u128::from(bps))
+}
+
+/// Returns the weighted median of the `prices`, sorted from the lowest,
+/// unless all of them weigh nothing.
+fn weighted_median(prices: Vec<(Price, u32)>) -> Option<Price> {
+    let total: u64 = prices.iter().map(|&(_, weight)| u64::from(weight)).sum();
+    // Hint: the lower median, just like that of a round, is an actual price;
+    // with equal weights it's the middle one.
+    let mut cumulative = 0;
+    prices.into_iter().find_map(|(price, weight)| {
+        cumulative += u64::from(weight);
+        (weight != 0 && 2 * cumulative >= total).then_some(price)
+    })
+}
+
+/// Returns the deviation of the `value` from the `reference` in basis
+/// points, saturated to `u32::MAX`.
+pub fn deviation_bps(value: Fixed, reference: Fixed) -> u32 {
+    let deviation_bps = fixed::mul_div(
+        value.abs_diff(reference).raw(),
+        10_000,
+        reference.raw(),
+        Rounding::Nearest,
+    );
+    // Hint: the deviation from zero is infinite.
+    deviation_bps.map_or(u32::MAX, |bps| u32::try_from(bps).unwrap_or(u32::MAX))
+}
+
+/// Returns the exponential moving averages over the `periods` values, as of
+/// every value from the `periods`-th one, the first being the simple average.
+fn ema(values: &[f64], periods: u16) -> Vec<f64> {
+    let periods = usize::from(periods);
+    if periods == 0 || values.len() < periods {
+        return Vec::new();
+    }
+    let smoothing = 2. / (periods as f64 + 1.);
+    let seed = values[..periods].iter().sum::<f64>() / periods as f64;
+    let mut averages = Vec::with_capacity(values.len() - periods + 1);
+    averages.push(seed);
+    for value in &values[periods..] {
+        let previous = averages[averages.len() - 1];
+        averages.push(previous + (value - previous) * smoothing);
+    }
+    averages
+}
+
+/// Returns the price of the `records`, from the oldest to the newest, as of
+/// the `timestamp` within the period they span.
+fn window_price(records: &[PriceRecord], timestamp: u64) -> Price {
+    let after = records
+        .iter()
+        .position(|record| record.timestamp >= timestamp)
+        .unwrap_or(records.len() - 1);
+    match after.checked_sub(1) {
+        Some(before) if records[after].timestamp > timestamp => {
+            interpolate(records[before], records[after], timestamp)
+        }
+        _ => records[after].price,
+    }
+}
+
+/// Returns the price as of the `timestamp`, which is strictly between those
+/// of the `before` and the `after` records.
+fn interpolate(before: PriceRecord, after: PriceRecord, timestamp: u64) -> Price {
+    let elapsed = u128::from(timestamp - before.timestamp);
+    let interval = u128::from(after.timestamp - before.timestamp);
+    let change = before.price.fixed().abs_diff(after.price.fixed()).raw();
+    // Hint: the change is never greater than the prices, neither is the
+    // interpolated one.
+    let change = Fixed::from_raw(
+        fixed::mul_div(change, elapsed, interval, Rounding::Nearest).unwrap_or(change),
+    );
+    let price = if after.price >= before.price {
+        before.price.fixed().checked_add(change)
+    } else {
+        before.price.fixed().checked_sub(change)
+    };
+    price
+        .and_then(|price| Price::from_fixed(price).ok())
+        .unwrap_or(before.price)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    fn asset(decimals: u8) -> Asset {
+        Asset::new(
+            decimals,
+            AssetMetadata {
+                name: "Bitcoin".to_string(),
+                base: "BTC".to_string(),
+                quote: "USD".to_string(),
+                source: "test".to_string(),
+            },
+            AssetConfig::default(),
+            "alice.testnet".parse().unwrap(),
+            b"h".to_vec(),
+        )
+    }
+
+    fn fixed(value: f64) -> Fixed {
+        Fixed::from_f64(value).unwrap()
+    }
+
+    fn price(value: f64) -> Price {
+        Price::new(value).unwrap()
+    }
+
+    fn reporter() -> AccountId {
+        "bob.testnet".parse().unwrap()
+    }
+
+    fn record(value: f64, timestamp: u64) -> PriceRecord {
+        PriceRecord {
+            price: price(value),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn depth() {
+        let mut asset = asset(2);
+        asset.set_config(AssetConfig {
+            depth: 2,
+            ..AssetConfig::default()
+        });
+        asset.push(record(1., 0), &reporter(), Vec::new());
+        assert_eq!(asset.average(), None);
+        asset.push(record(2., 0), &reporter(), Vec::new());
+        asset.push(record(4., 0), &reporter(), Vec::new());
+        assert_eq!(asset.average(), Some(fixed(3.)));
+
+        // The history beyond the depth is kept.
+        asset.set_config(AssetConfig {
+            depth: 3,
+            ..AssetConfig::default()
+        });
+        assert_eq!(
+            asset.average(),
+            Some(Fixed::from_raw(2_333_333_333_333_333_333))
+        );
+    }
+
+    #[test]
+    fn heartbeat() {
+        let mut asset = asset(2);
+        assert!(!asset.is_stale(100));
+        asset.set_config(AssetConfig {
+            heartbeat: U64(10),
+            ..AssetConfig::default()
+        });
+        assert!(asset.is_stale(100));
+        asset.push(record(1., 95), &reporter(), Vec::new());
+        assert!(!asset.is_stale(100));
+        assert!(!asset.is_stale(105));
+        assert!(asset.is_stale(106));
+    }
+
+    #[test]
+    fn deviation() {
+        let mut asset = asset(2);
+        asset.set_config(AssetConfig {
+            depth: 1,
+            heartbeat: U64(0),
+            max_deviation_bps: 1_000,
+            archive: false,
+            dedup_tolerance_bps: None,
+            archive_encoding: ArchiveEncoding::Full,
+            one_per_block: false,
+            max_timestamp_skew: U64(0),
+            alert_deviation_bps: 0,
+            short_depth: None,
+            min_report_interval: U64(0),
+            archive_retention: U64(0),
+            allow_zero: false,
+            quarantine_rounds: 0,
+            quarantine_deviation_bps: 0,
+            finality_blocks: 0,
+            log_returns: false,
+            kalman: None,
+        });
+        assert!(asset.within_deviation(price(1_000.)));
+        asset.push(record(100., 0), &reporter(), Vec::new());
+        assert!(asset.within_deviation(price(110.)));
+        assert!(asset.within_deviation(price(90.)));
+        assert!(!asset.within_deviation(price(110.1)));
+        assert!(!asset.within_deviation(price(89.9)));
+    }
+
+    #[test]
+    fn statistics() {
+        let mut asset = asset(2);
+        asset.set_config(AssetConfig {
+            depth: 3,
+            ..AssetConfig::default()
+        });
+        asset.push(record(2., 0), &reporter(), Vec::new());
+        asset.push(record(4., 0), &reporter(), Vec::new());
+        assert_eq!(asset.statistics("BTC".to_string(), 0, 0), None);
+        asset.push(record(9., 0), &reporter(), Vec::new());
+        let statistics = asset.statistics("BTC".to_string(), 0, 0).unwrap();
+        assert_eq!(statistics.average, 5.);
+        assert_eq!(statistics.min, price(2.));
+        assert_eq!(statistics.max, price(9.));
+        assert_eq!(statistics.variance, 26. / 3.);
+
+        // The oldest price leaves the window.
+        asset.push(record(4., 0), &reporter(), Vec::new());
+        let statistics = asset.statistics("BTC".to_string(), 0, 0).unwrap();
+        assert_eq!(statistics.min, price(4.));
+        assert_eq!(statistics.variance, 50. / 9.);
+
+        asset.reset();
+        assert_eq!(asset.statistics("BTC".to_string(), 0, 0), None);
+    }
+
+    #[test]
+    fn partial_window() {
+        let mut asset = asset(2);
+        asset.set_config(AssetConfig {
+            depth: 3,
+            ..AssetConfig::default()
+        });
+        for value in [2., 4., 9.] {
+            asset.push(record(value, 0), &reporter(), Vec::new());
+        }
+        // The slots of the forgotten records are still stored.
+        asset.reset();
+        asset.push(record(5., 0), &reporter(), Vec::new());
+        assert_eq!(asset.lows.get(), Some(price(5.)));
+        assert_eq!(asset.highs.get(), Some(price(5.)));
+        assert_eq!(asset.statistics, Statistics::of([price(5.)].into_iter()));
+
+        asset.push(record(3., 0), &reporter(), Vec::new());
+        assert!(asset.rewind(1, 0));
+        assert_eq!(asset.lows.get(), Some(price(5.)));
+        assert_eq!(asset.statistics, Statistics::of([price(5.)].into_iter()));
+        assert_eq!(asset.window().count(), 1);
+    }
+
+    #[quickcheck]
+    fn average_within_bounds(prices: Vec<Price>) -> bool {
+        let average = Statistics::of(prices.iter().copied()).average();
+        let bounds = prices.iter().min().zip(prices.iter().max());
+        match (average, bounds) {
+            (Some(average), Some((min, max))) => (min.fixed()..=max.fixed()).contains(&average),
+            (average, bounds) => average.is_none() && bounds.is_none(),
+        }
+    }
+
+    #[quickcheck]
+    fn sliding_statistics(prices: Vec<Price>, evicted: usize) -> bool {
+        // The prices leave the window in the order they have entered it.
+        let evicted = evicted % (prices.len() + 1);
+        let mut statistics = Statistics::of(prices.iter().copied());
+        for &price in &prices[..evicted] {
+            statistics.remove(price);
+        }
+        statistics == Statistics::of(prices[evicted..].iter().copied())
+    }
+
+    #[test]
+    fn maximum_window() {
+        let max = price(MAX_PRICE);
+        let statistics = Statistics::of(std::iter::repeat_n(max, MAX_HISTORY_DEPTH));
+        assert_eq!(statistics.average(), Some(max.fixed()));
+        assert_eq!(statistics.variance(), Some(WideFixed::default()));
+    }
+
+    #[test]
+    fn large_window() {
+        let mut asset = asset(2);
+        asset.set_config(AssetConfig {
+            depth: 100,
+            ..AssetConfig::default()
+        });
+        // The minimum leaves the window on every record.
+        for value in 1..=150 {
+            asset.push(record(f64::from(value), 0), &reporter(), Vec::new());
+        }
+        assert_eq!(asset.history_len(), 100);
+        assert_eq!(asset.average(), Some(fixed(100.5)));
+        let statistics = asset.statistics("BTC".to_string(), 0, 0).unwrap();
+        assert_eq!(statistics.min, price(51.));
+        assert_eq!(statistics.max, price(150.));
+
+        asset.release();
+        assert_eq!(asset.history_len(), 0);
+        assert_eq!(asset.latest(), None);
+    }
+
+    #[test]
+    fn dedup() {
+        let mut asset = asset(2);
+        asset.push(record(100., 0), &reporter(), Vec::new());
+        assert!(!asset.is_repeated(price(100.)));
+
+        asset.set_config(AssetConfig {
+            dedup_tolerance_bps: Some(10),
+            ..AssetConfig::default()
+        });
+        assert!(asset.is_repeated(price(100.1)));
+        assert!(!asset.is_repeated(price(100.2)));
+        asset.refresh(50);
+        assert_eq!(asset.latest(), Some(record(100., 50)));
+        assert_eq!(asset.depth_so_far(), 1);
+    }
+
+    #[test]
+    fn bounded_gaps() {
+        let mut asset = asset(2);
+        for timestamp in (0..=5000).chain([6000]) {
+            // Hint: a fresh context every now and then, so the gas isn't
+            // exhausted.
+            if timestamp % 64 == 0 {
+                near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new().build());
+            }
+            asset
+                .archive
+                .push(record(1., timestamp), ArchiveEncoding::Full, 2);
+        }
+        // The gap is past the records scanned from the oldest one.
+        assert!(asset.gaps(10, 0, u64::MAX, 50).is_empty());
+        assert_eq!(
+            asset.gaps(10, 4500, u64::MAX, 50),
+            vec![Gap {
+                from: 5000.into(),
+                to: 6000.into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn validate() {
+        assert_eq!(AssetConfig::default().validate(), Ok(()));
+        let invalid = |config: AssetConfig| config.validate().unwrap_err();
+        assert_eq!(
+            invalid(AssetConfig {
+                quarantine_rounds: 3,
+                ..AssetConfig::default()
+            }),
+            "The quarantine needs a deviation"
+        );
+        for config in [
+            AssetConfig {
+                max_deviation_bps: MAX_DEVIATION_BPS + 1,
+                ..AssetConfig::default()
+            },
+            AssetConfig {
+                alert_deviation_bps: MAX_DEVIATION_BPS + 1,
+                ..AssetConfig::default()
+            },
+            AssetConfig {
+                quarantine_rounds: 3,
+                quarantine_deviation_bps: MAX_DEVIATION_BPS + 1,
+                ..AssetConfig::default()
+            },
+        ] {
+            assert_eq!(invalid(config), "Deviations must be within 0..=100000 bps");
+        }
+        assert_eq!(
+            invalid(AssetConfig {
+                dedup_tolerance_bps: Some(10_000),
+                ..AssetConfig::default()
+            }),
+            "Dedup tolerance must be within 0..10000 bps"
+        );
+        assert_eq!(
+            invalid(AssetConfig {
+                heartbeat: 10.into(),
+                max_timestamp_skew: 10.into(),
+                ..AssetConfig::default()
+            }),
+            "The timestamp skew must be below the heartbeat"
+        );
+        assert_eq!(
+            invalid(AssetConfig {
+                heartbeat: 10.into(),
+                min_report_interval: 10.into(),
+                ..AssetConfig::default()
+            }),
+            "The report interval must be below the heartbeat"
+        );
+        assert_eq!(
+            invalid(AssetConfig {
+                finality_blocks: MAX_FINALITY_BLOCKS + 1,
+                ..AssetConfig::default()
+            }),
+            "Finality must be within 0..=86400 blocks"
+        );
+        // Without a heartbeat, any skew and interval go.
+        assert_eq!(
+            AssetConfig {
+                max_timestamp_skew: u64::MAX.into(),
+                min_report_interval: u64::MAX.into(),
+                quarantine_rounds: 3,
+                quarantine_deviation_bps: MAX_DEVIATION_BPS,
+                dedup_tolerance_bps: Some(9_999),
+                ..AssetConfig::default()
+            }
+            .validate(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn to_fixed() {
+        assert_eq!(asset(0).to_fixed(fixed(41_999.6)), Some(42_000));
+        assert_eq!(asset(2).to_fixed(fixed(41_999.6)), Some(4_199_960));
+        assert_eq!(asset(24).to_fixed(fixed(1.)), Some(10u128.pow(24)));
+        assert_eq!(asset(24).to_fixed(Fixed::from_raw(u128::MAX)), None);
+    }
+}
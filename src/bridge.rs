@@ -0,0 +1,265 @@
+//! Intake of the prices attested by a foreign chain, e.g. bridged from an EVM
+//! aggregator.
+//!
+//! The owner configures the set of the foreign signers, identified by their
+//! EVM addresses, and how many of them must sign an attestation. Anyone may
+//! relay an attestation, which is verified on-chain and recorded at once, with
+//! the [`Provenance::Bridged`] tag, bypassing the quorum.
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{Base64VecU8, U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json::json;
+use near_sdk::{env, log, near_bindgen, FunctionError};
+
+use crate::error::{ensure, ContractError};
+use crate::events;
+use crate::fixed::Fixed;
+use crate::price::Price;
+use crate::round::{Provenance, Submission};
+use crate::{LogLevel, PriceHistory, PriceHistoryExt};
+
+/// Prefix of the signed digests, so they can't be replayed elsewhere.
+const DOMAIN: &[u8] = b"coinmarketcap-attestation";
+
+/// The foreign signers of the attestations.
+#[derive(Debug, Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct BridgeSigners {
+    /// Hex EVM addresses of the signers, stored lowercase.
+    pub addresses: Vec<String>,
+    /// Amount of the distinct signers required to sign an attestation.
+    pub threshold: u16,
+}
+
+/// A price attested by the foreign signers.
+#[derive(Debug, Clone, PartialEq, BorshSerialize, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Attestation {
+    pub symbol: String,
+    /// The price multiplied by `10^decimals`.
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<u128>",
+        definitions = "crate::schema::definitions::<u128>",
+    ))))]
+    pub answer: U128,
+    pub decimals: u8,
+    /// Timestamp of the price on the foreign chain, in seconds.
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<u64>",
+        definitions = "crate::schema::definitions::<u64>",
+    ))))]
+    pub timestamp: U64,
+}
+
+/// A recoverable secp256k1 signature of the digest of an attestation, see the
+/// [`PriceHistory::get_attestation_digest`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AttestationSignature {
+    /// The `r` and `s` of the signature, 64 bytes.
+    pub signature: Base64VecU8,
+    /// The recovery identifier, either 0 or 1, or 27 or 28.
+    pub v: u8,
+}
+
+#[near_bindgen]
+impl PriceHistory {
+    /// Returns the foreign signers of the attestations, if configured.
+    pub fn get_bridge_signers(&self) -> Option<BridgeSigners> {
+        self.bridge_signers.clone()
+    }
+
+    /// Sets the foreign signers of the attestations, or stops accepting any.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, when an address is not a hex EVM one or is repeated, or
+    /// when the threshold is not within `1..=` the amount of the addresses.
+    pub fn set_bridge_signers(&mut self, signers: Option<BridgeSigners>) {
+        self.assert_owner("configure the bridge");
+        let signers = signers.map(|signers| {
+            let mut addresses = signers
+                .addresses
+                .iter()
+                .map(|address| {
+                    normalize_address(address).unwrap_or_else(|| {
+                        ContractError::OutOfBounds("The signers must be hex EVM addresses").panic()
+                    })
+                })
+                .collect::<Vec<_>>();
+            addresses.sort_unstable();
+            addresses.dedup();
+            ensure(
+                addresses.len() == signers.addresses.len(),
+                ContractError::OutOfBounds("The signers must be distinct"),
+            );
+            ensure(
+                (1..=addresses.len()).contains(&usize::from(signers.threshold)),
+                ContractError::OutOfBounds("The threshold must be within 1..=signers"),
+            );
+            BridgeSigners {
+                addresses,
+                threshold: signers.threshold,
+            }
+        });
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Bridge signers changed to {:?}", signers);
+        }
+        events::emit_config_changes(
+            None,
+            &json!({ "bridge_signers": self.bridge_signers }),
+            &json!({ "bridge_signers": signers }),
+        );
+        self.bridge_signers = signers;
+    }
+
+    /// Returns the keccak256 digest of the attestation the foreign signers
+    /// sign, which is bound to the account of the contract.
+    pub fn get_attestation_digest(&self, attestation: Attestation) -> Base64VecU8 {
+        attestation_digest(&attestation).to_vec().into()
+    }
+
+    /// Verifies the `signatures` of the attestation and records its price,
+    /// relayed by the caller, at once. The timestamp must be within the
+    /// `max_timestamp_skew` of the asset.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when no signers are configured, when not enough distinct
+    /// signers have signed the attestation, when the asset is not registered
+    /// or frozen, when the price is not within `0..=1e14`, is zero while the
+    /// asset doesn't allow it, or deviates from the average more than
+    /// configured, when the block already has a record
+    /// while only one is allowed, when the timestamp lags behind the block
+    /// more than configured or predates the latest record, or when the
+    /// storage balance of the account which has paid for the asset is not
+    /// enough.
+    pub fn record_attestation(
+        &mut self,
+        attestation: Attestation,
+        signatures: Vec<AttestationSignature>,
+    ) {
+        let signers = self
+            .bridge_signers
+            .clone()
+            .unwrap_or_else(|| ContractError::Unauthorized("record a bridged price").panic());
+        let digest = attestation_digest(&attestation);
+        let mut signed = signatures
+            .iter()
+            .filter_map(|signature| recover_address(&digest, signature))
+            .filter(|address| signers.addresses.contains(address))
+            .collect::<Vec<_>>();
+        signed.sort_unstable();
+        signed.dedup();
+        ensure(
+            signed.len() >= usize::from(signers.threshold),
+            ContractError::Unauthorized("record a price without enough signatures"),
+        );
+
+        let symbol = attestation.symbol;
+        let mut asset = self.asset(&symbol);
+        ensure(!asset.frozen, ContractError::Frozen);
+        self.assert_active();
+        let price = Fixed::from_scaled(attestation.answer.0, attestation.decimals)
+            .and_then(|price| Price::from_fixed(price).ok())
+            .unwrap_or_else(|| {
+                ContractError::OutOfBounds("The price must be within 0..=1e14").panic()
+            });
+        ensure(
+            asset.accepts_price(price),
+            ContractError::OutOfBounds("The price must not be zero"),
+        );
+        ensure(asset.within_deviation(price), ContractError::Deviation);
+        ensure(
+            asset.accepts_block(env::block_height()),
+            ContractError::SameBlock,
+        );
+        let timestamp = attestation.timestamp.0.saturating_mul(1_000_000_000);
+        ensure(
+            asset.accepts_timestamp(timestamp, env::block_timestamp()),
+            ContractError::OutOfBounds(
+                "The timestamp must be within the allowed skew and not predate the latest record",
+            ),
+        );
+        // Hint: a replayed attestation is as old as the original one.
+        ensure(
+            asset.accepts_bridged(timestamp),
+            ContractError::OutOfBounds("The attestation must be newer than the latest bridged one"),
+        );
+        asset.set_bridged(timestamp);
+        let initial_storage_usage = env::storage_usage();
+        let submission = Submission {
+            reporter: env::predecessor_account_id(),
+            price,
+            timestamp: timestamp.into(),
+            confidence: None,
+            provenance: Provenance::Bridged,
+        };
+        if self.log_level.allows(LogLevel::Debug) {
+            log!(
+                "Attested {} price {} by {} signers",
+                symbol,
+                price,
+                signed.len()
+            );
+        }
+        self.record_round(&symbol, asset, vec![submission], initial_storage_usage);
+    }
+}
+
+/// Returns the digest of the `attestation` signed by the foreign signers.
+fn attestation_digest(attestation: &Attestation) -> [u8; 32] {
+    // Hint: the serialization into a vector never fails.
+    let message = borsh::to_vec(&(DOMAIN, env::current_account_id(), attestation))
+        .unwrap_or_else(|_| unreachable!());
+    env::keccak256_array(&message)
+}
+
+/// Recovers the EVM address of the signer of the `digest`, if the signature
+/// is valid.
+pub(crate) fn recover_address(
+    digest: &[u8; 32],
+    signature: &AttestationSignature,
+) -> Option<String> {
+    let v = match signature.v {
+        27 | 28 => signature.v - 27,
+        v => v,
+    };
+    let public_key = env::ecrecover(digest, &signature.signature.0, v, true)?;
+    let hash = env::keccak256_array(public_key);
+    Some(to_hex(&hash[12..]))
+}
+
+/// Validates an EVM address, returning it lowercase with the "0x" prefix.
+pub(crate) fn normalize_address(address: &str) -> Option<String> {
+    let hex = address.strip_prefix("0x").unwrap_or(address);
+    (hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| format!("0x{}", hex.to_ascii_lowercase()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let digits: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("0x{}", digits)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn addresses() {
+        assert_eq!(
+            normalize_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").as_deref(),
+            Some("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed")
+        );
+        assert!(normalize_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAe").is_none());
+        assert!(normalize_address("0xZaAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").is_none());
+        assert_eq!(to_hex(&[0, 0xab, 0x10]), "0x00ab10");
+    }
+}
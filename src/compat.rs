@@ -0,0 +1,85 @@
+//! Views shaped after other oracles, so the tooling written against them
+//! consumes the feeds with a thin adapter.
+
+use near_sdk::json_types::{I64, U64};
+use near_sdk::serde::Serialize;
+use near_sdk::{near_bindgen, FunctionError};
+
+use crate::error::ContractError;
+use crate::fixed::Fixed;
+use crate::{PriceHistory, PriceHistoryExt, VERSION};
+
+/// The latest price of an asset in the shape of a [Pyth] price feed.
+///
+/// [Pyth]: https://docs.pyth.network/price-feeds
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PythPrice {
+    /// The price multiplied by `10^-expo`.
+    pub price: I64,
+    /// The confidence of the price, in the same units, zero when unknown.
+    pub conf: U64,
+    /// The negated amount of decimals of the asset.
+    pub expo: i32,
+    /// Timestamp of the price, in seconds.
+    pub publish_time: i64,
+}
+
+#[near_bindgen]
+impl PriceHistory {
+    /// Describes the pair of the asset, like "BTC / USD", in the fashion of
+    /// a Chainlink aggregator.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn description(&self, symbol: String) -> String {
+        let metadata = self.asset(&symbol).metadata();
+        format!("{} / {}", metadata.base, metadata.quote)
+    }
+
+    /// Returns the amount of decimals of the asset, just like the
+    /// [`Self::get_decimals`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn decimals(&self, symbol: String) -> u8 {
+        self.asset(&symbol).decimals
+    }
+
+    /// Returns the version of the contract, without the build of the
+    /// [`Self::get_version`].
+    pub fn version(&self) -> String {
+        VERSION.to_string()
+    }
+
+    /// Returns the latest price of the asset in the shape of a Pyth price
+    /// feed.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered, when no price has been
+    /// recorded, or when the price doesn't fit the fixed-point integers.
+    pub fn get_pyth_price(&self, symbol: String) -> PythPrice {
+        let asset = self.asset(&symbol);
+        let latest = asset
+            .latest()
+            .unwrap_or_else(|| ContractError::NotEnoughData.panic());
+        let to_integer = |value: Fixed| {
+            asset
+                .to_fixed(value)
+                .and_then(|value| i64::try_from(value).ok())
+                .unwrap_or_else(|| {
+                    ContractError::OutOfBounds("The price can't be represented as a Pyth price")
+                        .panic()
+                })
+        };
+        PythPrice {
+            price: I64(to_integer(latest.price.fixed())),
+            conf: U64(asset.confidence().map_or(0, |conf| to_integer(conf) as u64)),
+            expo: -i32::from(asset.decimals),
+            publish_time: (latest.timestamp / 1_000_000_000) as i64,
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Lifetime counters of the contract, a cheap health summary for the
+//! monitoring.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::near_bindgen;
+use near_sdk::serde::Serialize;
+
+use crate::{PriceHistory, PriceHistoryExt};
+
+/// The lifetime counters, as stored.
+#[derive(Debug, Default, Clone, PartialEq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Counters {
+    pub records_accepted: u64,
+    pub submissions_rejected: u64,
+    pub resets: u64,
+    pub last_reset: Option<u64>,
+}
+
+/// The lifetime counters of every asset, as returned by the views.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Stats {
+    /// Rounds recorded, not counting those refreshing a repeated price.
+    pub records_accepted: U64,
+    /// Submissions discarded along with the rounds closed short of the
+    /// quorum. The calls failing the checks are reverted, so they are never
+    /// counted.
+    pub submissions_rejected: U64,
+    pub resets: U64,
+    /// Block timestamp of the latest reset, in nanoseconds.
+    pub last_reset: Option<U64>,
+}
+
+#[near_bindgen]
+impl PriceHistory {
+    /// Returns the lifetime counters of the contract.
+    pub fn get_stats(&self) -> Stats {
+        Stats {
+            records_accepted: self.counters.records_accepted.into(),
+            submissions_rejected: self.counters.submissions_rejected.into(),
+            resets: self.counters.resets.into(),
+            last_reset: self.counters.last_reset.map(U64),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! A time-weighted price accumulator in the fashion of Uniswap V2.
+//!
+//! The accumulator grows by the latest price multiplied by the time elapsed
+//! since it was recorded, so the time-weighted average price between any two
+//! observations is `(cumulative_2 - cumulative_1) / (timestamp_2 -
+//! timestamp_1)`.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::serde::Serialize;
+
+use crate::fixed::WideFixed;
+use crate::price::Price;
+
+/// The cumulative price as of a moment.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CumulativePrice {
+    /// Sum of the prices multiplied by the nanoseconds they have lasted.
+    pub price_cumulative: f64,
+    /// Timestamp (in nanoseconds) the cumulative price is calculated as of.
+    pub timestamp: U64,
+}
+
+/// The accumulator.
+#[derive(Debug, Default, Clone, PartialEq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Accumulator {
+    price_cumulative: WideFixed,
+    /// The latest price and its timestamp.
+    latest: Option<(Price, u64)>,
+}
+
+impl Accumulator {
+    /// Accounts the `price` recorded at the `timestamp`.
+    pub fn record(&mut self, price: Price, timestamp: u64) {
+        self.price_cumulative = self.cumulative_at(timestamp);
+        self.latest = Some((price, timestamp));
+    }
+
+    /// Returns the cumulative price extrapolated to the `now` timestamp.
+    pub fn current(&self, now: u64) -> CumulativePrice {
+        CumulativePrice {
+            price_cumulative: self.cumulative_at(now).to_f64(),
+            timestamp: now.into(),
+        }
+    }
+
+    /// Returns the cumulative price extrapolated to the `timestamp`.
+    pub fn cumulative_at(&self, timestamp: u64) -> WideFixed {
+        match self.latest {
+            Some((price, since)) => self.price_cumulative.saturating_add(WideFixed::product(
+                price.fixed(),
+                timestamp.saturating_sub(since),
+            )),
+            None => self.price_cumulative,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn price(value: f64) -> Price {
+        Price::new(value).unwrap()
+    }
+
+    #[test]
+    fn twap() {
+        let mut accumulator = Accumulator::default();
+        assert_eq!(accumulator.current(10).price_cumulative, 0.);
+
+        accumulator.record(price(2.), 10);
+        let first = accumulator.current(10);
+        accumulator.record(price(4.), 20);
+        accumulator.record(price(1.), 50);
+        let second = accumulator.current(60);
+
+        assert_eq!(second.price_cumulative, 2. * 10. + 4. * 30. + 10.);
+        let twap = (second.price_cumulative - first.price_cumulative)
+            / (second.timestamp.0 - first.timestamp.0) as f64;
+        assert_eq!(twap, 3.);
+    }
+}
@@ -0,0 +1,70 @@
+//! Pushing prices to consumer contracts.
+
+use near_sdk::json_types::U64;
+use near_sdk::{env, ext_contract, log, near_bindgen, AccountId, Gas, Promise, PromiseError};
+
+use crate::{LogLevel, PriceHistory, PriceHistoryExt};
+
+/// Gas attached to the consumer call.
+const GAS_FOR_DELIVERY: Gas = Gas::from_tgas(10);
+/// Gas attached to the delivery callback.
+const GAS_FOR_DELIVERY_CALLBACK: Gas = Gas::from_tgas(5);
+
+/// Interface a consumer contract implements to receive prices, see
+/// [`PriceHistory::get_average_and_call`].
+#[ext_contract(ext_consumer)]
+pub trait PriceConsumer {
+    /// Receives the `average` price of the asset as of the `timestamp`, along
+    /// with the `msg` passed by the requester.
+    fn on_price_average(&mut self, symbol: String, average: f64, timestamp: U64, msg: String);
+}
+
+#[near_bindgen]
+impl PriceHistory {
+    /// Pushes the average price of the asset to the `receiver_id` by calling
+    /// its `on_price_average` with the timestamp of the latest record, and
+    /// resolves to whether the call has succeeded.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the caller is not an allowed consumer, when the asset
+    /// is not registered, when not enough historical data has been collected,
+    /// or when the price is stale.
+    pub fn get_average_and_call(
+        &mut self,
+        symbol: String,
+        receiver_id: AccountId,
+        msg: String,
+    ) -> Promise {
+        self.assert_consumer();
+        let asset = self.asset(&symbol);
+        let average = Self::require_average(&asset).to_f64();
+        // Hint: there is a record once there is an average.
+        let timestamp = asset.latest().map_or(0, |record| record.timestamp);
+        ext_consumer::ext(receiver_id.clone())
+            .with_static_gas(GAS_FOR_DELIVERY)
+            .on_price_average(symbol.clone(), average, timestamp.into(), msg)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_DELIVERY_CALLBACK)
+                    .on_average_delivered(receiver_id, symbol),
+            )
+    }
+
+    /// Reports the outcome of the delivery.
+    #[private]
+    pub fn on_average_delivered(
+        &mut self,
+        receiver_id: AccountId,
+        symbol: String,
+        #[callback_result] result: Result<(), PromiseError>,
+    ) -> bool {
+        let delivered = result.is_ok();
+        if !delivered && self.log_level.allows(LogLevel::Error) {
+            log!("Failed to deliver {} average to {}", symbol, receiver_id);
+        } else if delivered && self.log_level.allows(LogLevel::Debug) {
+            log!("Delivered {} average to {}", symbol, receiver_id);
+        }
+        delivered
+    }
+}
@@ -0,0 +1,161 @@
+//! Failures of the contract.
+//!
+//! Every failure is rendered as `ERR_CODE: message`, so front-ends and
+//! consumer contracts can branch on the code while humans read the message.
+
+use std::fmt;
+
+use near_sdk::{env, FunctionError};
+
+use crate::Balance;
+
+/// Reasons the contract refuses a call.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContractError {
+    /// The caller isn't allowed to perform the action.
+    Unauthorized(&'static str),
+    /// The asset is not registered.
+    NotRegistered,
+    /// The asset is already registered.
+    AlreadyRegistered,
+    /// The asset is not accepting any records.
+    Frozen,
+    /// Not enough historical data has been collected to calculate the
+    /// average.
+    NotEnoughData,
+    /// No price has been recorded within the heartbeat.
+    Stale,
+    /// The price deviates from the average more than configured.
+    Deviation,
+    /// A price has already been recorded in the current block.
+    SameBlock,
+    /// The reporter has recorded a price within the minimum interval.
+    TooFrequent,
+    /// The reporter has already submitted a price to the pending round.
+    AlreadySubmitted,
+    /// An argument or a result is out of the supported range.
+    OutOfBounds(&'static str),
+    /// The funds provided by the caller don't cover the cost.
+    InsufficientDeposit {
+        /// What's not enough, like "storage balance".
+        funds: &'static str,
+        required: Balance,
+        available: Balance,
+    },
+    /// The call requires exactly one yoctoNEAR attached.
+    OneYoctoRequired,
+    /// The storage balance is still in use.
+    StorageInUse,
+    /// No implementation is set for the proxy mode.
+    NoImplementation,
+    /// The contract doesn't accept any records anymore.
+    Decommissioned,
+}
+
+impl ContractError {
+    /// Returns the machine-readable code of the error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ContractError::Unauthorized(_) => "ERR_UNAUTHORIZED",
+            ContractError::NotRegistered => "ERR_NOT_REGISTERED",
+            ContractError::AlreadyRegistered => "ERR_ALREADY_REGISTERED",
+            ContractError::Frozen => "ERR_FROZEN",
+            ContractError::NotEnoughData => "ERR_NOT_ENOUGH_DATA",
+            ContractError::Stale => "ERR_STALE",
+            ContractError::Deviation => "ERR_DEVIATION",
+            ContractError::SameBlock => "ERR_SAME_BLOCK",
+            ContractError::TooFrequent => "ERR_TOO_FREQUENT",
+            ContractError::AlreadySubmitted => "ERR_ALREADY_SUBMITTED",
+            ContractError::OutOfBounds(_) => "ERR_OUT_OF_BOUNDS",
+            ContractError::InsufficientDeposit { .. } => "ERR_INSUFFICIENT_DEPOSIT",
+            ContractError::OneYoctoRequired => "ERR_ONE_YOCTO_REQUIRED",
+            ContractError::StorageInUse => "ERR_STORAGE_IN_USE",
+            ContractError::NoImplementation => "ERR_NO_IMPLEMENTATION",
+            ContractError::Decommissioned => "ERR_DECOMMISSIONED",
+        }
+    }
+}
+
+impl fmt::Display for ContractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.code())?;
+        match self {
+            ContractError::Unauthorized(action) => {
+                write!(f, "Sorry, you are not allowed to {}", action)
+            }
+            ContractError::NotRegistered => f.write_str("The asset is not registered"),
+            ContractError::AlreadyRegistered => f.write_str("The asset is already registered"),
+            ContractError::Frozen => f.write_str("The asset is frozen"),
+            ContractError::NotEnoughData => {
+                f.write_str("Not enough historical data has been collected yet")
+            }
+            ContractError::Stale => f.write_str("The price is stale"),
+            ContractError::Deviation => f.write_str("The price deviates from the average too much"),
+            ContractError::SameBlock => {
+                f.write_str("A price has already been recorded in this block")
+            }
+            ContractError::TooFrequent => {
+                f.write_str("The reporter has recorded a price too recently")
+            }
+            ContractError::AlreadySubmitted => {
+                f.write_str("The reporter has already submitted a price to this round")
+            }
+            ContractError::OutOfBounds(message) => f.write_str(message),
+            ContractError::InsufficientDeposit {
+                funds,
+                required,
+                available,
+            } => write!(
+                f,
+                "Not enough {}: {} yoctoNEAR required, {} available",
+                funds, required, available
+            ),
+            ContractError::OneYoctoRequired => {
+                f.write_str("Requires attached deposit of exactly 1 yoctoNEAR")
+            }
+            ContractError::StorageInUse => f.write_str("The storage balance is still in use"),
+            ContractError::NoImplementation => f.write_str("No implementation is set"),
+            ContractError::Decommissioned => f.write_str("The contract has been decommissioned"),
+        }
+    }
+}
+
+impl FunctionError for ContractError {
+    fn panic(&self) -> ! {
+        env::panic_str(&self.to_string())
+    }
+}
+
+/// Panics with the `error` unless the `condition` holds.
+#[cfg(feature = "contract")]
+pub fn ensure(condition: bool, error: ContractError) {
+    if !condition {
+        error.panic()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render() {
+        assert_eq!(
+            ContractError::Unauthorized("record a price").to_string(),
+            "ERR_UNAUTHORIZED: Sorry, you are not allowed to record a price"
+        );
+        assert_eq!(
+            ContractError::InsufficientDeposit {
+                funds: "deposit",
+                required: 10,
+                available: 5,
+            }
+            .to_string(),
+            "ERR_INSUFFICIENT_DEPOSIT: Not enough deposit: 10 yoctoNEAR required, 5 available"
+        );
+        assert_eq!(
+            ContractError::Stale.to_string(),
+            "ERR_STALE: The price is stale"
+        );
+    }
+}
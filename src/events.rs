@@ -0,0 +1,184 @@
+//! Structured events, logged in the [NEP-297] format so that off-chain
+//! indexers can follow the contract without issuing view calls.
+//!
+//! [NEP-297]: https://nomicon.io/Standards/EventsFormat
+
+use near_sdk::json_types::U64;
+use near_sdk::serde::Serialize;
+use near_sdk::serde_json::{self, Value};
+use near_sdk::{env, log, AccountId};
+
+use crate::asset::Crossover;
+use crate::price::Price;
+
+const STANDARD: &str = "coinmarketcap";
+const VERSION: &str = "1.0.0";
+
+/// Events emitted by the contract.
+#[derive(Serialize)]
+#[serde(
+    crate = "near_sdk::serde",
+    tag = "event",
+    content = "data",
+    rename_all = "snake_case"
+)]
+pub enum Event<'a> {
+    /// A price has been accepted.
+    PriceUpdate(PriceUpdate<'a>),
+    /// The history has been reset.
+    Reset(Reset<'a>),
+    /// The average has moved more than configured.
+    PriceDeviation(PriceDeviation<'a>),
+    /// The short-window average has crossed the average.
+    MovingAverageCross(MovingAverageCross<'a>),
+    /// A tunable of the contract or of an asset has been changed.
+    ConfigChange(ConfigChange<'a>),
+    /// No price has been recorded within the heartbeat.
+    HeartbeatMissed(HeartbeatMissed<'a>),
+    /// An upstream source has diverged from the consensus for too long.
+    SourceQuarantined(SourceQuarantined<'a>),
+}
+
+/// Payload of the [`Event::PriceUpdate`].
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceUpdate<'a> {
+    pub symbol: &'a str,
+    pub price: Price,
+    pub reporter: &'a AccountId,
+    pub round: u64,
+    /// Timestamp (in nanoseconds) of the record.
+    pub timestamp: U64,
+    /// The average after the record has been accepted, if enough historical
+    /// data has been collected.
+    pub average: Option<f64>,
+}
+
+/// Payload of the [`Event::PriceDeviation`].
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceDeviation<'a> {
+    pub symbol: &'a str,
+    /// The average before the record.
+    pub previous: f64,
+    /// The average after the record.
+    pub average: f64,
+    /// The change of the average, in basis points of the previous one.
+    pub deviation_bps: u32,
+    pub round: u64,
+    /// Timestamp (in nanoseconds) of the record.
+    pub timestamp: U64,
+}
+
+/// Payload of the [`Event::MovingAverageCross`].
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MovingAverageCross<'a> {
+    pub symbol: &'a str,
+    pub kind: Crossover,
+    pub short_average: f64,
+    pub average: f64,
+    pub round: u64,
+    /// Timestamp (in nanoseconds) of the record.
+    pub timestamp: U64,
+}
+
+/// Payload of the [`Event::HeartbeatMissed`].
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HeartbeatMissed<'a> {
+    pub symbol: &'a str,
+    /// Timestamp (in nanoseconds) of the latest record.
+    pub updated_at: U64,
+    /// The configured heartbeat, in nanoseconds.
+    pub heartbeat: U64,
+}
+
+/// Payload of the [`Event::SourceQuarantined`].
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SourceQuarantined<'a> {
+    pub symbol: &'a str,
+    pub source_id: &'a str,
+    /// Deviation of the latest price from the consensus, in basis points.
+    pub deviation_bps: u32,
+    /// Amount of the deviating prices in a row.
+    pub rounds: u16,
+    /// Timestamp (in nanoseconds) of the latest price.
+    pub timestamp: U64,
+}
+
+/// Payload of the [`Event::ConfigChange`].
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ConfigChange<'a> {
+    /// The asset, unless the tunable is of the contract.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<&'a str>,
+    pub field: &'a str,
+    pub previous: &'a Value,
+    pub value: &'a Value,
+    /// Block timestamp (in nanoseconds) the change takes effect at.
+    pub timestamp: U64,
+}
+
+/// Emits an [`Event::ConfigChange`] for every field which differs between
+/// the `previous` and the new `value` of a configuration.
+pub fn emit_config_changes<T: Serialize>(symbol: Option<&str>, previous: &T, value: &T) {
+    let (Ok(Value::Object(previous)), Ok(Value::Object(value))) =
+        (serde_json::to_value(previous), serde_json::to_value(value))
+    else {
+        return;
+    };
+    for (field, new) in &value {
+        let old = previous.get(field).unwrap_or(&Value::Null);
+        if old != new {
+            Event::ConfigChange(ConfigChange {
+                symbol,
+                field,
+                previous: old,
+                value: new,
+                timestamp: env::block_timestamp().into(),
+            })
+            .emit();
+        }
+    }
+}
+
+/// Payload of the [`Event::Reset`].
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Reset<'a> {
+    pub symbol: &'a str,
+    pub caller: &'a AccountId,
+    /// Amount of the discarded samples.
+    pub discarded: usize,
+    /// The average before the reset, if enough historical data had been
+    /// collected.
+    pub average: Option<f64>,
+    /// Block timestamp (in nanoseconds) of the reset.
+    pub timestamp: U64,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+impl Event<'_> {
+    /// Logs the event.
+    pub fn emit(&self) {
+        let log = EventLog {
+            standard: STANDARD,
+            version: VERSION,
+            event: self,
+        };
+        let json = serde_json::to_string(&log)
+            .unwrap_or_else(|_| env::panic_str("Unable to serialize an event"));
+        log!("EVENT_JSON:{}", json);
+    }
+}
@@ -0,0 +1,124 @@
+//! Reporters identified by their EVM addresses, e.g. those signing on the
+//! Aurora side.
+//!
+//! Such a reporter is the implicit account of its address, like
+//! "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed", and its prices are relayed
+//! by anyone along with a secp256k1 signature of the report.
+
+use near_sdk::borsh::{self, BorshSerialize};
+use near_sdk::json_types::{Base64VecU8, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, AccountId, FunctionError};
+
+use crate::bridge::{normalize_address, recover_address, AttestationSignature};
+use crate::error::{ensure, ContractError};
+use crate::price::Price;
+use crate::{PriceHistory, PriceHistoryExt, ReporterScope};
+
+/// Prefix of the signed digests, so they can't be replayed elsewhere.
+const DOMAIN: &[u8] = b"coinmarketcap-report";
+
+/// A price signed by an EVM reporter.
+#[derive(Debug, Clone, PartialEq, BorshSerialize, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceReport {
+    pub symbol: String,
+    pub price: Price,
+    /// When the price has been observed, in nanoseconds.
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<Option<u64>>",
+        definitions = "crate::schema::definitions::<Option<u64>>",
+    ))))]
+    pub timestamp: Option<U64>,
+    pub confidence: Option<Price>,
+    /// Greater than that of the previous report of the reporter, so a report
+    /// can't be replayed.
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<u64>",
+        definitions = "crate::schema::definitions::<u64>",
+    ))))]
+    pub nonce: U64,
+}
+
+#[near_bindgen]
+impl PriceHistory {
+    /// Allows the EVM `address` to record prices of the assets within the
+    /// `scope`, see the [`Self::add_reporter`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, when the address is not a hex EVM one, or when the scope
+    /// lists an unregistered asset.
+    pub fn add_evm_reporter(&mut self, address: String, scope: ReporterScope) {
+        self.add_reporter(evm_account(&address), scope);
+    }
+
+    /// Returns the nonce of the latest report of the EVM reporter, if any.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the address is not a hex EVM one.
+    pub fn get_evm_nonce(&self, address: String) -> Option<U64> {
+        self.evm_nonces.get(&evm_account(&address)).map(U64)
+    }
+
+    /// Returns the keccak256 digest of the report an EVM reporter signs,
+    /// which is bound to the account of the contract.
+    pub fn get_report_digest(&self, report: PriceReport) -> Base64VecU8 {
+        report_digest(&report).to_vec().into()
+    }
+
+    /// Verifies the `signature` of the report and submits its price on behalf
+    /// of the EVM reporter which has signed it, see the [`Self::record_price`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the signature is not valid, when the nonce is not
+    /// greater than that of the previous report of the reporter, or under the
+    /// same conditions as the [`Self::record_price`].
+    pub fn record_signed_price(&mut self, report: PriceReport, signature: AttestationSignature) {
+        let reporter = recover_address(&report_digest(&report), &signature)
+            .and_then(|address| address.parse::<AccountId>().ok())
+            .unwrap_or_else(|| ContractError::Unauthorized("record an unsigned price").panic());
+        ensure(
+            self.evm_nonces
+                .get(&reporter)
+                .is_none_or(|nonce| report.nonce.0 > nonce),
+            ContractError::OutOfBounds("The nonce must be greater than the previous one"),
+        );
+        // Hint: the nonces are on the contract, just like the reporters.
+        self.evm_nonces.insert(&reporter, &report.nonce.0);
+        self.submit_price(
+            reporter,
+            report.symbol,
+            report.price,
+            report.timestamp,
+            report.confidence,
+            None,
+        );
+    }
+}
+
+/// Returns the implicit account of the EVM `address`.
+///
+/// # Panics
+///
+/// Will panic when the address is not a hex EVM one.
+fn evm_account(address: &str) -> AccountId {
+    normalize_address(address)
+        .and_then(|address| address.parse().ok())
+        .unwrap_or_else(|| {
+            ContractError::OutOfBounds("The reporter must be a hex EVM address").panic()
+        })
+}
+
+/// Returns the digest of the `report` signed by an EVM reporter.
+fn report_digest(report: &PriceReport) -> [u8; 32] {
+    // Hint: the serialization into a vector never fails.
+    let message = borsh::to_vec(&(DOMAIN, env::current_account_id(), report))
+        .unwrap_or_else(|_| unreachable!());
+    env::keccak256_array(&message)
+}
@@ -0,0 +1,176 @@
+//! The minimum or the maximum price over a sliding window.
+//!
+//! The prices which may still become the extreme once the older ones leave
+//! the window are kept in a monotonic queue in the contract storage, so a new
+//! price only touches a few slots on average, unlike a rescan of the window.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+
+use crate::price::Price;
+
+/// Which extreme is tracked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub enum Kind {
+    Min,
+    Max,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+struct Candidate {
+    price: Price,
+    /// The round the price has been recorded in.
+    round: u64,
+}
+
+/// The extreme of a sliding window.
+#[derive(BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Extremum {
+    kind: Kind,
+    /// Candidates by their position in the queue; the older ones are never
+    /// better than the newer ones, the oldest is the extreme.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::LookupMap>",
+            definitions = "crate::schema::definitions::<crate::schema::LookupMap>",
+        )))
+    )]
+    candidates: LookupMap<u64, Candidate>,
+    /// Position of the oldest candidate.
+    front: u64,
+    /// Position past the newest candidate.
+    back: u64,
+}
+
+impl Extremum {
+    /// Creates an empty queue, stored under the `prefix`.
+    pub fn new(kind: Kind, prefix: Vec<u8>) -> Self {
+        Self {
+            kind,
+            candidates: LookupMap::new(prefix),
+            front: 0,
+            back: 0,
+        }
+    }
+
+    /// Returns the extreme, if there is any price in the window.
+    pub fn get(&self) -> Option<Price> {
+        self.candidate(self.front).map(|candidate| candidate.price)
+    }
+
+    /// Returns the amount of the stored candidates.
+    pub fn len(&self) -> u64 {
+        self.back - self.front
+    }
+
+    /// Accounts the `price` recorded in the `round`, which must be greater
+    /// than those of the previous prices.
+    pub fn push(&mut self, price: Price, round: u64) {
+        while self.back > self.front {
+            let newest = self.back - 1;
+            match self.candidates.get(&newest) {
+                Some(candidate) if self.outlasts(price, candidate.price) => {
+                    self.candidates.remove(&newest);
+                    self.back = newest;
+                }
+                _ => break,
+            }
+        }
+        self.candidates
+            .insert(&self.back, &Candidate { price, round });
+        self.back += 1;
+    }
+
+    /// Forgets the prices recorded before the `round`.
+    pub fn evict_before(&mut self, round: u64) {
+        while let Some(oldest) = self.candidate(self.front) {
+            if oldest.round >= round {
+                break;
+            }
+            self.candidates.remove(&self.front);
+            self.front += 1;
+        }
+    }
+
+    /// Forgets every price.
+    pub fn clear(&mut self) {
+        for position in self.front..self.back {
+            self.candidates.remove(&position);
+        }
+        self.front = self.back;
+    }
+
+    fn candidate(&self, position: u64) -> Option<Candidate> {
+        if position < self.back {
+            self.candidates.get(&position)
+        } else {
+            None
+        }
+    }
+
+    /// Checks whether the new `price` makes the older `candidate` never
+    /// become the extreme.
+    fn outlasts(&self, price: Price, candidate: Price) -> bool {
+        match self.kind {
+            Kind::Min => price <= candidate,
+            Kind::Max => price >= candidate,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+    use quickcheck_macros::quickcheck;
+
+    fn price(value: u8) -> Price {
+        Price::new(f64::from(value)).unwrap()
+    }
+
+    #[test]
+    fn sliding() {
+        let mut min = Extremum::new(Kind::Min, b"l".to_vec());
+        let mut max = Extremum::new(Kind::Max, b"u".to_vec());
+        assert_eq!(min.get(), None);
+        for (round, value) in [3, 1, 4, 1, 5].into_iter().enumerate() {
+            min.push(price(value), round as u64);
+            max.push(price(value), round as u64);
+        }
+        assert_eq!(min.get(), Some(price(1)));
+        assert_eq!(max.get(), Some(price(5)));
+
+        min.evict_before(4);
+        assert_eq!(min.get(), Some(price(5)));
+
+        max.clear();
+        assert_eq!(max.get(), None);
+    }
+
+    #[quickcheck]
+    fn window(input: Vec<u8>) -> bool {
+        // Hint: a fresh context for every case, so the gas isn't exhausted.
+        testing_env!(VMContextBuilder::new().build());
+        const WINDOW: usize = 4;
+        let mut min = Extremum::new(Kind::Min, b"l".to_vec());
+        let mut max = Extremum::new(Kind::Max, b"u".to_vec());
+        input.iter().enumerate().all(|(round, &value)| {
+            min.push(price(value), round as u64);
+            max.push(price(value), round as u64);
+            let oldest = (round + 1).saturating_sub(WINDOW);
+            min.evict_before(oldest as u64);
+            max.evict_before(oldest as u64);
+            let window = &input[oldest..=round];
+            min.get() == window.iter().copied().min().map(price)
+                && max.get() == window.iter().copied().max().map(price)
+        })
+    }
+}
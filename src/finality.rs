@@ -0,0 +1,167 @@
+//! The official answer of an asset, which lags behind the average.
+//!
+//! With a finality delay configured, a new average is only proposed as the
+//! answer, and becomes the official one once it has survived the delay
+//! without a dispute, so a manipulation within a few blocks never reaches the
+//! consumers relying on the official answer.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::serde::Serialize;
+
+use crate::fixed::Fixed;
+
+/// An average proposed as the answer.
+#[derive(Debug, Clone, Copy, PartialEq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Answer {
+    pub average: Fixed,
+    /// Timestamp of the latest record of the average, in nanoseconds.
+    pub timestamp: u64,
+    /// Height of the block the answer has been proposed in.
+    pub height: u64,
+}
+
+/// An official answer, as returned by the views.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfficialAnswer {
+    pub average: f64,
+    /// Timestamp of the latest record of the average, in nanoseconds.
+    pub timestamp: U64,
+    /// Height of the block the answer has been proposed in.
+    pub height: U64,
+}
+
+impl From<Answer> for OfficialAnswer {
+    fn from(answer: Answer) -> Self {
+        Self {
+            average: answer.average.to_f64(),
+            timestamp: answer.timestamp.into(),
+            height: answer.height.into(),
+        }
+    }
+}
+
+/// The proposed and the official answers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Finality {
+    proposed: Option<Answer>,
+    official: Option<Answer>,
+    /// The latest answer proposed while another one was pending, to be
+    /// proposed once that one settles.
+    candidate: Option<Answer>,
+}
+
+impl Finality {
+    /// Proposes the `answer` at the block `height`. While another one is
+    /// still to survive the `delay` in blocks, the `answer` is kept as the
+    /// candidate instead, so a stream of new averages never holds the
+    /// official answer back, and the latest of them is proposed once the
+    /// pending one settles.
+    pub fn propose(&mut self, answer: Answer, height: u64, delay: u64) {
+        self.settle(height, delay);
+        if self.proposed.is_none() {
+            self.proposed = Some(answer);
+        } else {
+            self.candidate = Some(answer);
+        }
+    }
+
+    /// Returns the official answer as of the block `height`, with the
+    /// `delay` in blocks.
+    pub fn official(&self, height: u64, delay: u64) -> Option<Answer> {
+        self.settled(height, delay).official
+    }
+
+    /// Returns the answer still to survive the `delay` in blocks as of the
+    /// block `height`, if any.
+    pub fn proposed(&self, height: u64, delay: u64) -> Option<Answer> {
+        self.settled(height, delay).proposed
+    }
+
+    /// Drops the answer still to survive the `delay` in blocks as of the
+    /// block `height` along with the candidate, and returns the former, if
+    /// any.
+    pub fn dispute(&mut self, height: u64, delay: u64) -> Option<Answer> {
+        self.settle(height, delay);
+        self.candidate = None;
+        self.proposed.take()
+    }
+
+    /// Makes the proposed answer official once it has survived the delay.
+    fn settle(&mut self, height: u64, delay: u64) {
+        *self = self.settled(height, delay);
+    }
+
+    /// Returns the answers as of the block `height`: the proposed answer
+    /// which has survived the `delay` is made official, and the candidate is
+    /// proposed in the block the former settled in.
+    fn settled(&self, height: u64, delay: u64) -> Self {
+        let mut finality = *self;
+        while let Some(proposed) = finality
+            .proposed
+            .filter(|proposed| is_final(proposed, height, delay))
+        {
+            finality.official = Some(proposed);
+            finality.proposed = finality.candidate.take().map(|candidate| Answer {
+                height: proposed.height.saturating_add(delay).max(candidate.height),
+                ..candidate
+            });
+        }
+        finality
+    }
+}
+
+fn is_final(answer: &Answer, height: u64, delay: u64) -> bool {
+    answer.height.saturating_add(delay) <= height
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn answer(average: u128, height: u64) -> Answer {
+        Answer {
+            average: Fixed::from_int(average).unwrap(),
+            timestamp: 0,
+            height,
+        }
+    }
+
+    #[test]
+    fn finality() {
+        let mut finality = Finality::default();
+        finality.propose(answer(1, 10), 10, 3);
+        assert_eq!(finality.official(12, 3), None);
+        assert_eq!(finality.proposed(12, 3), Some(answer(1, 10)));
+        // The pending answer isn't replaced, the latest one is proposed once
+        // it settles.
+        finality.propose(answer(2, 12), 12, 3);
+        assert_eq!(finality.official(13, 3), Some(answer(1, 10)));
+        assert_eq!(finality.proposed(13, 3), Some(answer(2, 13)));
+
+        finality.propose(answer(3, 14), 14, 3);
+        finality.propose(answer(4, 15), 15, 3);
+        assert_eq!(finality.dispute(15, 3), Some(answer(2, 13)));
+        // The candidate is dropped along with the disputed answer.
+        assert_eq!(finality.official(20, 3), Some(answer(1, 10)));
+        assert_eq!(finality.dispute(20, 3), None);
+    }
+
+    #[test]
+    fn quiet_after_burst() {
+        let mut finality = Finality::default();
+        finality.propose(answer(1, 10), 10, 3);
+        finality.propose(answer(2, 11), 11, 3);
+        // No more records: the latest average still becomes official.
+        assert_eq!(finality.official(13, 3), Some(answer(1, 10)));
+        assert_eq!(finality.proposed(13, 3), Some(answer(2, 13)));
+        assert_eq!(finality.official(15, 3), Some(answer(1, 10)));
+        assert_eq!(finality.official(16, 3), Some(answer(2, 13)));
+        assert_eq!(finality.proposed(16, 3), None);
+    }
+}
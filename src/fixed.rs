@@ -0,0 +1,361 @@
+//! Deterministic fixed-point arithmetic.
+//!
+//! Statistics are calculated with integers only, so they never depend on the
+//! floating point behavior of the host. Floats are only used to parse the
+//! input and to render the views.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+
+/// Amount of decimals of a [`Fixed`].
+pub const DECIMALS: u8 = 18;
+
+const SCALE: u128 = 10u128.pow(DECIMALS as u32);
+
+#[allow(clippy::all)]
+mod wide {
+    uint::construct_uint! {
+        /// A 256-bit unsigned integer.
+        pub struct U256(4);
+    }
+}
+
+use wide::U256;
+
+/// How to round a result which can't be represented exactly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Rounding {
+    /// Towards zero.
+    Down,
+    /// Away from zero.
+    Up,
+    /// To the nearest value, half away from zero.
+    Nearest,
+}
+
+/// A non-negative fixed-point number with 18 decimals.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, BorshDeserialize, BorshSerialize,
+)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Fixed(u128);
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed(0);
+    pub const ONE: Fixed = Fixed(SCALE);
+
+    /// Creates a number out of its raw representation, i.e. the value
+    /// multiplied by `10^18`.
+    pub const fn from_raw(raw: u128) -> Self {
+        Self(raw)
+    }
+
+    /// Returns the raw representation, i.e. the value multiplied by `10^18`.
+    pub const fn raw(self) -> u128 {
+        self.0
+    }
+
+    /// Converts an integer, returning `None` on overflow.
+    pub fn from_int(value: u128) -> Option<Self> {
+        value.checked_mul(SCALE).map(Self)
+    }
+
+    /// Converts a float, rounding it to the nearest representable number.
+    ///
+    /// Returns `None` when the value is negative, not finite, or too big.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        let scaled = (value * SCALE as f64).round();
+        // Hint: `u128::MAX as f64` is rounded up to `2^128`, hence the strict
+        // comparison.
+        if scaled.is_finite() && scaled >= 0. && scaled < u128::MAX as f64 {
+            Some(Self(scaled as u128))
+        } else {
+            None
+        }
+    }
+
+    /// Converts the number into the closest float, for the views.
+    pub fn to_f64(self) -> f64 {
+        let integer = self.0 / SCALE;
+        let fraction = self.0 % SCALE;
+        integer as f64 + fraction as f64 / SCALE as f64
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    /// Returns the distance between the numbers.
+    pub fn abs_diff(self, other: Self) -> Self {
+        Self(self.0.abs_diff(other.0))
+    }
+
+    pub fn checked_mul(self, other: Self, rounding: Rounding) -> Option<Self> {
+        mul_div(self.0, other.0, SCALE, rounding).map(Self)
+    }
+
+    /// Returns `None` on overflow or division by zero.
+    pub fn checked_div(self, other: Self, rounding: Rounding) -> Option<Self> {
+        mul_div(self.0, SCALE, other.0, rounding).map(Self)
+    }
+
+    pub fn checked_mul_int(self, other: u128) -> Option<Self> {
+        self.0.checked_mul(other).map(Self)
+    }
+
+    /// Returns `None` on division by zero.
+    pub fn checked_div_int(self, other: u128, rounding: Rounding) -> Option<Self> {
+        mul_div(self.0, 1, other, rounding).map(Self)
+    }
+
+    /// Converts the number into a fixed-point integer with the given amount
+    /// of `decimals`, returning `None` on overflow.
+    pub fn rescale(self, decimals: u8, rounding: Rounding) -> Option<u128> {
+        if decimals >= DECIMALS {
+            let factor = 10u128.checked_pow(u32::from(decimals - DECIMALS))?;
+            self.0.checked_mul(factor)
+        } else {
+            let factor = 10u128.pow(u32::from(DECIMALS - decimals));
+            mul_div(self.0, 1, factor, rounding)
+        }
+    }
+
+    /// Converts a fixed-point integer with the given amount of `decimals`
+    /// back, the inverse of [`Fixed::rescale`]. Returns `None` on overflow;
+    /// the extra decimals are truncated.
+    pub fn from_scaled(value: u128, decimals: u8) -> Option<Self> {
+        if decimals >= DECIMALS {
+            let factor = 10u128.checked_pow(u32::from(decimals - DECIMALS))?;
+            Some(Self(value / factor))
+        } else {
+            let factor = 10u128.pow(u32::from(DECIMALS - decimals));
+            value.checked_mul(factor).map(Self)
+        }
+    }
+}
+
+/// An ever-growing fixed-point accumulator with 18 decimals, wide enough to
+/// never overflow.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct WideFixed([u64; 4]);
+
+impl WideFixed {
+    /// Returns `value * factor`.
+    pub fn product(value: Fixed, factor: u64) -> Self {
+        Self::from(U256::from(value.0) * U256::from(factor))
+    }
+
+    /// Returns `value * value`, rounded down.
+    pub fn square(value: Fixed) -> Self {
+        let value = U256::from(value.0);
+        // Hint: a squared `u128` always fits.
+        Self::from(value * value / U256::from(SCALE))
+    }
+
+    pub fn saturating_add(self, other: Self) -> Self {
+        Self::from(U256::from(self).saturating_add(U256::from(other)))
+    }
+
+    pub fn saturating_sub(self, other: Self) -> Self {
+        Self::from(U256::from(self).saturating_sub(U256::from(other)))
+    }
+
+    /// Returns `self * numerator / denominator`, or `None` on overflow or
+    /// division by zero.
+    pub fn mul_div(self, numerator: u64, denominator: u64, rounding: Rounding) -> Option<Self> {
+        wide_mul_div(
+            U256::from(self),
+            U256::from(numerator),
+            U256::from(denominator),
+            rounding,
+        )
+        .map(Self::from)
+    }
+
+    /// Returns the square root, rounded down.
+    pub fn sqrt(self) -> Fixed {
+        // Hint: the root of a 256-bit number scaled once more always fits.
+        let value = U256::from(self).saturating_mul(U256::from(SCALE));
+        Fixed(value.integer_sqrt().low_u128())
+    }
+
+    /// Narrows the number, or returns `None` if it doesn't fit.
+    pub fn to_fixed(self) -> Option<Fixed> {
+        let value = U256::from(self);
+        (value.bits() <= 128).then(|| Fixed(value.low_u128()))
+    }
+
+    /// Converts the number into the closest float, for the views.
+    pub fn to_f64(self) -> f64 {
+        let value = U256::from(self);
+        let scale = U256::from(SCALE);
+        let (integer, fraction) = value.div_mod(scale);
+        let integer = integer
+            .0
+            .iter()
+            .rev()
+            .fold(0f64, |acc, &limb| acc * 2f64.powi(64) + limb as f64);
+        integer + fraction.low_u128() as f64 / SCALE as f64
+    }
+}
+
+impl From<U256> for WideFixed {
+    fn from(value: U256) -> Self {
+        Self(value.0)
+    }
+}
+
+impl From<WideFixed> for U256 {
+    fn from(value: WideFixed) -> Self {
+        U256(value.0)
+    }
+}
+
+/// Returns the square root of the sum of the squared `values`, rounded down,
+/// i.e. the values combined in quadrature.
+pub fn quadrature(values: impl IntoIterator<Item = Fixed>) -> Fixed {
+    let sum = values.into_iter().fold(U256::zero(), |sum, value| {
+        let value = U256::from(value.0);
+        sum.saturating_add(value * value)
+    });
+    // Hint: the root of a sum of squared `u128` always fits.
+    Fixed(sum.integer_sqrt().low_u128())
+}
+
+/// Returns `a * b / c` with a 256-bit intermediate product, or `None` on
+/// overflow or division by zero.
+pub fn mul_div(a: u128, b: u128, c: u128, rounding: Rounding) -> Option<u128> {
+    let result = wide_mul_div(U256::from(a), U256::from(b), U256::from(c), rounding)?;
+    (result.bits() <= 128).then(|| result.low_u128())
+}
+
+fn wide_mul_div(a: U256, b: U256, c: U256, rounding: Rounding) -> Option<U256> {
+    if c.is_zero() {
+        return None;
+    }
+    let (product, overflow) = a.overflowing_mul(b);
+    if overflow {
+        return None;
+    }
+    let (quotient, remainder) = product.div_mod(c);
+    let round_up = match rounding {
+        Rounding::Down => false,
+        Rounding::Up => !remainder.is_zero(),
+        // Hint: `remainder >= c - remainder` avoids overflowing `2 * remainder`.
+        Rounding::Nearest => remainder >= c - remainder,
+    };
+    if round_up {
+        quotient.checked_add(U256::one())
+    } else {
+        Some(quotient)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fixed(value: f64) -> Fixed {
+        Fixed::from_f64(value).unwrap()
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(Fixed::from_int(3), Some(Fixed::from_raw(3 * SCALE)));
+        assert_eq!(fixed(1.5).raw(), 1_500_000_000_000_000_000);
+        assert_eq!(fixed(42_000.25).to_f64(), 42_000.25);
+        assert_eq!(Fixed::from_f64(-1.), None);
+        assert_eq!(Fixed::from_f64(f64::NAN), None);
+        assert_eq!(Fixed::from_f64(f64::INFINITY), None);
+    }
+
+    #[test]
+    fn rounding() {
+        let two = fixed(2.);
+        let three = fixed(3.);
+        assert_eq!(
+            two.checked_div(three, Rounding::Down).unwrap().raw(),
+            666_666_666_666_666_666
+        );
+        assert_eq!(
+            two.checked_div(three, Rounding::Up).unwrap().raw(),
+            666_666_666_666_666_667
+        );
+        assert_eq!(
+            two.checked_div(three, Rounding::Nearest).unwrap().raw(),
+            666_666_666_666_666_667
+        );
+        assert_eq!(
+            Fixed::from_raw(5).checked_div_int(2, Rounding::Nearest),
+            Some(Fixed::from_raw(3))
+        );
+        assert_eq!(two.checked_div(Fixed::ZERO, Rounding::Down), None);
+    }
+
+    #[test]
+    fn checked() {
+        let big = Fixed::from_raw(u128::MAX);
+        assert_eq!(big.checked_add(Fixed::from_raw(1)), None);
+        assert_eq!(Fixed::ZERO.checked_sub(Fixed::from_raw(1)), None);
+        // The intermediate product doesn't overflow.
+        assert_eq!(big.checked_mul(Fixed::ONE, Rounding::Down), Some(big));
+        assert_eq!(big.checked_mul(fixed(2.), Rounding::Down), None);
+        assert_eq!(
+            fixed(1.5).checked_mul(fixed(2.), Rounding::Down),
+            Some(fixed(3.))
+        );
+    }
+
+    #[test]
+    fn rescale() {
+        assert_eq!(fixed(41_999.6).rescale(0, Rounding::Nearest), Some(42_000));
+        assert_eq!(
+            fixed(41_999.6).rescale(2, Rounding::Nearest),
+            Some(4_199_960)
+        );
+        assert_eq!(
+            fixed(1.).rescale(24, Rounding::Nearest),
+            Some(10u128.pow(24))
+        );
+        assert_eq!(
+            Fixed::from_raw(u128::MAX).rescale(24, Rounding::Nearest),
+            None
+        );
+    }
+
+    #[test]
+    fn quadrature() {
+        assert_eq!(super::quadrature([fixed(3.), fixed(4.)]), fixed(5.));
+        assert_eq!(super::quadrature([]), Fixed::ZERO);
+    }
+
+    #[test]
+    fn wide() {
+        let cumulative = WideFixed::product(fixed(1e14), u64::MAX);
+        assert_eq!(
+            cumulative
+                .saturating_add(cumulative)
+                .saturating_sub(cumulative),
+            cumulative
+        );
+        assert_eq!(
+            WideFixed::product(fixed(3.), 10).mul_div(1, 4, Rounding::Down),
+            Some(WideFixed::product(fixed(7.5), 1))
+        );
+        assert_eq!(WideFixed::product(fixed(2.5), 4).to_f64(), 10.);
+        assert_eq!(WideFixed::square(fixed(1.5)).to_f64(), 2.25);
+        let max = Fixed::from_int(10u128.pow(14)).unwrap();
+        assert_eq!(
+            WideFixed::square(max),
+            WideFixed::product(max, 10u64.pow(14))
+        );
+    }
+}
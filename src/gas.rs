@@ -0,0 +1,84 @@
+//! Profiling of the gas of the hot paths, so the regressions of the storage
+//! layout are caught as the windows grow.
+
+use near_sdk::json_types::U64;
+use near_sdk::serde::Serialize;
+use near_sdk::{env, near_bindgen};
+
+use crate::{PriceHistory, PriceHistoryExt};
+
+/// The gas of the hot paths of an asset, see the
+/// [`PriceHistory::get_gas_profile`].
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GasProfile {
+    pub symbol: String,
+    pub depth: u16,
+    /// Amount of the retained records.
+    pub history_len: U64,
+    /// Gas used by the latest record, up to storing it.
+    pub latest_record: U64,
+    /// The most gas used by a record.
+    pub peak_record: U64,
+    /// Gas used by each of the key views as of now, including the loading of
+    /// the asset.
+    pub views: Vec<ViewGas>,
+}
+
+/// The gas of a view.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ViewGas {
+    pub view: String,
+    pub gas: U64,
+}
+
+#[near_bindgen]
+impl PriceHistory {
+    /// Returns the gas used by the records of the asset, and measures that of
+    /// the key views as of now, for debugging.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn get_gas_profile(&self, symbol: String) -> GasProfile {
+        let asset = self.asset(&symbol);
+        let min_submissions = self.quorum.min_submissions;
+        let now = env::block_timestamp();
+        let views = vec![
+            measure("get_averages", || {
+                self.asset(&symbol)
+                    .average_info(symbol.clone(), min_submissions, now);
+            }),
+            measure("get_statistics", || {
+                self.asset(&symbol)
+                    .statistics(symbol.clone(), min_submissions, now);
+            }),
+            measure("get_all_latest_prices", || {
+                self.asset(&symbol)
+                    .latest_price(symbol.clone(), min_submissions, now);
+            }),
+            measure("get_price_at_time", || {
+                self.asset(&symbol).price_at(now);
+            }),
+        ];
+        GasProfile {
+            depth: asset.config().depth,
+            history_len: asset.history_len().into(),
+            latest_record: asset.record_gas.latest.into(),
+            peak_record: asset.record_gas.peak.into(),
+            views,
+            symbol,
+        }
+    }
+}
+
+/// Measures the gas used by the `view`.
+fn measure(view: &str, f: impl FnOnce()) -> ViewGas {
+    let initial_gas = env::used_gas();
+    f();
+    ViewGas {
+        view: view.to_string(),
+        gas: env::used_gas().saturating_sub(initial_gas).as_gas().into(),
+    }
+}
@@ -0,0 +1,125 @@
+//! The smoothed price of an asset, estimated by a Kalman filter.
+//!
+//! The price is modelled as a random walk, every record being a noisy
+//! measurement of it, so the estimate follows the records as closely as the
+//! ratio of the process noise to the measurement noise allows: the less the
+//! records are trusted, the smoother and the later the estimate.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::serde::{Deserialize, Serialize};
+
+/// Tunables of the filter, as variances in the squared units of the price.
+#[derive(
+    Debug, Clone, Copy, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct KalmanConfig {
+    /// How much the price is expected to move between the records.
+    pub process_noise: f64,
+    /// How much a record is expected to be off the price.
+    pub measurement_noise: f64,
+}
+
+impl KalmanConfig {
+    /// Checks the noises are finite, and the measurement one is positive.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let finite = self.process_noise.is_finite() && self.measurement_noise.is_finite();
+        if finite && self.process_noise >= 0. && self.measurement_noise > 0. {
+            Ok(())
+        } else {
+            Err("Kalman noises must be finite, the measurement one positive")
+        }
+    }
+}
+
+/// The state of the filter.
+#[derive(Debug, Clone, Copy, PartialEq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Kalman {
+    estimate: f64,
+    variance: f64,
+    /// Timestamp of the latest measurement, in nanoseconds.
+    timestamp: u64,
+}
+
+/// A smoothed price, as returned by the views.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SmoothedPrice {
+    pub price: f64,
+    /// Variance of the estimate, in the squared units of the price.
+    pub variance: f64,
+    /// Timestamp of the latest record, in nanoseconds.
+    pub timestamp: U64,
+}
+
+impl From<Kalman> for SmoothedPrice {
+    fn from(kalman: Kalman) -> Self {
+        Self {
+            price: kalman.estimate,
+            variance: kalman.variance,
+            timestamp: kalman.timestamp.into(),
+        }
+    }
+}
+
+impl Kalman {
+    /// Starts the filter at the first `value`, as uncertain as a record.
+    pub fn new(config: KalmanConfig, value: f64, timestamp: u64) -> Self {
+        Self {
+            estimate: value,
+            variance: config.measurement_noise,
+            timestamp,
+        }
+    }
+
+    /// Moves the estimate towards the measured `value`.
+    pub fn update(&mut self, config: KalmanConfig, value: f64, timestamp: u64) {
+        let predicted = self.variance + config.process_noise;
+        let gain = predicted / (predicted + config.measurement_noise);
+        self.estimate += gain * (value - self.estimate);
+        self.variance = (1. - gain) * predicted;
+        self.timestamp = timestamp;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn kalman() {
+        let config = KalmanConfig {
+            process_noise: 1.,
+            measurement_noise: 1.,
+        };
+        let mut kalman = Kalman::new(config, 10., 0);
+        // The gain is 2/3: the predicted variance is 2.
+        kalman.update(config, 13., 1);
+        let smoothed = SmoothedPrice::from(kalman);
+        assert!((smoothed.price - 12.).abs() < 1e-12);
+        assert!((smoothed.variance - 2. / 3.).abs() < 1e-12);
+        assert_eq!(smoothed.timestamp, U64(1));
+
+        // Without the process noise, the filter converges to the mean.
+        let config = KalmanConfig {
+            process_noise: 0.,
+            measurement_noise: 1.,
+        };
+        let mut kalman = Kalman::new(config, 1., 0);
+        for value in [2., 3., 4.] {
+            kalman.update(config, value, 0);
+        }
+        assert!((SmoothedPrice::from(kalman).price - 2.5).abs() < 1e-12);
+        assert!(KalmanConfig {
+            process_noise: 0.,
+            measurement_noise: 0.,
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,93 @@
+//! Housekeeping anyone may trigger, so the liveness of the feeds doesn't
+//! depend on the owner alone.
+
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::Serialize;
+use near_sdk::{env, log, near_bindgen};
+
+use crate::events;
+use crate::{LogLevel, PriceHistory, PriceHistoryExt};
+
+/// What a [`PriceHistory::poke`] has done.
+#[derive(Debug, Default, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Housekeeping {
+    /// Amount of the pending rounds closed past their submission window,
+    /// recorded or discarded.
+    pub rounds_closed: u32,
+    /// Amount of the assets which have missed their heartbeat.
+    pub heartbeats_missed: u32,
+    /// Amount of the archived records pruned past their retention.
+    pub records_pruned: U64,
+    /// The reward of the caller, in the payment token units.
+    pub reward: U128,
+}
+
+#[near_bindgen]
+impl PriceHistory {
+    /// Performs the due housekeeping of every asset: closes the pending
+    /// rounds past their submission window, recording those with enough
+    /// submissions, emits a `heartbeat_missed` event once per stale record,
+    /// and prunes the archive past its retention. The caller earns the keeper
+    /// reward, see [`Self::set_reward_config`], when anything has been done.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the storage balance of the account which has paid for
+    /// an asset is not enough for its recorded round.
+    pub fn poke(&mut self) -> Housekeeping {
+        let now = env::block_timestamp();
+        let mut housekeeping = Housekeeping::default();
+        for symbol in self.assets.keys_as_vector().to_vec() {
+            let mut asset = self.asset(&symbol);
+            #[cfg_attr(not(feature = "rounds"), allow(unused_mut))]
+            let mut initial_storage_usage = env::storage_usage();
+            let mut changed = false;
+            #[cfg(feature = "rounds")]
+            if !asset.frozen
+                && self.decommission.is_none()
+                && asset.pending.is_expired(&self.quorum, now)
+            {
+                housekeeping.rounds_closed += 1;
+                changed = true;
+                let submitted = asset.pending.submitted();
+                if let Some(submissions) = asset.pending.close(self.quorum.min_submissions) {
+                    self.record_round(&symbol, asset, submissions, initial_storage_usage);
+                    asset = self.asset(&symbol);
+                    initial_storage_usage = env::storage_usage();
+                } else {
+                    self.counters.submissions_rejected += submitted as u64;
+                }
+            }
+            if let Some(latest) = asset.report_missed_heartbeat(now) {
+                housekeeping.heartbeats_missed += 1;
+                changed = true;
+                events::Event::HeartbeatMissed(events::HeartbeatMissed {
+                    symbol: &symbol,
+                    updated_at: latest.timestamp.into(),
+                    heartbeat: asset.config().heartbeat,
+                })
+                .emit();
+            }
+            #[cfg(feature = "archive")]
+            {
+                let pruned = asset.prune_expired(now);
+                if pruned > 0 {
+                    housekeeping.records_pruned.0 += pruned;
+                    changed = true;
+                }
+            }
+            if changed {
+                self.assets.insert(&symbol, &asset);
+                self.settle_storage(&asset.storage_payer, initial_storage_usage);
+            }
+        }
+        if housekeeping != Housekeeping::default() {
+            housekeeping.reward = self.reward_keeper(&env::predecessor_account_id()).into();
+            if self.log_level.allows(LogLevel::Debug) {
+                log!("Housekeeping done: {:?}", housekeeping);
+            }
+        }
+        housekeeping
+    }
+}
@@ -1,124 +1,4850 @@
 //! Storing historical price data.
+//!
+//! The contract is behind the default `contract` feature. Without it the crate
+//! is a plain library of the [`aggregation`] core, so the off-chain bots and
+//! simulators compute exactly the same answers as the contract.
 
-use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::{env, near_bindgen};
+#[cfg(feature = "contract")]
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "contract")]
+use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet, Vector};
+#[cfg(feature = "contract")]
+use near_sdk::json_types::{U128, U64};
+#[cfg(feature = "contract")]
+use near_sdk::serde::Serialize;
+#[cfg(feature = "contract")]
+use near_sdk::serde_json::json;
+#[cfg(feature = "contract")]
+use near_sdk::{
+    env, log, near_bindgen, AccountId, BorshStorageKey, FunctionError, NearToken, PanicOnDefault,
+    Promise, StorageUsage,
+};
 
-near_sdk::setup_alloc!();
+/// Declares the `items` of the contract layer, compiled with the `contract`
+/// feature only.
+macro_rules! contract {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "contract")]
+            $item
+        )*
+    };
+}
+
+mod archive;
+mod asset;
+mod cumulative;
+mod error;
+mod extremum;
+mod finality;
+mod fixed;
+mod kalman;
+mod observation;
+mod price;
+mod ring;
+mod round;
+#[cfg(feature = "schema")]
+mod schema;
+
+contract! {
+    mod access;
+    mod bridge;
+    mod compat;
+    mod counters;
+    mod delivery;
+    mod events;
+    mod evm;
+    mod gas;
+    mod keeper;
+    mod logging;
+    mod proxy;
+    mod quote;
+    mod reporter;
+    mod rewards;
+    mod slashing;
+    mod storage;
+    mod subscription;
+    mod token;
+}
+
+/// The aggregation core, shared by the contract and the off-chain code: the
+/// history of an asset with its running statistics, and the rounds of the
+/// submissions it records the medians of.
+///
+/// The history lives in the `near_sdk` collections, which are backed by the
+/// mocked blockchain off-chain.
+pub mod aggregation {
+    pub use crate::asset::{
+        within_bps, Asset, AssetConfig, AssetMetadata, PriceRecord, PriceStatistics, Quality,
+        MAX_DECIMALS,
+    };
+    pub use crate::finality::{Answer, Finality};
+    pub use crate::fixed::{Fixed, Rounding};
+    pub use crate::kalman::{Kalman, KalmanConfig};
+    pub use crate::price::{Price, MAX_PRICE};
+    #[cfg(feature = "serde")]
+    pub use crate::ring::Latest;
+    pub use crate::ring::Ring;
+    pub use crate::round::{confidence, median, PendingRound, Provenance, Quorum, Submission};
+}
+
+pub use archive::ArchiveEncoding;
+pub use asset::{
+    Answers, AssetConfig, AssetConfigPatch, AssetInfo, AssetMetadata, AssetRegistration,
+    AttributedEntry, AverageInfo, EpochSnapshot, Forecast, Gap, History, HistoryEntry, LatestPrice,
+    LogReturn, Macd, Order, PeriodReturn, PriceAtTime, PriceBand, PriceStatistics, Quality,
+    SourceDivergence, SourcePrice,
+};
+pub use cumulative::CumulativePrice;
+pub use error::ContractError;
+pub use finality::OfficialAnswer;
+pub use fixed::Rounding;
+pub use kalman::{KalmanConfig, SmoothedPrice};
+pub use price::Price;
+pub use round::{Provenance, Quorum, Submission};
+
+contract! {
+    pub use bridge::{Attestation, AttestationSignature, BridgeSigners};
+    pub use compat::PythPrice;
+    pub use counters::Stats;
+    pub use delivery::PriceConsumer;
+    pub use evm::PriceReport;
+    pub use gas::{GasProfile, ViewGas};
+    pub use keeper::Housekeeping;
+    pub use logging::LogLevel;
+    pub use reporter::{ReporterInfo, ReporterScope};
+    pub use rewards::{ReporterRewards, RewardConfig};
+    pub use slashing::{SlashProposal, SlashStatus};
+    pub use storage::{StorageBalance, StorageBalanceBounds, StorageReport};
+    pub use subscription::{SubscriberInfo, Subscription};
+    pub use token::PaymentToken;
+
+    use asset::{Asset, PriceRecord, MAX_DECIMALS};
+    use counters::Counters;
+    use error::ensure;
+    use fixed::Fixed;
+    use subscription::PendingNotifications;
+}
+
+#[cfg(feature = "indicators")]
+use observation::{MAX_CARDINALITY, OBSERVATION_BYTES};
+
+/// Amount of yoctoNEAR.
+type Balance = u128;
+
+contract! {
+    /// Version of the contract.
+    const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+    /// Identifier of the build, provided with the `BUILD_ID` environment
+    /// variable at the compile time, e.g. a git commit hash.
+    const BUILD_ID: &str = match option_env!("BUILD_ID") {
+        Some(build_id) => build_id,
+        None => "unknown",
+    };
+
+    /// Amount of entries returned by paged views by default.
+    const DEFAULT_PAGE_LIMIT: u64 = 50;
+
+    /// Symbol of the NEAR feed, used to convert between NEAR and the assets.
+    const NEAR_SYMBOL: &str = "NEAR";
 
-mod fifo;
+    /// Amount of decimals of NEAR, i.e. the yoctoNEAR.
+    const NEAR_DECIMALS: u8 = 24;
 
-const HISTORY_DEPTH: usize = 5;
+    /// Seconds the time-weighted average price is calculated over by
+    /// default.
+    const DEFAULT_TWAP_PERIOD: u64 = 1800;
 
-/// A contract that's able to store a historical data and making an average out
-/// of it.
+    /// Standard deviations of the prices on either side of the average in a
+    /// price band by default.
+    const DEFAULT_BAND_DEVIATIONS: u8 = 2;
+
+    /// Weight of an upstream source, unless set otherwise.
+    const DEFAULT_SOURCE_WEIGHT: u32 = 1;
+}
+
+#[cfg(feature = "contract")]
+/// Version and build of the deployed contract.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VersionInfo {
+    pub version: String,
+    pub build_id: String,
+    /// Whether the contract has been decommissioned, so the views are
+    /// deprecated.
+    pub deprecated: bool,
+    /// The account serving the feeds instead, if any.
+    pub successor: Option<AccountId>,
+}
+
+#[cfg(feature = "contract")]
+/// The end of life of the contract.
+#[derive(BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+struct Decommission {
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<Option<String>>",
+            definitions = "crate::schema::definitions::<Option<String>>",
+        )))
+    )]
+    successor: Option<AccountId>,
+    /// Block timestamp of the decommission, in nanoseconds.
+    timestamp: u64,
+}
+
+#[cfg(feature = "contract")]
+/// Storage prefixes of the collections.
+#[derive(BorshStorageKey, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+enum StorageKey {
+    Assets,
+    Reporters,
+    StorageBalances,
+    Allowances,
+    TokenCredits,
+    Consumers,
+    History { asset_id: u64 },
+    SlashProposals,
+    TokenRewards,
+    Conversions,
+    Subscribers,
+    Notifications,
+    EvmNonces,
+    SourceWeights,
+}
+
+#[cfg(feature = "contract")]
+/// A contract that's able to store a historical data for a number of assets
+/// and making an average out of it.
 #[near_bindgen]
-#[derive(Default, BorshDeserialize, BorshSerialize)]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
 pub struct PriceHistory {
-    price_history: fifo::Fifo<f64, HISTORY_DEPTH>,
-    recorded: u16,
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::UnorderedMap>",
+            definitions = "crate::schema::definitions::<crate::schema::UnorderedMap>",
+        )))
+    )]
+    assets: UnorderedMap<String, Asset>,
+    /// Accounts, apart from the contract's one, allowed to record prices.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::UnorderedMap>",
+            definitions = "crate::schema::definitions::<crate::schema::UnorderedMap>",
+        )))
+    )]
+    reporters: UnorderedMap<AccountId, ReporterScope>,
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::LookupMap>",
+            definitions = "crate::schema::definitions::<crate::schema::LookupMap>",
+        )))
+    )]
+    storage_balances: LookupMap<AccountId, StorageBalance>,
+    log_level: LogLevel,
+    /// Fee charged for a premium view.
+    access_fee: Balance,
+    /// Prepaid allowances of the consumers of the premium views.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::LookupMap>",
+            definitions = "crate::schema::definitions::<crate::schema::LookupMap>",
+        )))
+    )]
+    allowances: LookupMap<AccountId, Balance>,
+    /// Access fees collected in NEAR, which are the revenue of the owner
+    /// rather than the rewards of the reporters.
+    fee_revenue: Balance,
+    payment_token: Option<PaymentToken>,
+    /// Prepaid credits of the consumers, in the payment token units.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::LookupMap>",
+            definitions = "crate::schema::definitions::<crate::schema::LookupMap>",
+        )))
+    )]
+    token_credits: LookupMap<AccountId, Balance>,
+    /// Collected fees, in the payment token units.
+    token_reward_pool: Balance,
+    /// Whether only the `consumers` may use the premium views.
+    consumer_allowlist: bool,
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::UnorderedSet>",
+            definitions = "crate::schema::definitions::<crate::schema::UnorderedSet>",
+        )))
+    )]
+    consumers: UnorderedSet<AccountId>,
+    /// The aggregator the views are forwarded to in the proxy mode.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<Option<String>>",
+            definitions = "crate::schema::definitions::<Option<String>>",
+        )))
+    )]
+    implementation: Option<AccountId>,
+    /// Number of assets registered over the lifetime of the contract, so
+    /// each price history gets its own storage prefix.
+    assets_registered: u64,
+    /// Whether the admin functions are locked forever.
+    renounced: bool,
+    /// Set once no more prices are accepted.
+    decommission: Option<Decommission>,
+    /// Submissions aggregated into every record.
+    quorum: Quorum,
+    /// Amount of the reporters on duty per epoch, if they take turns.
+    rotation: Option<u16>,
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::Vector>",
+            definitions = "crate::schema::definitions::<crate::schema::Vector>",
+        )))
+    )]
+    slash_proposals: Vector<SlashProposal>,
+    /// Rewards of the reporters, in the payment token units.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::LookupMap>",
+            definitions = "crate::schema::definitions::<crate::schema::LookupMap>",
+        )))
+    )]
+    token_rewards: LookupMap<AccountId, ReporterRewards>,
+    reward_config: RewardConfig,
+    /// Conversion feeds between the quote currencies, see the [`quote`].
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::LookupMap>",
+            definitions = "crate::schema::definitions::<crate::schema::LookupMap>",
+        )))
+    )]
+    conversions: LookupMap<String, String>,
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::UnorderedMap>",
+            definitions = "crate::schema::definitions::<crate::schema::UnorderedMap>",
+        )))
+    )]
+    subscribers: UnorderedMap<AccountId, Subscription>,
+    /// Notifications of the latest records yet to be sent to the subscribers.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::LookupMap>",
+            definitions = "crate::schema::definitions::<crate::schema::LookupMap>",
+        )))
+    )]
+    notifications: LookupMap<String, PendingNotifications>,
+    /// Foreign signers of the bridged prices, see the [`bridge`].
+    bridge_signers: Option<BridgeSigners>,
+    /// Nonces of the latest reports of the EVM reporters, see the [`evm`].
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::LookupMap>",
+            definitions = "crate::schema::definitions::<crate::schema::LookupMap>",
+        )))
+    )]
+    evm_nonces: LookupMap<AccountId, u64>,
+    counters: Counters,
+    /// Weights of the upstream sources other than the default one.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::LookupMap>",
+            definitions = "crate::schema::definitions::<crate::schema::LookupMap>",
+        )))
+    )]
+    source_weights: LookupMap<String, u32>,
 }
 
+#[cfg(feature = "contract")]
 #[near_bindgen]
 impl PriceHistory {
-    /// Returns the average price.
+    /// Initializes the contract with the given logging verbosity (`Info` by
+    /// default).
     ///
     /// # Panics
     ///
-    /// Will panic when not enough historical data has been collected.
-    pub fn get_average(&self) -> f64 {
-        static_assertions::const_assert_ne!(HISTORY_DEPTH, 0);
-        if usize::from(self.recorded) != HISTORY_DEPTH {
-            env::panic(b"Not enough historical data has been collected yet")
+    /// Will panic when the contract has already been initialized, so the live
+    /// feeds can never be replaced with the defaults.
+    #[init]
+    pub fn new(log_level: Option<LogLevel>) -> Self {
+        Self {
+            assets: UnorderedMap::new(StorageKey::Assets),
+            reporters: UnorderedMap::new(StorageKey::Reporters),
+            storage_balances: LookupMap::new(StorageKey::StorageBalances),
+            log_level: log_level.unwrap_or_default(),
+            access_fee: 0,
+            allowances: LookupMap::new(StorageKey::Allowances),
+            fee_revenue: 0,
+            payment_token: None,
+            token_credits: LookupMap::new(StorageKey::TokenCredits),
+            token_reward_pool: 0,
+            consumer_allowlist: false,
+            consumers: UnorderedSet::new(StorageKey::Consumers),
+            implementation: None,
+            assets_registered: 0,
+            renounced: false,
+            decommission: None,
+            quorum: Quorum::default(),
+            rotation: None,
+            slash_proposals: Vector::new(StorageKey::SlashProposals),
+            token_rewards: LookupMap::new(StorageKey::TokenRewards),
+            conversions: LookupMap::new(StorageKey::Conversions),
+            subscribers: UnorderedMap::new(StorageKey::Subscribers),
+            notifications: LookupMap::new(StorageKey::Notifications),
+            bridge_signers: None,
+            evm_nonces: LookupMap::new(StorageKey::EvmNonces),
+            reward_config: RewardConfig::default(),
+            counters: Counters::default(),
+            source_weights: LookupMap::new(StorageKey::SourceWeights),
         }
-        let sum: f64 = self.price_history.iter().sum();
-        sum / HISTORY_DEPTH as f64
     }
 
-    /// Adds the provided `price` to the storage.
+    /// Registers an asset, prices of which are represented with the given
+    /// amount of `decimals` in fixed-point views. When no `config` is
+    /// provided, the default one is used.
+    ///
+    /// The storage is paid from the caller's storage balance, see
+    /// [`Self::storage_deposit`].
     ///
     /// # Panics
     ///
     /// Will panic when called not from the account which was used to deployed
-    /// the contract.
-    pub fn record_price(&mut self, price: f64) {
-        if env::signer_account_id() != env::current_account_id() {
-            // Prevent others from adding possibly malicious records.
-            env::panic(b"Sorry, you are not allowed to record a price")
+    /// the contract, when the asset is already registered, when `decimals`
+    /// exceed 24, when the `config` is invalid, or when the caller's storage
+    /// balance is not enough.
+    pub fn register_asset(
+        &mut self,
+        symbol: String,
+        decimals: u8,
+        metadata: AssetMetadata,
+        config: Option<AssetConfig>,
+    ) {
+        self.register_assets(vec![AssetRegistration {
+            symbol,
+            decimals,
+            metadata,
+            config,
+        }]);
+    }
+
+    /// Registers a batch of assets, see the [`Self::register_asset`]. Either
+    /// every asset is registered or none of them is.
+    ///
+    /// # Panics
+    ///
+    /// Will panic under the same conditions as the [`Self::register_asset`]
+    /// for any of the assets, or when the batch repeats a symbol.
+    pub fn register_assets(&mut self, assets: Vec<AssetRegistration>) {
+        self.assert_owner("register an asset");
+        self.assert_active();
+        let initial_storage_usage = env::storage_usage();
+        let storage_payer = env::predecessor_account_id();
+        for registration in assets {
+            let AssetRegistration {
+                symbol,
+                decimals,
+                metadata,
+                config,
+            } = registration;
+            ensure(
+                decimals <= MAX_DECIMALS,
+                ContractError::OutOfBounds("Too many decimals"),
+            );
+            ensure(
+                self.assets.get(&symbol).is_none(),
+                ContractError::AlreadyRegistered,
+            );
+            let config = config.unwrap_or_default();
+            Self::validate_config(&config);
+            let history_prefix = StorageKey::History {
+                asset_id: self.assets_registered,
+            };
+            self.assets_registered += 1;
+            self.assets.insert(
+                &symbol,
+                &Asset::new(
+                    decimals,
+                    metadata,
+                    config,
+                    storage_payer.clone(),
+                    history_prefix,
+                ),
+            );
+            if self.log_level.allows(LogLevel::Info) {
+                log!("Registered {} with {} decimals", symbol, decimals);
+            }
         }
-        if usize::from(self.recorded) < HISTORY_DEPTH {
-            // Hint: no overflow check required until the const assert holds
-            // true.
-            static_assertions::const_assert_ne!(HISTORY_DEPTH, usize::MAX);
-            self.recorded += 1;
+        // Hint: a panic reverts the whole batch, so there is nothing to undo.
+        self.settle_storage(&storage_payer, initial_storage_usage);
+    }
+
+    /// Returns the metadata of the asset.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn get_asset_metadata(&self, symbol: String) -> AssetMetadata {
+        self.asset(&symbol).metadata()
+    }
+
+    /// Returns the configuration of the asset.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn get_asset_config(&self, symbol: String) -> AssetConfig {
+        self.asset(&symbol).config().clone()
+    }
+
+    /// Replaces the configuration of the asset.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, when the asset is not registered, or when the `config`
+    /// is invalid.
+    pub fn set_asset_config(&mut self, symbol: String, config: AssetConfig) {
+        self.assert_owner("configure an asset");
+        let mut asset = self.asset(&symbol);
+        Self::validate_config(&config);
+        if self.log_level.allows(LogLevel::Info) {
+            log!("{} configuration changed to {:?}", symbol, config);
         }
-        self.price_history.push(price)
+        events::emit_config_changes(Some(&symbol), asset.config(), &config);
+        asset.set_config(config);
+        self.assets.insert(&symbol, &asset);
     }
 
-    /// Returns the depth of the recorded history.
-    pub fn depth_so_far(&self) -> usize {
-        usize::from(self.recorded)
+    /// Changes only the tunables of the asset present in the `patch`,
+    /// keeping the others, instead of replacing the whole configuration.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, when the asset is not registered, or when the patched
+    /// configuration is invalid.
+    pub fn update_asset_config(&mut self, symbol: String, patch: AssetConfigPatch) {
+        let config = self.asset(&symbol).config().patched(patch);
+        self.set_asset_config(symbol, config);
     }
 
-    /// Returns the amount of required historical data to calculate the average.
-    pub fn required_depth(&self) -> usize {
-        HISTORY_DEPTH
+    /// Removes the asset along with its history, refunding the storage to
+    /// the account which has paid for it.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, or when the asset is not registered.
+    pub fn remove_asset(&mut self, symbol: String) {
+        self.assert_owner("remove an asset");
+        let initial_storage_usage = env::storage_usage();
+        let mut asset = self.asset(&symbol);
+        asset.release();
+        self.assets.remove(&symbol);
+        self.settle_storage(&asset.storage_payer, initial_storage_usage);
+        if self.log_level.allows(LogLevel::Info) {
+            log!("{} has been removed", symbol);
+        }
+    }
+
+    /// Moves the asset, along with its history, configuration, reporter
+    /// permissions, subscriptions and pending notifications, to the
+    /// `new_symbol`. The storage difference is settled
+    /// with the account which has paid for the asset.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, when the `old_symbol` is not registered, when the
+    /// `new_symbol` is already registered, or when the storage balance is
+    /// not enough.
+    pub fn migrate_asset(&mut self, old_symbol: String, new_symbol: String) {
+        self.assert_owner("migrate an asset");
+        let asset = self.asset(&old_symbol);
+        ensure(
+            self.assets.get(&new_symbol).is_none(),
+            ContractError::AlreadyRegistered,
+        );
+        let initial_storage_usage = env::storage_usage();
+        self.assets.remove(&old_symbol);
+        self.assets.insert(&new_symbol, &asset);
+
+        let renamed: Vec<_> = self
+            .reporters
+            .iter()
+            .filter_map(|(account_id, mut scope)| {
+                scope
+                    .rename(&old_symbol, &new_symbol)
+                    .then_some((account_id, scope))
+            })
+            .collect();
+        for (account_id, scope) in renamed {
+            self.reporters.insert(&account_id, &scope);
+        }
+        // Hint: renaming keeps the order of the subscribers, so the cursor of
+        // the pending notifications stays valid.
+        let renamed: Vec<_> = self
+            .subscribers
+            .iter()
+            .filter_map(|(account_id, mut subscription)| {
+                subscription
+                    .rename(&old_symbol, &new_symbol)
+                    .then_some((account_id, subscription))
+            })
+            .collect();
+        for (account_id, subscription) in renamed {
+            self.subscribers.insert(&account_id, &subscription);
+        }
+        if let Some(pending) = self.notifications.remove(&old_symbol) {
+            self.notifications.insert(&new_symbol, &pending);
+        }
+        self.settle_storage(&asset.storage_payer, initial_storage_usage);
+
+        if self.log_level.allows(LogLevel::Info) {
+            log!("{} has been migrated to {}", old_symbol, new_symbol);
+        }
     }
 
-    /// Forgets the history.
-    pub fn reset(&mut self) {
-        self.recorded = 0;
-        env::log(b"History has been reset");
+    /// Stops accepting records of the asset.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, or when the asset is not registered.
+    pub fn freeze_asset(&mut self, symbol: String) {
+        self.set_frozen(symbol, true)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use near_sdk::MockedBlockchain;
-    use near_sdk::{testing_env, VMContext};
-
-    // Hint: copied from one of NEAR SDK examples.
-    fn get_context(input: Vec<u8>, is_view: bool) -> VMContext {
-        VMContext {
-            current_account_id: "alice.testnet".to_string(),
-            signer_account_id: "robert.testnet".to_string(),
-            signer_account_pk: vec![0, 1, 2],
-            predecessor_account_id: "jane.testnet".to_string(),
-            input,
-            block_index: 0,
-            block_timestamp: 0,
-            account_balance: 0,
-            account_locked_balance: 0,
-            storage_usage: 0,
-            attached_deposit: 0,
-            prepaid_gas: 10u64.pow(18),
-            random_seed: vec![0, 1, 2],
-            is_view,
-            output_data_receivers: vec![],
-            epoch_height: 19,
+    /// Resumes accepting records of the asset.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, or when the asset is not registered.
+    pub fn unfreeze_asset(&mut self, symbol: String) {
+        self.set_frozen(symbol, false)
+    }
+
+    /// Checks whether the asset is frozen.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn is_frozen(&self, symbol: String) -> bool {
+        self.asset(&symbol).frozen
+    }
+
+    /// Checks whether the asset is registered.
+    pub fn asset_exists(&self, symbol: String) -> bool {
+        self.assets.get(&symbol).is_some()
+    }
+
+    /// Returns up to `limit` (50 by default) registered assets, starting
+    /// from the `from_index`.
+    pub fn list_assets(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<AssetInfo> {
+        self.map_assets(from_index, limit, |symbol, asset| asset.info(symbol))
+    }
+
+    /// Returns the latest prices of up to `limit` (50 by default) registered
+    /// assets, starting from the `from_index`.
+    pub fn get_all_latest_prices(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<LatestPrice> {
+        let now = env::block_timestamp();
+        self.map_assets(from_index, limit, |symbol, asset| {
+            asset.latest_price(symbol, self.quorum.min_submissions, now)
+        })
+    }
+
+    /// Returns the amount of decimals used for the fixed-point representation
+    /// of the asset's prices.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn get_decimals(&self, symbol: String) -> u8 {
+        self.asset(&symbol).decimals
+    }
+
+    /// Returns the average price, in the `quote` currency if requested, see
+    /// the [`Self::set_conversion_feed`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered, when not enough
+    /// historical data has been collected, when the price is stale, or when
+    /// it can't be converted into the `quote` currency.
+    pub fn get_average(&self, symbol: String, quote: Option<String>) -> f64 {
+        let asset = self.asset(&symbol);
+        let average = Self::require_average(&asset);
+        match quote {
+            Some(quote) => self.requote(average, &asset.metadata().quote, &quote),
+            None => average,
         }
+        .to_f64()
     }
 
-    #[test]
-    fn record() {
-        let context = get_context(vec![], false);
-        testing_env!(context);
-        let mut counter = PriceHistory::default();
+    /// Returns the average of the asset with the prices below the lower
+    /// `pct` percent of the window and above the upper one clamped to the
+    /// bounds rather than dropped, which dampens the outliers of a small
+    /// window.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered, when the `pct` isn't
+    /// below 50, when not enough historical data has been collected, or when
+    /// the price is stale.
+    pub fn get_winsorized_mean(&self, symbol: String, pct: u8) -> f64 {
+        ensure(
+            pct < 50,
+            ContractError::OutOfBounds("The percentile must be below 50"),
+        );
+        let asset = self.asset(&symbol);
+        Self::require_average(&asset);
+        asset
+            .winsorized_mean(pct)
+            .unwrap_or_else(|| ContractError::NotEnoughData.panic())
+            .to_f64()
+    }
+
+    /// Returns the latest price of the asset along with the average and the
+    /// time-weighted average price over the `twap_period` seconds (1800 by
+    /// default), so a consumer can apply a rule like "the lower of the spot
+    /// and the TWAP" with a single call. Unlike the [`Self::get_average`],
+    /// doesn't panic when there is not enough data or the price is stale.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn get_answers(&self, symbol: String, twap_period: Option<u64>) -> Answers {
+        self.asset(&symbol).answers(
+            symbol,
+            self.quorum.min_submissions,
+            env::block_timestamp(),
+            twap_period.unwrap_or(DEFAULT_TWAP_PERIOD),
+        )
+    }
+
+    /// Returns the average of the asset with the prices `deviations`
+    /// standard deviations (2 by default) below and above it: the
+    /// conservative one, e.g. for the collaterals of a lending protocol, and
+    /// the aggressive one, e.g. for its debts, so either check errs on the
+    /// safe side while the prices are volatile.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered, when not enough
+    /// historical data has been collected, or when the price is stale.
+    pub fn get_price_band(&self, symbol: String, deviations: Option<u8>) -> PriceBand {
+        let asset = self.asset(&symbol);
+        Self::require_average(&asset);
+        asset
+            .price_band(symbol, deviations.unwrap_or(DEFAULT_BAND_DEVIATIONS))
+            .unwrap_or_else(|| ContractError::OutOfBounds("The band overflows").panic())
+    }
+
+    /// Returns an estimate of the price of the asset `horizon_ns`
+    /// nanoseconds from now, extrapolated by the linear trend of the window,
+    /// within `deviations` standard errors of the prediction (2 by default).
+    /// Unlike the recorded prices, it's only meant to be shown, not relied on.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered, or without at least
+    /// three records apart in time.
+    #[cfg(feature = "indicators")]
+    pub fn get_forecast(
+        &self,
+        symbol: String,
+        horizon_ns: U64,
+        deviations: Option<u8>,
+    ) -> Forecast {
+        self.asset(&symbol)
+            .forecast(
+                symbol,
+                env::block_timestamp().saturating_add(horizon_ns.0),
+                deviations.unwrap_or(DEFAULT_BAND_DEVIATIONS),
+            )
+            .unwrap_or_else(|| ContractError::NotEnoughData.panic())
+    }
+
+    /// Returns the average prices of the assets along with their freshness.
+    /// Unlike the [`Self::get_average`], doesn't panic when there is not
+    /// enough data or the price is stale.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when any of the assets is not registered.
+    pub fn get_averages(&self, symbols: Vec<String>) -> Vec<AverageInfo> {
+        let now = env::block_timestamp();
+        symbols
+            .into_iter()
+            .map(|symbol| {
+                self.asset(&symbol)
+                    .average_info(symbol, self.quorum.min_submissions, now)
+            })
+            .collect()
+    }
+
+    /// Returns the average, the minimum, the maximum and the variance of the
+    /// prices within the configured depth. The statistics are kept up to date
+    /// on every record, so the view doesn't go through the history.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered, or when not enough
+    /// historical data has been collected.
+    #[cfg(feature = "indicators")]
+    pub fn get_statistics(&self, symbol: String) -> PriceStatistics {
+        self.asset(&symbol)
+            .statistics(symbol, self.quorum.min_submissions, env::block_timestamp())
+            .unwrap_or_else(|| ContractError::NotEnoughData.panic())
+    }
+
+    /// Returns the annualized volatility of the window of the asset,
+    /// estimated from the log returns between its records and the time
+    /// between them, e.g. `0.8` for 80% a year, or `None` without at least
+    /// two records apart in time.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    #[cfg(feature = "indicators")]
+    pub fn get_volatility_annualized(&self, symbol: String) -> Option<f64> {
+        self.asset(&symbol).annualized_volatility()
+    }
+
+    /// Returns the beta of the asset versus the `reference_symbol` one, of
+    /// the log returns over the overlapping period of their windows, e.g.
+    /// `1.5` for an asset moving half as much again as the reference, or
+    /// `None` without at least two moments in common or if the reference
+    /// hasn't changed.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when either asset is not registered.
+    #[cfg(feature = "indicators")]
+    pub fn get_beta(&self, symbol: String, reference_symbol: String) -> Option<f64> {
+        self.asset(&symbol).beta(&self.asset(&reference_symbol))
+    }
+
+    /// Returns the log returns between the records of the window, from the
+    /// oldest to the newest, if none of the prices is zero.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    #[cfg(feature = "indicators")]
+    pub fn get_log_returns(&self, symbol: String) -> Option<Vec<LogReturn>> {
+        self.asset(&symbol).window_log_returns()
+    }
+
+    /// Returns the records the average of the asset is calculated of, from
+    /// the oldest to the newest, or in the other `order`.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn get_history(&self, symbol: String, order: Option<Order>) -> Vec<HistoryEntry> {
+        self.asset(&symbol).history(order.unwrap_or_default())
+    }
+
+    /// Returns up to `limit` (50 by default) oldest intervals longer than the
+    /// `expected_interval_ns` during which no price of the asset has been
+    /// recorded, so the uptime of the feed can be audited. Only the intervals
+    /// ending after the `from` and beginning before the `to` timestamps, if
+    /// any, are listed. The archive is scanned if it has any records, the
+    /// retained history otherwise, up to 4096 of them from the shard of the
+    /// `from` on, so a long range is audited as several shorter ones.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn get_gaps(
+        &self,
+        symbol: String,
+        expected_interval_ns: U64,
+        from: Option<U64>,
+        to: Option<U64>,
+        limit: Option<u64>,
+    ) -> Vec<Gap> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        self.asset(&symbol).gaps(
+            expected_interval_ns.0,
+            from.map_or(0, |from| from.0),
+            to.map_or(u64::MAX, |to| to.0),
+            usize::try_from(limit).unwrap_or(usize::MAX),
+        )
+    }
+
+    /// Returns every submission of the `round_id` of the asset, the retained
+    /// record of which has been aggregated from them, if it's still retained.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn get_submissions(&self, symbol: String, round_id: U64) -> Option<Vec<Submission>> {
+        self.asset(&symbol).round_submissions(round_id.0)
+    }
+
+    /// Returns the price of the asset as of the `timestamp_ns`, linearly
+    /// interpolated between the surrounding records, or that of the nearest
+    /// record, flagged as such, when the moment is outside of the recorded
+    /// ones.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered, or when no price has been
+    /// recorded.
+    pub fn get_price_at_time(&self, symbol: String, timestamp_ns: U64) -> PriceAtTime {
+        self.asset(&symbol)
+            .price_at(timestamp_ns.0)
+            .unwrap_or_else(|| ContractError::NotEnoughData.panic())
+    }
+
+    /// Returns the price of the asset estimated by the Kalman filter with the
+    /// configured noises, or `None` when the filter isn't configured or no
+    /// price has been recorded since.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn get_smoothed_price(&self, symbol: String) -> Option<SmoothedPrice> {
+        self.asset(&symbol).smoothed_price()
+    }
+
+    /// Returns the MACD of the retained records of the asset, of the
+    /// exponential moving averages over the `fast`, the `slow` and the
+    /// `signal` records, like 12, 26 and 9, for which the 34 records retained
+    /// by default suffice.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered, when the `fast` period
+    /// isn't shorter than the `slow` one or either of the periods is zero, or
+    /// when less than `slow + signal - 1` records are retained.
+    #[cfg(feature = "indicators")]
+    pub fn get_macd(&self, symbol: String, fast: u16, slow: u16, signal: u16) -> Macd {
+        ensure(
+            fast != 0 && fast < slow && signal != 0,
+            ContractError::OutOfBounds("The periods of the MACD"),
+        );
+        self.asset(&symbol)
+            .macd(fast, slow, signal)
+            .unwrap_or_else(|| ContractError::NotEnoughData.panic())
+    }
+
+    /// Returns the simple and the log returns of the asset over the
+    /// `window_ns` nanoseconds till now, of the prices as of the start and
+    /// the end of the period, found as by `get_price_at_time`.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered, when no price has been
+    /// recorded, or when the starting price is zero.
+    #[cfg(feature = "indicators")]
+    pub fn get_return(&self, symbol: String, window_ns: U64) -> PeriodReturn {
+        self.asset(&symbol)
+            .period_return(env::block_timestamp(), window_ns.0)
+            .unwrap_or_else(|| ContractError::NotEnoughData.panic())
+    }
+
+    /// Returns the average of the asset as of the start of the `epoch`, which
+    /// is persisted by the first record of every epoch, if enough historical
+    /// data has been collected by then.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    #[cfg(feature = "indicators")]
+    pub fn get_epoch_snapshot(&self, symbol: String, epoch: U64) -> Option<EpochSnapshot> {
+        self.asset(&symbol)
+            .epoch_snapshot(epoch.0)
+            .map(EpochSnapshot::from)
+    }
+
+    /// Returns the ever-growing cumulative price of the asset (the sum of the
+    /// prices multiplied by the nanoseconds they have lasted) as of now. The
+    /// time-weighted average price between two observations is the
+    /// difference of the cumulative prices divided by the elapsed time.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    #[cfg(feature = "indicators")]
+    pub fn get_price_cumulative(&self, symbol: String) -> CumulativePrice {
+        self.asset(&symbol)
+            .accumulator
+            .current(env::block_timestamp())
+    }
+
+    /// Returns the cumulative prices of the asset as of each of the
+    /// `seconds_ago` before now, see [`Self::get_price_cumulative`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered, or when any of the
+    /// moments predates the oldest kept observation.
+    #[cfg(feature = "indicators")]
+    pub fn observe(&self, symbol: String, seconds_ago: Vec<u64>) -> Vec<CumulativePrice> {
+        self.asset(&symbol)
+            .observe(env::block_timestamp(), &seconds_ago)
+            .unwrap_or_else(|| ContractError::OutOfBounds("The observation is too old").panic())
+    }
 
-        for price in [1., 2., 3., 4., 5.] {
-            counter.record_price(price);
+    /// Returns the maximum amount of observations kept for the asset.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    #[cfg(feature = "indicators")]
+    pub fn get_observation_cardinality(&self, symbol: String) -> u16 {
+        self.asset(&symbol).observations().cardinality()
+    }
+
+    /// Raises the maximum amount of observations kept for the asset, up to
+    /// 1024, so [`Self::observe`] can look further back. The storage of the
+    /// added observations is paid with the attached deposit, the excess is
+    /// refunded: the buffer grows with the records, which are charged to the
+    /// account that has paid for the asset, so the deposit is credited to its
+    /// storage balance.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered, when the `cardinality`
+    /// exceeds 1024, or when the deposit doesn't cover the storage.
+    #[cfg(feature = "indicators")]
+    #[payable]
+    pub fn grow_observation_cardinality(&mut self, symbol: String, cardinality: u16) -> u16 {
+        ensure(
+            cardinality <= MAX_CARDINALITY,
+            ContractError::OutOfBounds("The cardinality exceeds 1024"),
+        );
+        let mut asset = self.asset(&symbol);
+        let current = asset.observations().cardinality();
+        let added = cardinality.saturating_sub(current);
+        let initial_storage_usage = env::storage_usage();
+        let prepaid = env::storage_byte_cost()
+            .saturating_mul(Balance::from(u64::from(added) * OBSERVATION_BYTES));
+        if added > 0 {
+            asset.grow_observations(cardinality);
+            self.assets.insert(&symbol, &asset);
+            self.prepay_storage(&asset.storage_payer, prepaid.as_yoctonear());
+            if self.log_level.allows(LogLevel::Info) {
+                log!(
+                    "{} observation cardinality grown from {} to {}",
+                    symbol,
+                    current,
+                    cardinality
+                );
+            }
         }
+        let used = env::storage_usage().saturating_sub(initial_storage_usage);
+        let cost = env::storage_byte_cost()
+            .saturating_mul(Balance::from(used))
+            .saturating_add(prepaid);
+        let deposit = env::attached_deposit();
+        ensure(
+            deposit >= cost,
+            ContractError::InsufficientDeposit {
+                funds: "deposit",
+                required: cost.as_yoctonear(),
+                available: deposit.as_yoctonear(),
+            },
+        );
+        if deposit > cost {
+            Promise::new(env::predecessor_account_id())
+                .transfer(deposit.saturating_sub(cost))
+                .detach();
+        }
+        asset.observations().cardinality()
+    }
 
-        let expected = 3.;
-        assert!((counter.get_average() - expected).abs() < 1e-5);
+    /// Returns the average price as a fixed-point number with the amount of
+    /// `decimals` configured for the asset, unless others are requested,
+    /// rounded to the nearest one unless the `rounding` is requested, e.g.
+    /// `Down` for a conservative valuation of a collateral.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered, when the `decimals`
+    /// exceed 24, when not enough historical data has been collected, or when
+    /// the price is stale.
+    pub fn get_average_fixed(
+        &self,
+        symbol: String,
+        decimals: Option<u8>,
+        rounding: Option<Rounding>,
+    ) -> U128 {
+        let asset = self.asset(&symbol);
+        let decimals = decimals.unwrap_or(asset.decimals);
+        ensure(
+            decimals <= MAX_DECIMALS,
+            ContractError::OutOfBounds("Too many decimals"),
+        );
+        let average = Self::require_average(&asset);
+        average
+            .rescale(decimals, rounding.unwrap_or(Rounding::Nearest))
+            .unwrap_or_else(|| {
+                ContractError::OutOfBounds("The average can't be represented as a fixed-point")
+                    .panic()
+            })
+            .into()
     }
 
-    #[test]
-    #[should_panic]
-    fn empty() {
-        let context = get_context(vec![], false);
-        testing_env!(context);
-        let counter = PriceHistory::default();
-        counter.get_average();
+    /// Converts the `near_amount` of yoctoNEAR into the amount of the asset,
+    /// in its units with the configured decimals, at the average prices of the
+    /// NEAR and of the asset. The amount is rounded down.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset or the NEAR is not registered, when they are
+    /// quoted in different currencies, when not enough historical data has
+    /// been collected for either of them, when either of the prices is stale
+    /// or zero, or when the amount overflows.
+    pub fn price_in_yocto(&self, symbol: String, near_amount: U128) -> U128 {
+        let (near, asset) = self.near_pair(&symbol);
+        Fixed::from_scaled(near_amount.0, NEAR_DECIMALS)
+            .and_then(|amount| amount.checked_mul(near, Rounding::Down))
+            .and_then(|value| value.checked_div(asset.1, Rounding::Down))
+            .and_then(|amount| amount.rescale(asset.0, Rounding::Down))
+            .unwrap_or_else(|| ContractError::OutOfBounds("The amount overflows").panic())
+            .into()
+    }
+
+    /// Converts the `amount` of the asset, in its units with the configured
+    /// decimals, into yoctoNEAR, the inverse of the [`Self::price_in_yocto`].
+    /// The amount is rounded down.
+    ///
+    /// # Panics
+    ///
+    /// Will panic under the same conditions as the [`Self::price_in_yocto`].
+    pub fn yocto_in_price(&self, symbol: String, amount: U128) -> U128 {
+        let (near, asset) = self.near_pair(&symbol);
+        Fixed::from_scaled(amount.0, asset.0)
+            .and_then(|amount| amount.checked_mul(asset.1, Rounding::Down))
+            .and_then(|value| value.checked_div(near, Rounding::Down))
+            .and_then(|amount| amount.rescale(NEAR_DECIMALS, Rounding::Down))
+            .unwrap_or_else(|| ContractError::OutOfBounds("The amount overflows").panic())
+            .into()
+    }
+
+    /// Checks whether no price of the asset has been recorded within the
+    /// configured heartbeat.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn is_stale(&self, symbol: String) -> bool {
+        self.asset(&symbol).is_stale(env::block_timestamp())
+    }
+
+    /// Adds the provided `price` of the asset to the storage. Prices which
+    /// are not finite numbers within `0..=1e14`, or are subnormal, are
+    /// rejected with the input, and zero is rejected unless the asset allows
+    /// it.
+    /// A price which repeats the latest one within the configured tolerance
+    /// only refreshes the timestamp of the latest record, without an event.
+    /// The optional `timestamp` is when the price has been observed off-chain,
+    /// the block timestamp by default, and the optional `confidence` is its
+    /// uncertainty, combined into that of the record. With a quorum set, see
+    /// [`Self::set_quorum`], the price is submitted to the pending round
+    /// instead, which is recorded as its median once complete. Every record
+    /// with an average is pushed to the subscribers, see [`Self::subscribe`],
+    /// as long as the gas lasts, see [`Self::continue_notifications`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called neither from the account which was used to
+    /// deployed the contract nor by a reporter allowed to record the asset
+    /// and on duty in the epoch, when the asset is not registered or frozen,
+    /// when the price is zero while the asset doesn't allow it, when the
+    /// price deviates from the average more than configured, when
+    /// the block already has a record while only one is allowed, when the
+    /// `timestamp` lags behind the block more than configured or predates the
+    /// latest record, when the reporter has recorded a price within the
+    /// minimum interval or has already submitted one to the pending round, or
+    /// when the storage balance of the account which has paid for the asset
+    /// doesn't cover the growth of the history.
+    pub fn record_price(
+        &mut self,
+        symbol: String,
+        price: Price,
+        timestamp: Option<U64>,
+        confidence: Option<Price>,
+    ) {
+        self.submit_price(
+            env::signer_account_id(),
+            symbol,
+            price,
+            timestamp,
+            confidence,
+            None,
+        );
+    }
+
+    /// Records the price of the asset as the [`Self::record_price`] does,
+    /// also keeping it as the latest price of the upstream source, like
+    /// `"coingecko"`, for the per-source views.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the `source_id` is empty or longer than 32 bytes,
+    /// when the source is new while the asset has 16 of them already, or
+    /// under the same conditions as the [`Self::record_price`].
+    pub fn record_source_price(
+        &mut self,
+        symbol: String,
+        source_id: String,
+        price: Price,
+        timestamp: Option<U64>,
+        confidence: Option<Price>,
+    ) {
+        self.submit_price(
+            env::signer_account_id(),
+            symbol,
+            price,
+            timestamp,
+            confidence,
+            Some(source_id),
+        );
+    }
+
+    /// Returns the latest price of every upstream source of the asset, see
+    /// the [`Self::record_source_price`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn get_source_prices(&self, symbol: String) -> Vec<SourcePrice> {
+        self.asset(&symbol).source_prices()
+    }
+
+    /// Returns the latest price of every upstream source of the asset with
+    /// its age and deviation from the weighted median of all the sources, so
+    /// a failing upstream API stands out.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn get_source_divergence(&self, symbol: String) -> Vec<SourceDivergence> {
+        self.asset(&symbol)
+            .source_divergence(env::block_timestamp(), |source_id| {
+                self.get_source_weight(source_id.clone())
+            })
+    }
+
+    /// Returns the weighted median of the latest prices of the upstream
+    /// sources of the asset, ignoring those older than the `max_age`
+    /// nanoseconds, if any, or `None` when there are no such prices.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn get_cross_source_median(&self, symbol: String, max_age: Option<U64>) -> Option<Price> {
+        self.asset(&symbol)
+            .cross_source_median(Self::source_cutoff(max_age), |source_id| {
+                self.get_source_weight(source_id.clone())
+            })
+    }
+
+    /// Returns the weighted mean of the latest prices of the upstream
+    /// sources of the asset, ignoring those older than the `max_age`
+    /// nanoseconds, if any, or `None` when there are no such prices.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn get_cross_source_mean(&self, symbol: String, max_age: Option<U64>) -> Option<Price> {
+        self.asset(&symbol)
+            .cross_source_mean(Self::source_cutoff(max_age), |source_id| {
+                self.get_source_weight(source_id.clone())
+            })
+    }
+
+    /// Sets the weight of the upstream source in the combined answers, so a
+    /// more trusted source dominates them. Every source weighs 1 by default,
+    /// and a source weighing 0 is left out.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract.
+    pub fn set_source_weight(&mut self, source_id: String, weight: u32) {
+        self.assert_owner("weigh the sources");
+        let previous = if weight == DEFAULT_SOURCE_WEIGHT {
+            self.source_weights.remove(&source_id)
+        } else {
+            self.source_weights.insert(&source_id, &weight)
+        };
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Source {} weighs {}", source_id, weight);
+        }
+        events::emit_config_changes(
+            None,
+            &json!({ "source_weights": { source_id.as_str(): previous.unwrap_or(DEFAULT_SOURCE_WEIGHT) } }),
+            &json!({ "source_weights": { source_id.as_str(): weight } }),
+        );
+    }
+
+    /// Lets the quarantined upstream source of the asset back into the
+    /// cross-source answers, see the `quarantine_rounds` of the
+    /// [`AssetConfig`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, when the asset is not registered, or when the source
+    /// isn't quarantined.
+    pub fn reinstate_source(&mut self, symbol: String, source_id: String) {
+        self.assert_owner("reinstate a source");
+        let mut asset = self.asset(&symbol);
+        ensure(
+            asset.reinstate_source(&source_id),
+            ContractError::OutOfBounds("The source isn't quarantined"),
+        );
+        self.assets.insert(&symbol, &asset);
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Source {} of {} reinstated", source_id, symbol);
+        }
+    }
+
+    /// Returns the quarantined upstream sources of the asset.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn get_quarantined_sources(&self, symbol: String) -> Vec<String> {
+        self.asset(&symbol).quarantined_sources()
+    }
+
+    /// Returns the weight of the upstream source in the combined answers.
+    pub fn get_source_weight(&self, source_id: String) -> u32 {
+        self.source_weights
+            .get(&source_id)
+            .unwrap_or(DEFAULT_SOURCE_WEIGHT)
+    }
+
+    /// Allows the account to record prices of the assets within the `scope`,
+    /// replacing its previous scope, if any.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, or when the scope lists an unregistered asset.
+    pub fn add_reporter(&mut self, account_id: AccountId, scope: ReporterScope) {
+        self.assert_owner("manage reporters");
+        if let ReporterScope::Assets(symbols) = &scope {
+            for symbol in symbols {
+                self.asset(symbol);
+            }
+        }
+        let previous = self.reporters.insert(&account_id, &scope);
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Reporter {} allowed for {:?}", account_id, scope);
+        }
+        events::emit_config_changes(
+            None,
+            &json!({ "reporters": { account_id.as_str(): previous } }),
+            &json!({ "reporters": { account_id.as_str(): scope } }),
+        );
+    }
+
+    /// Revokes the account's permission to record prices.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, or when the remaining reporters wouldn't make the quorum.
+    pub fn remove_reporter(&mut self, account_id: AccountId) {
+        self.assert_owner("manage reporters");
+        if let Some(previous) = self.reporters.remove(&account_id) {
+            self.validate_quorum(&self.quorum);
+            if self.log_level.allows(LogLevel::Info) {
+                log!("Reporter {} removed", account_id);
+            }
+            events::emit_config_changes(
+                None,
+                &json!({ "reporters": { account_id.as_str(): previous } }),
+                &json!({ "reporters": { account_id.as_str(): null } }),
+            );
+        }
+    }
+
+    /// Returns the assets the account is allowed to record prices of, if it
+    /// is a reporter.
+    pub fn get_reporter_scope(&self, account_id: AccountId) -> Option<ReporterScope> {
+        self.reporters.get(&account_id)
+    }
+
+    /// Checks whether the account is allowed to record prices of any asset,
+    /// the contract's account included, regardless of the rotation.
+    pub fn is_reporter(&self, account_id: AccountId) -> bool {
+        account_id == env::current_account_id() || self.reporters.get(&account_id).is_some()
+    }
+
+    /// Returns up to `limit` (50 by default) reporters, apart from the
+    /// contract's account, starting from the `from_index`.
+    pub fn get_reporters(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<ReporterInfo> {
+        let accounts = self.reporters.keys_as_vector();
+        let scopes = self.reporters.values_as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let to_index = from_index
+            .saturating_add(limit.unwrap_or(DEFAULT_PAGE_LIMIT))
+            .min(accounts.len());
+        (from_index..to_index)
+            .filter_map(|index| {
+                Some(ReporterInfo {
+                    account_id: accounts.get(index)?,
+                    scope: scopes.get(index)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the amount of the reporters, apart from the contract's account.
+    pub fn get_reporter_count(&self) -> u64 {
+        self.reporters.len()
+    }
+
+    /// Returns the submissions aggregated into every record.
+    pub fn get_quorum(&self) -> Quorum {
+        self.quorum
+    }
+
+    /// Makes every record the median of at least `min_submissions` prices of
+    /// distinct reporters, collected for up to the `submission_window`
+    /// nanoseconds (zero by default, i.e. until the minimum is reached) or
+    /// until `max_submissions` are collected. The pending rounds are kept.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, when `min_submissions` is zero or exceeds
+    /// `max_submissions`, or when it exceeds the amount of the accounts
+    /// allowed to record prices, the contract's one included.
+    #[cfg(feature = "rounds")]
+    pub fn set_quorum(
+        &mut self,
+        min_submissions: u16,
+        max_submissions: u16,
+        submission_window: Option<U64>,
+    ) {
+        self.assert_owner("set the quorum");
+        let quorum = Quorum {
+            min_submissions,
+            max_submissions,
+            submission_window: submission_window.unwrap_or(U64(0)),
+        };
+        self.validate_quorum(&quorum);
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Quorum changed to {:?}", quorum);
+        }
+        events::emit_config_changes(None, &self.quorum, &quorum);
+        self.quorum = quorum;
+    }
+
+    /// Returns the amount of the reporters on duty per epoch, if they take
+    /// turns.
+    pub fn get_rotation(&self) -> Option<u16> {
+        self.rotation
+    }
+
+    /// Makes only `size` of the reporters, chosen by the epoch height, allowed
+    /// to record prices within an epoch, so that all of them take turns; none
+    /// lets every reporter record prices at any time. The contract's account
+    /// is always allowed to.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, when the `size` is zero, or when the reporters on duty
+    /// wouldn't make the quorum.
+    pub fn set_rotation(&mut self, size: Option<u16>) {
+        self.assert_owner("set the rotation");
+        ensure(
+            size != Some(0),
+            ContractError::OutOfBounds("The rotation must have at least one reporter"),
+        );
+        let previous = std::mem::replace(&mut self.rotation, size);
+        self.validate_quorum(&self.quorum);
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Rotation changed to {:?}", size);
+        }
+        events::emit_config_changes(
+            None,
+            &json!({ "rotation": previous }),
+            &json!({ "rotation": size }),
+        );
+    }
+
+    /// Returns the reporters allowed to record prices in the current epoch,
+    /// apart from the contract's account.
+    pub fn get_reporters_on_duty(&self) -> Vec<AccountId> {
+        let reporters = self.reporters.keys_as_vector();
+        match self.rotation {
+            Some(size) => reporter::on_duty(reporters.len(), size, env::epoch_height())
+                .filter_map(|index| reporters.get(index))
+                .collect(),
+            None => reporters.to_vec(),
+        }
+    }
+
+    /// Returns the depth of the recorded history.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn depth_so_far(&self, symbol: String) -> usize {
+        self.asset(&symbol).depth_so_far()
+    }
+
+    /// Returns the amount of the prices submitted to the pending round.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn pending_submissions(&self, symbol: String) -> usize {
+        self.asset(&symbol).pending.submitted()
+    }
+
+    /// Returns the amount of required historical data to calculate the average.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn required_depth(&self, symbol: String) -> usize {
+        usize::from(self.asset(&symbol).config().depth)
+    }
+
+    /// Forgets the history of the asset.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, when the contract has been decommissioned, or when the
+    /// asset is not registered.
+    pub fn reset(&mut self, symbol: String) {
+        self.assert_owner("reset an asset");
+        self.assert_active();
+        let mut asset = self.asset(&symbol);
+        let average = asset.average().map(Fixed::to_f64);
+        let discarded = asset.reset();
+        self.assets.insert(&symbol, &asset);
+        self.counters.resets += 1;
+        self.counters.last_reset = Some(env::block_timestamp());
+        events::Event::Reset(events::Reset {
+            symbol: &symbol,
+            caller: &env::predecessor_account_id(),
+            discarded,
+            average,
+            timestamp: env::block_timestamp().into(),
+        })
+        .emit();
+    }
+
+    /// Drops the `count` newest records of the asset, restoring the window
+    /// from the older retained ones, e.g. after a batch of bad data. Up to
+    /// the larger of the depth and 34 records are retained. The archive and
+    /// the cumulative price keep the dropped records.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, when the asset is not registered, or when fewer records
+    /// are retained.
+    pub fn rewind(&mut self, symbol: String, count: u16) {
+        self.assert_owner("rewind an asset");
+        let mut asset = self.asset(&symbol);
+        ensure(
+            asset.rewind(count, env::block_timestamp()),
+            ContractError::OutOfBounds("Can't rewind more records than retained"),
+        );
+        self.assets.insert(&symbol, &asset);
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Rewound {} newest {} records", count, symbol);
+        }
+    }
+
+    /// Inserts a record of the asset observed at the `timestamp` into the
+    /// archive, in the chronological order, e.g. to backfill the history
+    /// from before the asset has been registered. Neither the window nor the
+    /// averages and the cumulative price are affected.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, when the asset is not registered or doesn't keep the
+    /// archive, when the price is zero while the asset doesn't allow it, when
+    /// the `timestamp` postdates the block, or when the storage balance of
+    /// the account which has paid for the asset doesn't cover the record.
+    #[cfg(feature = "archive")]
+    pub fn record_historical(&mut self, symbol: String, price: Price, timestamp: U64) {
+        self.assert_owner("backfill the archive");
+        let mut asset = self.asset(&symbol);
+        ensure(
+            asset.accepts_price(price),
+            ContractError::OutOfBounds("The price must not be zero"),
+        );
+        ensure(
+            timestamp.0 <= env::block_timestamp(),
+            ContractError::OutOfBounds("The timestamp must not postdate the block"),
+        );
+        let initial_storage_usage = env::storage_usage();
+        ensure(
+            asset.backfill(PriceRecord {
+                price,
+                timestamp: timestamp.0,
+            }),
+            ContractError::OutOfBounds("The asset doesn't keep the archive"),
+        );
+        self.assets.insert(&symbol, &asset);
+        self.settle_storage(&asset.storage_payer, initial_storage_usage);
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Archived {} of {} at {}", price, symbol, timestamp.0);
+        }
+    }
+
+    /// Returns the official answer of the asset: with `finality_blocks`
+    /// configured, the latest average which has survived that many blocks
+    /// without a dispute, otherwise the average. `None` until there is one.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn get_official_answer(&self, symbol: String) -> Option<OfficialAnswer> {
+        self.asset(&symbol)
+            .official_answer(env::block_height())
+            .map(OfficialAnswer::from)
+    }
+
+    /// Returns the average of the asset still to survive the
+    /// `finality_blocks` before it becomes the official answer, if any.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    pub fn get_proposed_answer(&self, symbol: String) -> Option<OfficialAnswer> {
+        self.asset(&symbol)
+            .proposed_answer(env::block_height())
+            .map(OfficialAnswer::from)
+    }
+
+    /// Drops the average of the asset still to survive the
+    /// `finality_blocks`, so it never becomes the official answer; the next
+    /// record proposes a new one.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called neither from the account which was used to
+    /// deployed the contract nor by a reporter allowed to record the asset,
+    /// when the asset is not registered, or when no answer is proposed.
+    pub fn dispute_answer(&mut self, symbol: String) {
+        ensure(
+            self.may_report(&env::signer_account_id(), &symbol),
+            ContractError::Unauthorized("dispute an answer"),
+        );
+        let mut asset = self.asset(&symbol);
+        let disputed = asset
+            .dispute_answer(env::block_height())
+            .unwrap_or_else(|| ContractError::OutOfBounds("No answer is proposed").panic());
+        self.assets.insert(&symbol, &asset);
+        if self.log_level.allows(LogLevel::Info) {
+            log!(
+                "Answer {} of {} proposed at {} disputed by {}",
+                disputed.average.to_f64(),
+                symbol,
+                disputed.height,
+                env::signer_account_id()
+            );
+        }
+    }
+
+    /// Returns the amount of the archived records of the asset.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    #[cfg(feature = "archive")]
+    pub fn get_archive_len(&self, symbol: String) -> u64 {
+        self.asset(&symbol).archive.len()
+    }
+
+    /// Drops the archived records of the asset which predate the `before`
+    /// timestamp, by whole shards of 1024 records, and returns the amount of
+    /// the dropped records. The storage is refunded to the account which has
+    /// paid for the asset.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, or when the asset is not registered.
+    #[cfg(feature = "archive")]
+    pub fn prune_archive(&mut self, symbol: String, before: U64) -> u64 {
+        self.assert_owner("prune the archive");
+        let mut asset = self.asset(&symbol);
+        let initial_storage_usage = env::storage_usage();
+        let dropped = asset.archive.prune_before(before.0);
+        if dropped > 0 {
+            self.assets.insert(&symbol, &asset);
+            self.settle_storage(&asset.storage_payer, initial_storage_usage);
+            if self.log_level.allows(LogLevel::Info) {
+                log!("{} archived records of {} pruned", dropped, symbol);
+            }
+        }
+        dropped
+    }
+
+    /// Returns the version and build of the contract.
+    pub fn get_version(&self) -> VersionInfo {
+        VersionInfo {
+            version: VERSION.to_string(),
+            build_id: BUILD_ID.to_string(),
+            deprecated: self.decommission.is_some(),
+            successor: self
+                .decommission
+                .as_ref()
+                .and_then(|decommission| decommission.successor.clone()),
+        }
+    }
+
+    /// Returns the Borsh serialization of the state of the contract, so it
+    /// can be snapshot and diffed off-chain. The items of the collections are
+    /// stored separately, under their prefixes.
+    #[cfg(feature = "debug")]
+    pub fn debug_state(&self) -> near_sdk::json_types::Base64VecU8 {
+        // Hint: the serialization into a vector never fails.
+        near_sdk::borsh::to_vec(self)
+            .unwrap_or_else(|_| unreachable!())
+            .into()
+    }
+
+    /// Returns the current logging verbosity.
+    pub fn get_log_level(&self) -> LogLevel {
+        self.log_level
+    }
+
+    /// Permanently locks every admin function, proving the configuration and
+    /// the reporters can never change again. The `confirmation` must be the
+    /// account of the contract. The prices are still recorded.
+    ///
+    /// Note the code can still be replaced with a full access key of the
+    /// account, so those are to be removed as well.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, or when the `confirmation` doesn't match.
+    pub fn renounce_ownership(&mut self, confirmation: AccountId) {
+        self.assert_owner("renounce the ownership");
+        ensure(
+            confirmation == env::current_account_id(),
+            ContractError::OutOfBounds("The confirmation must be the account of the contract"),
+        );
+        self.renounced = true;
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Ownership renounced");
+        }
+    }
+
+    /// Ends the life of the contract: no more assets or prices are accepted,
+    /// and the views are marked as deprecated in favor of the `successor`,
+    /// if any, see [`Self::get_version`]. The fee revenue in NEAR is
+    /// transferred to the `beneficiary`, if any; the storage balances, the allowances and
+    /// the token credits stay withdrawable, see [`Self::storage_withdraw`],
+    /// [`Self::withdraw_allowance`] and [`Self::withdraw_token_credit`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, or when the contract has already been decommissioned.
+    pub fn decommission(&mut self, successor: Option<AccountId>, beneficiary: Option<AccountId>) {
+        self.assert_owner("decommission the contract");
+        self.assert_active();
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Decommissioned in favor of {:?}", successor);
+        }
+        self.decommission = Some(Decommission {
+            successor,
+            timestamp: env::block_timestamp(),
+        });
+        if let Some(beneficiary) = beneficiary {
+            let fee_revenue = std::mem::take(&mut self.fee_revenue);
+            if fee_revenue > 0 {
+                Promise::new(beneficiary)
+                    .transfer(NearToken::from_yoctonear(fee_revenue))
+                    .detach();
+            }
+        }
+    }
+
+    /// Checks whether the admin functions are locked forever.
+    pub fn is_ownership_renounced(&self) -> bool {
+        self.renounced
+    }
+
+    /// Sets the logging verbosity.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract.
+    pub fn set_log_level(&mut self, log_level: LogLevel) {
+        self.assert_owner("change the log level");
+        if self.log_level.allows(LogLevel::Info) || log_level.allows(LogLevel::Info) {
+            log!(
+                "Log level changed from {:?} to {:?}",
+                self.log_level,
+                log_level
+            );
+        }
+        events::emit_config_changes(
+            None,
+            &json!({ "log_level": self.log_level }),
+            &json!({ "log_level": log_level }),
+        );
+        self.log_level = log_level;
+    }
+}
+
+#[cfg(feature = "contract")]
+impl PriceHistory {
+    /// Panics unless called from the account which was used to deploy the
+    /// contract, telling the caller it's not allowed to perform the `action`.
+    /// Nobody is allowed to once the ownership is renounced.
+    fn assert_owner(&self, action: &'static str) {
+        ensure(
+            !self.renounced && env::signer_account_id() == env::current_account_id(),
+            ContractError::Unauthorized(action),
+        )
+    }
+
+    /// Panics once the contract has been decommissioned.
+    fn assert_active(&self) {
+        ensure(self.decommission.is_none(), ContractError::Decommissioned)
+    }
+
+    /// Freezes or unfreezes the asset.
+    fn set_frozen(&mut self, symbol: String, frozen: bool) {
+        self.assert_owner("freeze an asset");
+        let mut asset = self.asset(&symbol);
+        asset.frozen = frozen;
+        self.assets.insert(&symbol, &asset);
+        if self.log_level.allows(LogLevel::Info) {
+            let action = if frozen { "frozen" } else { "unfrozen" };
+            log!("{} has been {}", symbol, action);
+        }
+    }
+
+    /// Checks whether the account is allowed to record prices of the asset.
+    fn may_report(&self, account_id: &AccountId, symbol: &str) -> bool {
+        *account_id == env::current_account_id()
+            || (self
+                .reporters
+                .get(account_id)
+                .is_some_and(|scope| scope.covers(symbol))
+                && self.is_on_duty(account_id))
+    }
+
+    /// Checks whether the reporter is on duty in the current epoch.
+    fn is_on_duty(&self, account_id: &AccountId) -> bool {
+        let Some(size) = self.rotation else {
+            return true;
+        };
+        let reporters = self.reporters.keys_as_vector();
+        reporter::on_duty(reporters.len(), size, env::epoch_height())
+            .any(|index| reporters.get(index).as_ref() == Some(account_id))
+    }
+
+    /// Returns the amount of the accounts allowed to record prices at once,
+    /// the contract's one included.
+    fn active_reporters(&self) -> u64 {
+        let reporters = self.reporters.len();
+        let on_duty = self
+            .rotation
+            .map_or(reporters, |size| reporters.min(u64::from(size)));
+        on_duty + 1
+    }
+
+    /// Maps up to `limit` (50 by default) registered assets, starting from
+    /// the `from_index`.
+    fn map_assets<T>(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+        f: impl Fn(String, Asset) -> T,
+    ) -> Vec<T> {
+        let symbols = self.assets.keys_as_vector();
+        let assets = self.assets.values_as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let to_index = from_index
+            .saturating_add(limit.unwrap_or(DEFAULT_PAGE_LIMIT))
+            .min(symbols.len());
+        (from_index..to_index)
+            .filter_map(|index| Some(f(symbols.get(index)?, assets.get(index)?)))
+            .collect()
+    }
+
+    /// Submits the `price` of the asset by the `reporter`, from the upstream
+    /// source, if any, see the [`Self::record_source_price`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic under the same conditions as the [`Self::record_price`].
+    fn submit_price(
+        &mut self,
+        reporter: AccountId,
+        symbol: String,
+        price: Price,
+        timestamp: Option<U64>,
+        confidence: Option<Price>,
+        source_id: Option<String>,
+    ) {
+        let mut asset = self.asset(&symbol);
+        // Prevent others from adding possibly malicious records.
+        ensure(
+            self.may_report(&reporter, &symbol),
+            ContractError::Unauthorized("record a price"),
+        );
+        ensure(!asset.frozen, ContractError::Frozen);
+        self.assert_active();
+        ensure(
+            asset.accepts_price(price),
+            ContractError::OutOfBounds("The price must not be zero"),
+        );
+        ensure(asset.within_deviation(price), ContractError::Deviation);
+        ensure(
+            asset.accepts_block(env::block_height()),
+            ContractError::SameBlock,
+        );
+        let timestamp = match timestamp {
+            Some(U64(timestamp)) => {
+                ensure(
+                    asset.accepts_timestamp(timestamp, env::block_timestamp()),
+                    ContractError::OutOfBounds(
+                        "The timestamp must be within the allowed skew and not predate the latest record",
+                    ),
+                );
+                timestamp
+            }
+            None => env::block_timestamp(),
+        };
+        ensure(
+            !asset.reported_recently(&reporter, timestamp),
+            ContractError::TooFrequent,
+        );
+        let initial_storage_usage = env::storage_usage();
+        asset.set_reported(&reporter, timestamp);
+        if let Some(source_id) = &source_id {
+            let quarantined = asset
+                .record_source(source_id, PriceRecord { price, timestamp }, |source_id| {
+                    self.get_source_weight(source_id.clone())
+                })
+                .unwrap_or_else(|error| error.panic());
+            if let Some(deviation_bps) = quarantined {
+                events::Event::SourceQuarantined(events::SourceQuarantined {
+                    symbol: &symbol,
+                    source_id,
+                    deviation_bps,
+                    rounds: asset.config().quarantine_rounds,
+                    timestamp: timestamp.into(),
+                })
+                .emit();
+            }
+        }
+        // Hint: the submission closes an expired round first.
+        let expired = asset
+            .pending
+            .is_expired(&self.quorum, env::block_timestamp())
+            .then(|| asset.pending.submitted());
+        let submission = Submission {
+            reporter,
+            price,
+            timestamp: timestamp.into(),
+            confidence,
+            provenance: Provenance::Reporter,
+        };
+        let Some(submissions) = asset
+            .pending
+            .submit(&self.quorum, submission, env::block_timestamp())
+            .unwrap_or_else(|error| error.panic())
+        else {
+            self.counters.submissions_rejected += expired.unwrap_or(0) as u64;
+            self.assets.insert(&symbol, &asset);
+            self.settle_storage(&asset.storage_payer, initial_storage_usage);
+            if self.log_level.allows(LogLevel::Debug) {
+                log!("Submitted {} price {} to the pending round", symbol, price);
+            }
+            return;
+        };
+        self.record_round(&symbol, asset, submissions, initial_storage_usage);
+    }
+
+    /// Records the median of the `submissions` of a round of the asset, and
+    /// settles the storage used since the `initial_storage_usage`.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the storage balance of the account which has paid for
+    /// the asset is not enough.
+    fn record_round(
+        &mut self,
+        symbol: &String,
+        mut asset: Asset,
+        submissions: Vec<Submission>,
+        initial_storage_usage: StorageUsage,
+    ) {
+        // Hint: a round is never recorded without submissions.
+        let median = round::median(&submissions).unwrap_or_else(|| unreachable!());
+        let (price, U64(timestamp)) = (median.price, median.timestamp);
+        asset.snapshot_epoch(env::epoch_height(), timestamp);
+        asset.record_block(env::block_height());
+        let previous_average = asset.average();
+        let (round, crossover) = if asset.is_repeated(price) {
+            asset.refresh(timestamp);
+            (None, None)
+        } else {
+            let round = asset.push(
+                PriceRecord { price, timestamp },
+                &median.reporter,
+                submissions.clone(),
+            );
+            asset.propose_answer(env::block_height());
+            (Some(round), asset.crossover())
+        };
+        // Hint: the gas of the call so far, which has loaded the asset and
+        // computed the record.
+        asset.record_gas.record(env::used_gas().as_gas());
+        self.assets.insert(symbol, &asset);
+        if round.is_some() {
+            self.counters.records_accepted += 1;
+        }
+        // Hint: the history grows until it's full, at the cost of the
+        // account which has paid for the asset.
+        self.settle_storage(&asset.storage_payer, initial_storage_usage);
+        // Hint: the storage of the rewards is on the contract, just like that
+        // of the reporters.
+        self.reward_round(&submissions, &median);
+        let Some(round) = round else {
+            if self.log_level.allows(LogLevel::Debug) {
+                log!("Refreshed {} price {}", symbol, price);
+            }
+            return;
+        };
+
+        if self.log_level.allows(LogLevel::Debug) {
+            log!("Recorded {} price {} as round {}", symbol, price, round);
+        }
+        // Hint: the events are emitted whatever the log level, for the indexers.
+        events::Event::PriceUpdate(events::PriceUpdate {
+            symbol,
+            price,
+            reporter: &median.reporter,
+            round,
+            timestamp: timestamp.into(),
+            average: asset.average().map(Fixed::to_f64),
+        })
+        .emit();
+        if let (Some(previous), Some(average)) = (previous_average, asset.average()) {
+            if let Some(deviation_bps) = asset.alert_deviation(previous, average) {
+                events::Event::PriceDeviation(events::PriceDeviation {
+                    symbol,
+                    previous: previous.to_f64(),
+                    average: average.to_f64(),
+                    deviation_bps,
+                    round,
+                    timestamp: timestamp.into(),
+                })
+                .emit();
+            }
+        }
+        if let (Some(kind), Some(short_average), Some(average)) =
+            (crossover, asset.short_average(), asset.average())
+        {
+            events::Event::MovingAverageCross(events::MovingAverageCross {
+                symbol,
+                kind,
+                short_average: short_average.to_f64(),
+                average: average.to_f64(),
+                round,
+                timestamp: timestamp.into(),
+            })
+            .emit();
+        }
+        #[cfg(feature = "subscriptions")]
+        if let Some(average) = asset.average() {
+            self.notify_subscribers(symbol, average, timestamp);
+        }
+    }
+
+    /// Loads a registered asset.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered.
+    fn asset(&self, symbol: &String) -> Asset {
+        self.assets
+            .get(symbol)
+            .unwrap_or_else(|| ContractError::NotRegistered.panic())
+    }
+
+    /// Returns the average price of the NEAR, and the decimals and the
+    /// average price of the asset quoted in the same currency.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset or the NEAR is not registered, when they are
+    /// quoted in different currencies, when not enough historical data has
+    /// been collected for either of them, or when either of the prices is
+    /// stale.
+    fn near_pair(&self, symbol: &String) -> (Fixed, (u8, Fixed)) {
+        let near = self.asset(&NEAR_SYMBOL.to_string());
+        let asset = self.asset(symbol);
+        ensure(
+            near.metadata().quote == asset.metadata().quote,
+            ContractError::OutOfBounds("The asset must be quoted in the currency of the NEAR"),
+        );
+        (
+            Self::require_average(&near),
+            (asset.decimals, Self::require_average(&asset)),
+        )
+    }
+
+    /// Calculates the average price of the asset.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when not enough historical data has been collected, or
+    /// when the price is stale.
+    fn require_average(asset: &Asset) -> Fixed {
+        let average = asset
+            .average()
+            .unwrap_or_else(|| ContractError::NotEnoughData.panic());
+        ensure(
+            !asset.is_stale(env::block_timestamp()),
+            ContractError::Stale,
+        );
+        average
+    }
+
+    /// Returns the timestamp the prices of the upstream sources mustn't
+    /// predate to be no older than the `max_age` nanoseconds, if any.
+    fn source_cutoff(max_age: Option<U64>) -> u64 {
+        max_age.map_or(0, |U64(max_age)| {
+            env::block_timestamp().saturating_sub(max_age)
+        })
+    }
+
+    /// Panics when the `quorum` is invalid with the current reporters.
+    fn validate_quorum(&self, quorum: &Quorum) {
+        if let Err(message) = quorum.validate(self.active_reporters()) {
+            ContractError::OutOfBounds(message).panic()
+        }
+    }
+
+    /// Panics when the asset configuration is invalid.
+    fn validate_config(config: &AssetConfig) {
+        if let Err(message) = config.validate() {
+            ContractError::OutOfBounds(message).panic()
+        }
+    }
+}
+
+#[cfg(all(test, feature = "contract"))]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    #[cfg(feature = "subscriptions")]
+    use near_sdk::Gas;
+    use near_sdk::{testing_env, PromiseError, PromiseOrValue, VMContext};
+
+    fn get_context(is_view: bool) -> VMContext {
+        VMContextBuilder::new()
+            .current_account_id(account("alice.testnet"))
+            .signer_account_id(account("alice.testnet"))
+            .predecessor_account_id(account("jane.testnet"))
+            .storage_usage(0)
+            .is_view(is_view)
+            .build()
+    }
+
+    /// Switches to a new call, keeping the state.
+    fn switch_context(update: impl FnOnce(&mut VMContext)) {
+        let mut context = get_context(false);
+        context.storage_usage = env::storage_usage();
+        update(&mut context);
+        testing_env!(context);
+    }
+
+    /// Switches to a call signed by the given account, keeping the state.
+    fn sign_as(signer: &str) {
+        switch_context(|context| context.signer_account_id = account(signer));
+    }
+
+    fn account(account_id: &str) -> AccountId {
+        account_id.parse().unwrap()
+    }
+
+    /// Deposits 1 NEAR to cover the storage of the caller.
+    fn deposit_storage(contract: &mut PriceHistory) {
+        switch_context(|context| context.attached_deposit = NearToken::from_near(1));
+        contract.storage_deposit(None, None);
+        switch_context(|_| {});
+    }
+
+    const ONE_NEAR: u128 = 10u128.pow(24);
+
+    fn price(value: f64) -> Price {
+        Price::new(value).unwrap()
+    }
+
+    fn btc() -> String {
+        "BTC".to_string()
+    }
+
+    fn metadata(name: &str, base: &str) -> AssetMetadata {
+        AssetMetadata {
+            name: name.to_string(),
+            base: base.to_string(),
+            quote: "USD".to_string(),
+            source: "CoinMarketCap".to_string(),
+        }
+    }
+
+    /// Creates a contract with a registered BTC asset.
+    fn with_btc() -> PriceHistory {
+        let mut contract = PriceHistory::new(None);
+        deposit_storage(&mut contract);
+        contract.register_asset(btc(), 8, metadata("Bitcoin", "BTC"), None);
+        contract
+    }
+
+    #[test]
+    fn record() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut counter = with_btc();
+
+        for value in [1., 2., 3., 4., 5.] {
+            counter.record_price(btc(), price(value), None, None);
+        }
+
+        let expected = 3.;
+        assert!((counter.get_average(btc(), None) - expected).abs() < 1e-5);
+        assert_eq!(
+            counter.get_average_fixed(btc(), None, None),
+            U128(300_000_000)
+        );
+    }
+
+    #[test]
+    fn average_rounding() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for value in [1., 1., 1., 1., 2.] {
+            contract.record_price(btc(), price(value), None, None);
+        }
+        let average = |decimals, rounding| contract.get_average_fixed(btc(), decimals, rounding).0;
+        assert_eq!(average(Some(0), None), 1);
+        assert_eq!(average(Some(0), Some(Rounding::Up)), 2);
+        assert_eq!(average(Some(1), Some(Rounding::Down)), 12);
+        assert_eq!(average(None, Some(Rounding::Down)), 120_000_000);
+    }
+
+    #[test]
+    fn register_assets() {
+        testing_env!(get_context(false));
+        let mut contract = PriceHistory::new(None);
+        deposit_storage(&mut contract);
+        let registration = |symbol: &str| AssetRegistration {
+            symbol: symbol.to_string(),
+            decimals: 8,
+            metadata: metadata(symbol, symbol),
+            config: None,
+        };
+        contract.register_assets(vec![registration("BTC"), registration("ETH")]);
+        assert_eq!(contract.list_assets(None, None).len(), 2);
+
+        let result = std::panic::catch_unwind(move || {
+            contract.register_assets(vec![registration("NEAR"), registration("NEAR")])
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn list_reporters() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        assert_eq!(contract.get_reporter_count(), 0);
+        contract.add_reporter(account("bob.testnet"), ReporterScope::All);
+        contract.add_reporter(account("carol.testnet"), ReporterScope::Assets(vec![btc()]));
+        assert_eq!(contract.get_reporter_count(), 2);
+        assert_eq!(
+            contract.get_reporters(Some(1), None),
+            vec![ReporterInfo {
+                account_id: account("carol.testnet"),
+                scope: ReporterScope::Assets(vec![btc()]),
+            }]
+        );
+        assert_eq!(contract.get_reporters(None, Some(1)).len(), 1);
+    }
+
+    #[test]
+    fn predicates() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        assert!(contract.asset_exists(btc()));
+        assert!(!contract.asset_exists("ETH".to_string()));
+        assert!(contract.is_reporter(account("alice.testnet")));
+        assert!(!contract.is_reporter(account("bob.testnet")));
+        contract.add_reporter(account("bob.testnet"), ReporterScope::All);
+        assert!(contract.is_reporter(account("bob.testnet")));
+    }
+
+    #[test]
+    fn requote() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for _ in 0..5 {
+            contract.record_price(btc(), price(30.), None, None);
+        }
+        contract.register_asset(
+            "EUR".to_string(),
+            4,
+            metadata("Euro", "EUR"),
+            Some(AssetConfig {
+                depth: 1,
+                ..AssetConfig::default()
+            }),
+        );
+        contract.record_price("EUR".to_string(), price(1.5), None, None);
+        assert_eq!(contract.get_average(btc(), Some("USD".to_string())), 30.);
+
+        contract.set_conversion_feed(
+            "USD".to_string(),
+            "EUR".to_string(),
+            Some("EUR".to_string()),
+        );
+        assert_eq!(contract.get_average(btc(), Some("EUR".to_string())), 20.);
+        assert!(near_sdk::test_utils::get_logs()
+            .iter()
+            .any(|log| log.contains(
+                "\"field\":\"conversions\",\"previous\":{\"USD/EUR\":null},\
+             \"value\":{\"USD/EUR\":\"EUR\"}"
+            )));
+        contract.set_conversion_feed(
+            "EUR".to_string(),
+            "USD".to_string(),
+            Some("EUR".to_string()),
+        );
+        assert_eq!(
+            contract.get_conversion_feed("EUR".to_string(), "USD".to_string()),
+            Some("EUR".to_string())
+        );
+        contract.set_conversion_feed("USD".to_string(), "EUR".to_string(), None);
+        assert!(near_sdk::test_utils::get_logs()
+            .iter()
+            .any(|log| log.contains(
+                "\"field\":\"conversions\",\"previous\":{\"USD/EUR\":\"EUR\"},\
+             \"value\":{\"USD/EUR\":null}"
+            )));
+        assert_eq!(
+            contract.get_conversion_feed("USD".to_string(), "EUR".to_string()),
+            None
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The feed must be a pair of the currencies")]
+    fn requote_mismatch() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_conversion_feed("USD".to_string(), "EUR".to_string(), Some(btc()));
+    }
+
+    #[test]
+    fn yocto_conversion() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.register_asset(
+            "NEAR".to_string(),
+            4,
+            metadata("NEAR Protocol", "NEAR"),
+            Some(AssetConfig {
+                depth: 1,
+                ..AssetConfig::default()
+            }),
+        );
+        contract.record_price("NEAR".to_string(), price(4.), None, None);
+        for _ in 0..5 {
+            contract.record_price(btc(), price(2.), None, None);
+        }
+        let one_near = 10u128.pow(24);
+        // 10 NEAR worth 40 USD, i.e. 20 BTC.
+        assert_eq!(
+            contract.price_in_yocto(btc(), U128(10 * one_near)).0,
+            2_000_000_000
+        );
+        assert_eq!(
+            contract.yocto_in_price(btc(), U128(2_000_000_000)).0,
+            10 * one_near
+        );
+        assert_eq!(contract.price_in_yocto(btc(), U128(1)).0, 0);
+    }
+
+    #[test]
+    fn price_update_event() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut counter = with_btc();
+
+        counter.record_price(btc(), price(1.5), None, None);
+
+        assert_eq!(
+            near_sdk::test_utils::get_logs(),
+            vec![
+                "Registered BTC with 8 decimals",
+                "EVENT_JSON:{\"standard\":\"coinmarketcap\",\"version\":\"1.0.0\",\
+                 \"event\":\"price_update\",\"data\":{\"symbol\":\"BTC\",\"price\":1.5,\
+                 \"reporter\":\"alice.testnet\",\"round\":1,\"timestamp\":\"0\",\
+                 \"average\":null}}"
+            ]
+        );
+    }
+
+    #[test]
+    fn price_deviation_event() {
+        testing_env!(get_context(false));
+        let mut counter = with_btc();
+        counter.set_asset_config(
+            btc(),
+            AssetConfig {
+                depth: 2,
+                alert_deviation_bps: 1_000,
+                ..AssetConfig::default()
+            },
+        );
+        for value in [10., 10., 11.] {
+            counter.record_price(btc(), price(value), None, None);
+        }
+        assert!(!near_sdk::test_utils::get_logs()
+            .iter()
+            .any(|log| log.contains("price_deviation")));
+
+        counter.record_price(btc(), price(13.), None, None);
+        assert_eq!(
+            near_sdk::test_utils::get_logs().last().unwrap(),
+            "EVENT_JSON:{\"standard\":\"coinmarketcap\",\"version\":\"1.0.0\",\
+             \"event\":\"price_deviation\",\"data\":{\"symbol\":\"BTC\",\"previous\":10.5,\
+             \"average\":12.0,\"deviation_bps\":1429,\"round\":4,\"timestamp\":\"0\"}}"
+        );
+    }
+
+    #[test]
+    fn moving_average_cross_event() {
+        testing_env!(get_context(false));
+        let mut counter = with_btc();
+        counter.set_asset_config(
+            btc(),
+            AssetConfig {
+                depth: 4,
+                short_depth: Some(2),
+                ..AssetConfig::default()
+            },
+        );
+        let crosses = |values: &[f64], counter: &mut PriceHistory| {
+            for &value in values {
+                counter.record_price(btc(), price(value), None, None);
+            }
+            near_sdk::test_utils::get_logs()
+                .iter()
+                .filter(|log| log.contains("moving_average_cross"))
+                .count()
+        };
+        // Falling, then below the average.
+        assert_eq!(crosses(&[4., 3., 2., 1., 1.], &mut counter), 0);
+        assert_eq!(crosses(&[5.], &mut counter), 1);
+        assert_eq!(
+            near_sdk::test_utils::get_logs().last().unwrap(),
+            "EVENT_JSON:{\"standard\":\"coinmarketcap\",\"version\":\"1.0.0\",\
+             \"event\":\"moving_average_cross\",\"data\":{\"symbol\":\"BTC\",\"kind\":\"golden\",\
+             \"short_average\":3.0,\"average\":2.25,\"round\":6,\"timestamp\":\"0\"}}"
+        );
+        assert_eq!(crosses(&[0.5, 0.5], &mut counter), 2);
+    }
+
+    #[test]
+    fn update_asset_config() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        let patch = |json: &str| near_sdk::serde_json::from_str::<AssetConfigPatch>(json).unwrap();
+        contract.update_asset_config(
+            btc(),
+            patch(r#"{"heartbeat": "10", "dedup_tolerance_bps": 5}"#),
+        );
+        contract.update_asset_config(btc(), patch(r#"{"depth": 3}"#));
+        assert_eq!(
+            contract.get_asset_config(btc()),
+            AssetConfig {
+                depth: 3,
+                heartbeat: 10.into(),
+                dedup_tolerance_bps: Some(5),
+                ..AssetConfig::default()
+            }
+        );
+
+        contract.update_asset_config(btc(), patch(r#"{"dedup_tolerance_bps": null}"#));
+        assert_eq!(contract.get_asset_config(btc()).dedup_tolerance_bps, None);
+        assert_eq!(contract.get_asset_config(btc()).depth, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Short depth must be within 1..depth")]
+    fn short_too_deep() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_asset_config(
+            btc(),
+            AssetConfig {
+                short_depth: Some(5),
+                ..AssetConfig::default()
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The quarantine needs a deviation")]
+    fn quarantine_without_deviation() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.update_asset_config(
+            btc(),
+            AssetConfigPatch {
+                quarantine_rounds: Some(3),
+                ..AssetConfigPatch::default()
+            },
+        );
+    }
+
+    #[test]
+    fn config_change_event() {
+        testing_env!(get_context(false));
+        let mut counter = with_btc();
+        counter.set_asset_config(
+            btc(),
+            AssetConfig {
+                depth: 3,
+                ..AssetConfig::default()
+            },
+        );
+        assert_eq!(
+            near_sdk::test_utils::get_logs().last().unwrap(),
+            "EVENT_JSON:{\"standard\":\"coinmarketcap\",\"version\":\"1.0.0\",\
+             \"event\":\"config_change\",\"data\":{\"symbol\":\"BTC\",\"field\":\"depth\",\
+             \"previous\":5,\"value\":3,\"timestamp\":\"0\"}}"
+        );
+
+        counter.set_access_fee(U128(10));
+        assert_eq!(
+            near_sdk::test_utils::get_logs().last().unwrap(),
+            "EVENT_JSON:{\"standard\":\"coinmarketcap\",\"version\":\"1.0.0\",\
+             \"event\":\"config_change\",\"data\":{\"field\":\"access_fee\",\
+             \"previous\":\"0\",\"value\":\"10\",\"timestamp\":\"0\"}}"
+        );
+
+        // Whatever the log level.
+        counter.set_log_level(LogLevel::Off);
+        counter.set_source_weight("binance".to_string(), 3);
+        assert_eq!(
+            near_sdk::test_utils::get_logs().last().unwrap(),
+            "EVENT_JSON:{\"standard\":\"coinmarketcap\",\"version\":\"1.0.0\",\
+             \"event\":\"config_change\",\"data\":{\"field\":\"source_weights\",\
+             \"previous\":{\"binance\":1},\"value\":{\"binance\":3},\"timestamp\":\"0\"}}"
+        );
+
+        let changed = |field: &str| {
+            near_sdk::test_utils::get_logs()
+                .last()
+                .unwrap()
+                .contains(&format!("\"field\":\"{}\"", field))
+        };
+        counter.set_log_level(LogLevel::Error);
+        assert!(changed("log_level"));
+        counter.add_reporter(account("bob.testnet"), ReporterScope::All);
+        assert!(near_sdk::test_utils::get_logs().last().unwrap().contains(
+            "\"field\":\"reporters\",\"previous\":{\"bob.testnet\":null},\
+             \"value\":{\"bob.testnet\":\"All\"}"
+        ));
+        counter.remove_reporter(account("bob.testnet"));
+        assert!(changed("reporters"));
+        counter.set_consumer_allowlist(true);
+        assert!(changed("consumer_allowlist"));
+        counter.add_consumer(account("bob.testnet"));
+        assert!(changed("consumers"));
+        counter.remove_consumer(account("bob.testnet"));
+        assert!(changed("consumers"));
+        counter.set_implementation(Some(account("v2.testnet")));
+        assert!(changed("implementation"));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNAUTHORIZED: Sorry, you are not allowed to configure an asset")]
+    fn renounced() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.renounce_ownership(account("alice.testnet"));
+        assert!(contract.is_ownership_renounced());
+        contract.record_price(btc(), price(1.), None, None);
+        contract.set_asset_config(btc(), AssetConfig::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DECOMMISSIONED: The contract has been decommissioned")]
+    fn decommission() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.decommission(Some(account("successor.testnet")), None);
+        let version = contract.get_version();
+        assert!(version.deprecated);
+        assert_eq!(version.successor, Some(account("successor.testnet")));
+        contract.record_price(btc(), price(1.), None, None);
+    }
+
+    #[test]
+    #[cfg(feature = "indicators")]
+    fn rewind() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for value in [1., 2., 3., 4., 5., 100., 200.] {
+            contract.record_price(btc(), price(value), None, None);
+        }
+        contract.rewind(btc(), 2);
+        assert_eq!(contract.get_average(btc(), None), 3.);
+        assert_eq!(contract.get_statistics(btc()).max, price(5.));
+        assert_eq!(contract.depth_so_far(btc()), 5);
+        contract.record_price(btc(), price(6.), None, None);
+        assert_eq!(contract.get_average(btc(), None), 4.);
+        let history = contract.get_full_history(btc());
+        let history: Vec<_> = history.iter().collect();
+        assert_eq!(history.len(), 6);
+        assert_eq!(history.last().unwrap().price, price(6.));
+        // The rounds of the rewound records aren't reused.
+        let submission = |round| {
+            contract
+                .get_submissions(btc(), U64(round))
+                .map(|submissions| submissions[0].price)
+        };
+        assert_eq!(submission(8), Some(price(6.)));
+        assert_eq!(submission(7), None);
+        assert_eq!(submission(6), None);
+        assert_eq!(submission(5), Some(price(5.)));
+        assert_eq!(contract.get_statistics(btc()).min, price(2.));
+    }
+
+    #[test]
+    fn provenance() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.record_price(btc(), price(1.), None, None);
+        contract.add_reporter(account("bob.testnet"), ReporterScope::All);
+        sign_as("bob.testnet");
+        contract.record_price(btc(), price(2.), None, None);
+        let reporters: Vec<_> = contract
+            .get_history_with_reporters(btc())
+            .into_iter()
+            .map(|entry| (entry.price, entry.reporter))
+            .collect();
+        assert_eq!(
+            reporters,
+            vec![
+                (price(1.), account("alice.testnet")),
+                (price(2.), account("bob.testnet")),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TOO_FREQUENT: The reporter has recorded a price too recently")]
+    fn too_frequent() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_asset_config(
+            btc(),
+            AssetConfig {
+                min_report_interval: 10.into(),
+                ..AssetConfig::default()
+            },
+        );
+        contract.add_reporter(account("bob.testnet"), ReporterScope::All);
+        contract.record_price(btc(), price(1.), None, None);
+        // The interval is tracked per reporter.
+        switch_context(|context| {
+            context.block_timestamp = 5;
+            context.signer_account_id = account("bob.testnet");
+        });
+        contract.record_price(btc(), price(2.), None, None);
+        switch_context(|context| context.block_timestamp = 10);
+        contract.record_price(btc(), price(3.), None, None);
+        assert_eq!(contract.depth_so_far(btc()), 3);
+        switch_context(|context| {
+            context.block_timestamp = 10;
+            context.signer_account_id = account("bob.testnet");
+        });
+        contract.record_price(btc(), price(4.), None, None);
+    }
+
+    #[test]
+    #[cfg(feature = "rounds")]
+    fn too_frequent_submissions() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_asset_config(
+            btc(),
+            AssetConfig {
+                min_report_interval: 10.into(),
+                ..AssetConfig::default()
+            },
+        );
+        contract.add_reporter(account("bob.testnet"), ReporterScope::All);
+        contract.set_quorum(2, 2, None);
+        contract.record_price(btc(), price(1.), None, None);
+        sign_as("bob.testnet");
+        contract.record_price(btc(), price(2.), None, None);
+        assert_eq!(contract.depth_so_far(btc()), 1);
+        // Neither of the reporters may submit again, only one of them has
+        // been the median.
+        for reporter in ["jane.testnet", "bob.testnet"] {
+            switch_context(|context| {
+                context.block_timestamp = 5;
+                context.signer_account_id = account(reporter);
+            });
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                contract.record_price(btc(), price(3.), None, None)
+            }));
+            assert!(result.is_err());
+        }
+        switch_context(|context| context.block_timestamp = 10);
+        contract.record_price(btc(), price(3.), None, None);
+        assert_eq!(contract.pending_submissions(btc()), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "rounds")]
+    fn quorum() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.add_reporter(account("bob.testnet"), ReporterScope::All);
+        contract.add_reporter(account("carol.testnet"), ReporterScope::All);
+        contract.set_quorum(2, 3, Some(10.into()));
+        contract.record_price(btc(), price(3.), None, None);
+        sign_as("bob.testnet");
+        contract.record_price(btc(), price(1.), None, None);
+        assert_eq!(contract.pending_submissions(btc()), 2);
+        assert_eq!(contract.depth_so_far(btc()), 0);
+        sign_as("carol.testnet");
+        contract.record_price(btc(), price(2.), None, None);
+        assert_eq!(contract.pending_submissions(btc()), 0);
+        assert_eq!(contract.depth_so_far(btc()), 1);
+        assert_eq!(
+            contract
+                .get_submissions(btc(), 1.into())
+                .unwrap()
+                .iter()
+                .map(|submission| submission.price.get())
+                .collect::<Vec<_>>(),
+            [3., 1., 2.]
+        );
+        assert_eq!(contract.get_submissions(btc(), 2.into()), None);
+        assert!(near_sdk::test_utils::get_logs()
+            .last()
+            .unwrap()
+            .contains("\"price\":2.0,\"reporter\":\"carol.testnet\""));
+    }
+
+    #[test]
+    #[cfg(feature = "rounds")]
+    fn quality() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.update_asset_config(
+            btc(),
+            AssetConfigPatch {
+                heartbeat: Some(10.into()),
+                ..AssetConfigPatch::default()
+            },
+        );
+        contract.record_price(btc(), price(1.), None, None);
+        let quality =
+            |contract: &PriceHistory| contract.get_all_latest_prices(None, None)[0].quality;
+        assert_eq!(quality(&contract), Quality::Full);
+        // The latest record doesn't make the raised quorum.
+        contract.add_reporter(account("bob.testnet"), ReporterScope::All);
+        contract.set_quorum(2, 2, None);
+        assert_eq!(quality(&contract), Quality::Degraded);
+        switch_context(|context| context.block_timestamp = 11);
+        assert_eq!(quality(&contract), Quality::Stale);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "ERR_OUT_OF_BOUNDS: The quorum must not exceed the amount of the reporters"
+    )]
+    #[cfg(feature = "rounds")]
+    fn quorum_exceeds_reporters() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.add_reporter(account("bob.testnet"), ReporterScope::All);
+        contract.set_quorum(2, 2, None);
+        contract.remove_reporter(account("bob.testnet"));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNAUTHORIZED: Sorry, you are not allowed to record a price")]
+    fn rotation() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for reporter in ["bob.testnet", "carol.testnet", "dave.testnet"] {
+            contract.add_reporter(account(reporter), ReporterScope::All);
+        }
+        contract.set_rotation(Some(1));
+        assert_eq!(contract.get_reporters_on_duty(), [account("bob.testnet")]);
+        sign_as("bob.testnet");
+        contract.record_price(btc(), price(1.), None, None);
+        switch_context(|context| {
+            context.epoch_height = 1;
+            context.signer_account_id = account("carol.testnet");
+        });
+        contract.record_price(btc(), price(2.), None, None);
+        assert_eq!(contract.depth_so_far(btc()), 2);
+        switch_context(|context| {
+            context.epoch_height = 1;
+            context.signer_account_id = account("bob.testnet");
+        });
+        contract.record_price(btc(), price(3.), None, None);
+    }
+
+    #[test]
+    fn slash_proposal() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        let bob = account("bob.testnet");
+        contract.add_reporter(bob.clone(), ReporterScope::All);
+        sign_as("bob.testnet");
+        contract.record_price(btc(), price(1.), None, None);
+        let proposal_id = contract.propose_slash(btc(), bob.clone(), 1.into(), "Off".into());
+        assert_eq!(
+            contract
+                .get_slash_proposal(proposal_id)
+                .map(|proposal| (proposal.evidence.len(), proposal.status)),
+            Some((1, SlashStatus::Pending))
+        );
+
+        sign_as("alice.testnet");
+        contract.resolve_slash(proposal_id, true);
+        assert_eq!(
+            contract
+                .get_slash_proposal(proposal_id)
+                .map(|proposal| proposal.status),
+            Some(SlashStatus::Approved)
+        );
+        assert_eq!(contract.get_reporter_scope(bob), None);
+    }
+
+    #[test]
+    fn silent() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut counter = PriceHistory::new(None);
+        deposit_storage(&mut counter);
+
+        counter.set_log_level(LogLevel::Off);
+        counter.register_asset(btc(), 8, metadata("Bitcoin", "BTC"), None);
+        counter.record_price(btc(), price(1.5), None, None);
+        counter.reset(btc());
+
+        let logs = near_sdk::test_utils::get_logs();
+        assert_eq!(logs[0], "Log level changed from Info to Off");
+        // Only the events of the level change, of the record and of the reset.
+        assert!(logs[1].contains("\"field\":\"log_level\""));
+        assert!(logs[2].contains("\"event\":\"price_update\""));
+        assert!(logs[3].contains("\"event\":\"reset\""));
+        assert_eq!(logs.len(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNAUTHORIZED: Sorry, you are not allowed to reset an asset")]
+    fn reset_by_stranger() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        sign_as("bob.testnet");
+        contract.reset(btc());
+    }
+
+    #[test]
+    fn reset_event() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut counter = with_btc();
+        for value in [1., 2., 3., 4., 5.] {
+            counter.record_price(btc(), price(value), None, None);
+        }
+        counter.set_log_level(LogLevel::Off);
+        counter.set_log_level(LogLevel::Info);
+
+        counter.reset(btc());
+
+        assert_eq!(
+            near_sdk::test_utils::get_logs().last().unwrap(),
+            "EVENT_JSON:{\"standard\":\"coinmarketcap\",\"version\":\"1.0.0\",\
+             \"event\":\"reset\",\"data\":{\"symbol\":\"BTC\",\"caller\":\"jane.testnet\",\"discarded\":5,\
+             \"average\":3.0,\"timestamp\":\"0\"}}"
+        );
+        assert_eq!(counter.depth_so_far(btc()), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "The contract is not initialized")]
+    fn not_initialized() {
+        testing_env!(get_context(false));
+        PriceHistory::default();
+    }
+
+    #[test]
+    #[should_panic]
+    fn empty() {
+        let context = get_context(false);
+        testing_env!(context);
+        let counter = with_btc();
+        counter.get_average(btc(), None);
+    }
+
+    #[test]
+    fn list_assets() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = with_btc();
+        contract.register_asset(
+            "NEAR".to_string(),
+            4,
+            metadata("NEAR Protocol", "NEAR"),
+            None,
+        );
+
+        assert_eq!(
+            contract.get_asset_metadata("NEAR".to_string()),
+            metadata("NEAR Protocol", "NEAR")
+        );
+        assert_eq!(
+            contract.list_assets(Some(1), None),
+            vec![AssetInfo {
+                symbol: "NEAR".to_string(),
+                decimals: 4,
+                metadata: metadata("NEAR Protocol", "NEAR"),
+                frozen: false,
+            }]
+        );
+        assert_eq!(contract.list_assets(None, Some(1))[0].symbol, btc());
+        assert!(contract.list_assets(Some(5), None).is_empty());
+    }
+
+    #[test]
+    fn scoped_reporter() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.register_asset(
+            "NEAR".to_string(),
+            4,
+            metadata("NEAR Protocol", "NEAR"),
+            None,
+        );
+        let bob = account("bob.testnet");
+        contract.add_reporter(bob.clone(), ReporterScope::Assets(vec!["NEAR".to_string()]));
+
+        sign_as("bob.testnet");
+        contract.record_price("NEAR".to_string(), price(5.), None, None);
+        assert_eq!(contract.depth_so_far("NEAR".to_string()), 1);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.record_price(btc(), price(40_000.), None, None)
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Sorry, you are not allowed to record a price")]
+    fn removed_reporter() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        let bob = account("bob.testnet");
+        contract.add_reporter(bob.clone(), ReporterScope::All);
+        contract.remove_reporter(bob.clone());
+
+        sign_as("bob.testnet");
+        contract.record_price(btc(), price(40_000.), None, None);
+    }
+
+    #[test]
+    fn get_averages() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.register_asset(
+            "NEAR".to_string(),
+            4,
+            metadata("NEAR Protocol", "NEAR"),
+            Some(AssetConfig {
+                depth: 1,
+                ..AssetConfig::default()
+            }),
+        );
+        contract.record_price("NEAR".to_string(), price(10.), None, None);
+
+        assert_eq!(
+            contract.get_averages(vec!["NEAR".to_string(), btc()]),
+            vec![
+                AverageInfo {
+                    symbol: "NEAR".to_string(),
+                    average: Some(10.),
+                    updated_at: Some(0.into()),
+                    stale: false,
+                    quality: Quality::Full,
+                },
+                AverageInfo {
+                    symbol: btc(),
+                    average: None,
+                    updated_at: None,
+                    stale: false,
+                    quality: Quality::Full,
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn get_all_latest_prices() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.register_asset(
+            "NEAR".to_string(),
+            4,
+            metadata("NEAR Protocol", "NEAR"),
+            None,
+        );
+        contract.record_price("NEAR".to_string(), price(10.), None, None);
+        contract.record_price("NEAR".to_string(), price(11.), None, None);
+
+        assert_eq!(
+            contract.get_all_latest_prices(None, None),
+            vec![
+                LatestPrice {
+                    symbol: btc(),
+                    price: None,
+                    timestamp: None,
+                    confidence: None,
+                    stale: false,
+                    quality: Quality::Full,
+                },
+                LatestPrice {
+                    symbol: "NEAR".to_string(),
+                    price: Some(price(11.)),
+                    timestamp: Some(0.into()),
+                    confidence: None,
+                    stale: false,
+                    quality: Quality::Full,
+                }
+            ]
+        );
+        assert_eq!(contract.get_all_latest_prices(Some(1), Some(1)).len(), 1);
+    }
+
+    #[test]
+    fn migrate_asset() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        let bob = account("bob.testnet");
+        contract.add_reporter(bob.clone(), ReporterScope::Assets(vec![btc()]));
+        contract.record_price(btc(), price(40_000.), None, None);
+
+        contract.migrate_asset(btc(), "XBT".to_string());
+
+        assert_eq!(contract.depth_so_far("XBT".to_string()), 1);
+        assert_eq!(
+            contract.get_reporter_scope(bob),
+            Some(ReporterScope::Assets(vec!["XBT".to_string()]))
+        );
+        assert_eq!(contract.list_assets(None, None).len(), 1);
+        sign_as("bob.testnet");
+        contract.record_price("XBT".to_string(), price(41_000.), None, None);
+    }
+
+    #[test]
+    #[cfg(feature = "subscriptions")]
+    fn migrate_subscriptions() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for subscriber in ["bob.testnet", "carol.testnet"] {
+            switch_context(|context| {
+                context.predecessor_account_id = account(subscriber);
+                context.attached_deposit = NearToken::from_near(1);
+            });
+            contract.storage_deposit(None, None);
+            contract.subscribe(vec![btc()], None);
+        }
+        switch_context(|_| {});
+        for value in [1., 2., 3., 4.] {
+            contract.record_price(btc(), price(value), None, None);
+        }
+        switch_context(|context| context.prepaid_gas = Gas::from_tgas(40));
+        contract.record_price(btc(), price(5.), None, None);
+        assert_eq!(contract.get_notification_cursor(btc()), Some(U64(1)));
+
+        switch_context(|_| {});
+        contract.migrate_asset(btc(), "XBT".to_string());
+        assert_eq!(contract.get_notification_cursor(btc()), None);
+        assert_eq!(
+            contract.get_notification_cursor("XBT".to_string()),
+            Some(U64(1))
+        );
+        assert_eq!(
+            contract
+                .get_subscription(account("carol.testnet"))
+                .unwrap()
+                .symbols,
+            vec!["XBT".to_string()]
+        );
+        // The notifications continue under the new symbol.
+        let receipts = near_sdk::test_utils::get_created_receipts().len();
+        assert_eq!(
+            contract.continue_notifications("XBT".to_string(), U64(1)),
+            None
+        );
+        let receipts = &near_sdk::test_utils::get_created_receipts()[receipts..];
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].receiver_id, account("carol.testnet"));
+    }
+
+    #[test]
+    fn storage() {
+        testing_env!(get_context(false));
+        let mut contract = PriceHistory::new(None);
+        deposit_storage(&mut contract);
+        let jane = account("jane.testnet");
+        // The balance locks the storage of its own.
+        let min = contract.storage_balance_bounds().min.0;
+        assert!(min > 0);
+        let deposited = StorageBalance {
+            total: ONE_NEAR.into(),
+            available: (ONE_NEAR - min).into(),
+        };
+        assert_eq!(
+            contract.storage_balance_of(jane.clone()),
+            Some(deposited.clone())
+        );
+
+        contract.register_asset(btc(), 8, metadata("Bitcoin", "BTC"), None);
+        // The history grows at the cost of the same account.
+        contract.record_price(btc(), price(40_000.), None, None);
+        let balance = contract.storage_balance_of(jane.clone()).unwrap();
+        assert_eq!(balance.total.0, ONE_NEAR);
+        assert!(balance.available.0 < ONE_NEAR);
+
+        let report = contract.get_storage_report();
+        assert_eq!(
+            u128::from(report.assets_bytes.0) * env::storage_byte_cost().as_yoctonear(),
+            ONE_NEAR - contract.storage_balance_bounds().min.0 - balance.available.0
+        );
+        assert_eq!(report.reporters_bytes, 0.into());
+        assert_eq!(
+            report.total_bytes.0,
+            report.assets_bytes.0 + report.other_bytes.0
+        );
+
+        contract.remove_asset(btc());
+        assert_eq!(contract.storage_balance_of(jane), Some(deposited));
+        assert!(contract.list_assets(None, None).is_empty());
+    }
+
+    #[test]
+    fn storage_registration() {
+        testing_env!(get_context(false));
+        let mut contract = PriceHistory::new(None);
+        let min = contract.storage_balance_bounds().min.0;
+        let register = |contract: &mut PriceHistory, deposit| {
+            switch_context(|context| context.attached_deposit = NearToken::from_yoctonear(deposit));
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                contract.storage_deposit(Some(account("bob.testnet")), Some(true))
+            }))
+        };
+        // Nobody registers an account at the cost of the contract.
+        assert!(register(&mut contract, 0).is_err());
+        assert_eq!(contract.storage_balance_of(account("bob.testnet")), None);
+        // The excess is refunded.
+        let balance = register(&mut contract, min + 10).unwrap();
+        assert_eq!(balance.total, U128(min));
+        assert_eq!(balance.available, U128(0));
+        assert_eq!(near_sdk::test_utils::get_created_receipts().len(), 1);
+        // The account is registered already.
+        assert!(register(&mut contract, 0).is_ok());
+        // The minimum covers the longest id.
+        let usage = env::storage_usage();
+        switch_context(|context| context.attached_deposit = NearToken::from_yoctonear(min));
+        contract.storage_deposit(Some("a".repeat(64).parse().unwrap()), None);
+        let used = u128::from(env::storage_usage() - usage);
+        assert_eq!(used * env::storage_byte_cost().as_yoctonear(), min);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough storage balance")]
+    fn no_storage_deposit() {
+        testing_env!(get_context(false));
+        let mut contract = PriceHistory::new(None);
+        contract.register_asset(btc(), 8, metadata("Bitcoin", "BTC"), None);
+    }
+
+    #[test]
+    fn allowance_storage() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        let bob = || Some(account("bob.testnet"));
+        switch_context(|context| context.attached_deposit = NearToken::from_near(1));
+        let allowance = contract.deposit_allowance(bob()).0;
+        assert!(allowance < ONE_NEAR && allowance > 0);
+        // Only a new allowance is charged.
+        assert_eq!(contract.deposit_allowance(bob()).0, allowance + ONE_NEAR);
+
+        switch_context(|context| context.attached_deposit = NearToken::from_near(0));
+        let free = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.deposit_allowance(Some(account("carol.testnet")))
+        }));
+        assert!(free.is_err());
+    }
+
+    #[test]
+    fn withdraw_allowance() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        switch_context(|context| context.attached_deposit = NearToken::from_near(1));
+        let allowance = contract.deposit_allowance(None).0;
+        contract.decommission(None, None);
+
+        switch_context(|context| context.attached_deposit = NearToken::from_yoctonear(1));
+        assert_eq!(
+            contract.withdraw_allowance(Some(U128(10))),
+            U128(allowance - 10)
+        );
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.withdraw_allowance(Some(U128(allowance)))
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.withdraw_allowance(None), U128(0));
+        assert_eq!(contract.get_allowance(account("jane.testnet")), U128(0));
+        // The whole deposit is back, the storage of the allowance included.
+        let refunded: u128 = near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .flat_map(|receipt| &receipt.actions)
+            .map(|action| match action {
+                near_sdk::mock::MockAction::Transfer { deposit, .. } => deposit.as_yoctonear(),
+                _ => 0,
+            })
+            .sum();
+        assert_eq!(refunded, ONE_NEAR);
+    }
+
+    #[test]
+    fn premium_view() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for value in 1..=40 {
+            contract.record_price(btc(), price(f64::from(value)), None, None);
+        }
+        contract.set_access_fee(U128(10));
+
+        // An excess deposit goes to the allowance.
+        switch_context(|context| context.attached_deposit = NearToken::from_yoctonear(15));
+        let history = contract.get_full_history(btc());
+        let entries = near_sdk::serde_json::to_value(&history).unwrap();
+        assert_eq!(entries[0]["price"], json!(7.));
+        assert!(entries[0]["timestamp"].is_string());
+        let history: Vec<_> = history.iter().collect();
+        assert_eq!(history.len(), 34);
+        assert_eq!(history[0].price, price(7.));
+        assert_eq!(history[33].price, price(40.));
+        let jane = account("jane.testnet");
+        assert_eq!(contract.get_allowance(jane.clone()), U128(5));
+
+        switch_context(|context| context.attached_deposit = NearToken::from_yoctonear(5));
+        contract.get_full_history(btc());
+        assert_eq!(contract.get_allowance(jane), U128(0));
+        assert_eq!(contract.get_fee_revenue(), U128(20));
+
+        switch_context(|context| context.attached_deposit = NearToken::from_yoctonear(5));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.get_full_history(btc())
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    #[cfg(not(feature = "archive"))]
+    #[should_panic(expected = "The archive is not supported by this build")]
+    fn archive_unsupported() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_asset_config(
+            btc(),
+            AssetConfig {
+                archive: true,
+                ..AssetConfig::default()
+            },
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "archive")]
+    fn archive() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_asset_config(
+            btc(),
+            AssetConfig {
+                archive: true,
+                ..AssetConfig::default()
+            },
+        );
+        for value in 1..=40 {
+            contract.record_price(btc(), price(f64::from(value)), None, None);
+        }
+        assert_eq!(contract.get_archive_len(btc()), 40);
+        let archive = contract.get_archive(btc(), Some(35), None);
+        assert_eq!(archive.len(), 5);
+        assert_eq!(archive[0].price, price(36.));
+
+        let balance = contract
+            .storage_balance_of(account("jane.testnet"))
+            .unwrap();
+        let report = contract.get_storage_report();
+        assert_eq!(
+            u128::from(report.assets_bytes.0) * env::storage_byte_cost().as_yoctonear(),
+            ONE_NEAR - contract.storage_balance_bounds().min.0 - balance.available.0
+        );
+
+        // The only shard is never dropped.
+        assert_eq!(contract.prune_archive(btc(), U64(u64::MAX)), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "archive")]
+    fn record_historical() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_asset_config(
+            btc(),
+            AssetConfig {
+                archive: true,
+                ..AssetConfig::default()
+            },
+        );
+        for value in 1..=10 {
+            switch_context(|context| context.block_timestamp = value * 1_000_000_000);
+            contract.record_price(btc(), price(value as f64), None, None);
+        }
+        let average = contract.get_average(btc(), None);
+        let depth = contract.depth_so_far(btc());
+        contract.record_historical(btc(), price(100.), U64(0));
+        assert_eq!(contract.get_archive_len(btc()), 11);
+        assert_eq!(
+            contract.get_archive(btc(), None, Some(1))[0].price,
+            price(100.)
+        );
+        assert_eq!(contract.get_average(btc(), None), average);
+        assert_eq!(contract.depth_so_far(btc()), depth);
+
+        let future = U64(env::block_timestamp() + 1);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.record_historical(btc(), price(1.), future)
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "indicators")]
+    fn epoch_snapshots() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for value in 1..=5 {
+            contract.record_price(btc(), price(f64::from(value)), None, None);
+        }
+        for epoch in [3, 7] {
+            switch_context(|context| context.epoch_height = epoch);
+            contract.record_price(btc(), price(9.), None, None);
+            contract.record_price(btc(), price(10.), None, None);
+        }
+        // Not enough data in the first epoch.
+        assert_eq!(contract.get_epoch_snapshot(btc(), 0.into()), None);
+        let averages: Vec<_> = [3, 5, 7]
+            .into_iter()
+            .map(|epoch| {
+                contract
+                    .get_epoch_snapshot(btc(), epoch.into())
+                    .map(|snapshot| snapshot.average)
+            })
+            .collect();
+        assert_eq!(averages, vec![Some(3.), None, Some(6.2)]);
+
+        let balance = contract
+            .storage_balance_of(account("jane.testnet"))
+            .unwrap();
+        let report = contract.get_storage_report();
+        assert_eq!(
+            u128::from(report.assets_bytes.0) * env::storage_byte_cost().as_yoctonear(),
+            ONE_NEAR - contract.storage_balance_bounds().min.0 - balance.available.0
+        );
+    }
+
+    #[test]
+    fn token_payment() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.record_price(btc(), price(1.), None, None);
+        contract.set_access_fee(U128(10));
+        let usdc = account("usdc.testnet");
+        contract.set_payment_token(Some(usdc), U128(3));
+        let jane = account("jane.testnet");
+        let bob = account("bob.testnet");
+
+        switch_context(|context| context.predecessor_account_id = account("usdc.testnet"));
+        let refund = contract.ft_on_transfer(bob.clone(), U128(7), "jane.testnet".to_string());
+        assert!(matches!(refund, PromiseOrValue::Value(U128(0))));
+        assert_eq!(contract.get_token_credit(jane.clone()), U128(7));
+        assert_eq!(contract.get_token_credit(bob.clone()), U128(0));
+
+        switch_context(|context| context.predecessor_account_id = account("fake.testnet"));
+        let refund = contract.ft_on_transfer(bob.clone(), U128(7), String::new());
+        assert!(matches!(refund, PromiseOrValue::Value(U128(7))));
+
+        switch_context(|context| context.predecessor_account_id = account("usdc.testnet"));
+        let refund = contract.ft_on_transfer(bob.clone(), U128(4), "fund_rewards".to_string());
+        assert!(matches!(refund, PromiseOrValue::Value(U128(0))));
+        assert_eq!(contract.get_token_reward_pool(), U128(4));
+
+        // A failed transfer restores the rewards.
+        contract.on_rewards_claimed(bob.clone(), U128(2), Err(PromiseError::Failed));
+        assert_eq!(contract.get_token_rewards(bob), U128(2));
+
+        switch_context(|_| {});
+        contract.get_full_history(btc());
+        contract.get_full_history(btc());
+        assert_eq!(contract.get_token_credit(jane.clone()), U128(1));
+        assert_eq!(contract.get_token_reward_pool(), U128(10));
+        assert_eq!(contract.get_fee_revenue(), U128(0));
+
+        // The rest of the credit is withdrawable, and restored on a failure.
+        contract.withdraw_token_credit(None).detach();
+        assert_eq!(contract.get_token_credit(jane.clone()), U128(0));
+        assert!(
+            format!("{:?}", near_sdk::test_utils::get_created_receipts()).contains("usdc.testnet")
+        );
+        switch_context(|context| context.predecessor_account_id = account("alice.testnet"));
+        assert!(!contract.on_credit_withdrawn(jane.clone(), U128(1), Err(PromiseError::Failed)));
+        assert_eq!(contract.get_token_credit(jane), U128(1));
+    }
+
+    #[test]
+    #[cfg(feature = "rounds")]
+    fn rewards() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_payment_token(Some(account("usdc.testnet")), U128(3));
+        contract.set_reward_config(RewardConfig {
+            per_submission: U128(10),
+            median_bonus: U128(5),
+            bonus_tolerance_bps: 100,
+            ..RewardConfig::default()
+        });
+        contract.add_reporter(account("bob.testnet"), ReporterScope::All);
+        contract.set_quorum(2, 2, None);
+        switch_context(|context| context.predecessor_account_id = account("usdc.testnet"));
+        let refund =
+            contract.ft_on_transfer(account("jane.testnet"), U128(100), "fund_rewards".into());
+        assert!(matches!(refund, PromiseOrValue::Value(U128(0))));
+
+        switch_context(|_| {});
+        contract.record_price(btc(), price(100.), None, None);
+        sign_as("bob.testnet");
+        contract.record_price(btc(), price(105.), None, None);
+        assert_eq!(
+            contract.get_token_rewards(account("alice.testnet")),
+            U128(15)
+        );
+        assert_eq!(
+            contract.get_reporter_rewards(account("bob.testnet")),
+            ReporterRewards {
+                unclaimed: U128(10),
+                accrued: U128(10),
+                submissions: U64(1),
+                bonuses: U64(0),
+            }
+        );
+        assert_eq!(contract.get_token_reward_pool(), U128(75));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_OUT_OF_BOUNDS: No rewards to claim")]
+    fn no_rewards() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_payment_token(Some(account("usdc.testnet")), U128(3));
+        contract.claim_rewards().detach();
+    }
+
+    #[test]
+    #[should_panic(expected = "Sorry, you are not allowed to consume the data")]
+    fn consumer_allowlist() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.record_price(btc(), price(1.), None, None);
+        let jane = account("jane.testnet");
+        contract.set_consumer_allowlist(true);
+        assert!(!contract.is_consumer(jane.clone()));
+        contract.add_consumer(jane.clone());
+        assert!(contract.is_consumer(jane.clone()));
+        assert_eq!(contract.get_full_history(btc()).len(), 1);
+
+        contract.remove_consumer(jane);
+        contract.get_full_history(btc());
+    }
+
+    #[test]
+    fn get_average_and_call() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for value in [1., 2., 3., 4., 5.] {
+            contract.record_price(btc(), price(value), None, None);
+        }
+        let recorded_at = env::block_timestamp();
+        switch_context(|context| context.block_timestamp = recorded_at + 1_000);
+        let consumer = account("consumer.testnet");
+        contract
+            .get_average_and_call(btc(), consumer.clone(), "hello".to_string())
+            .detach();
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        // The average is as of the latest record, not of the block.
+        let near_sdk::mock::MockAction::FunctionCallWeight { args, .. } = &receipts[0].actions[0]
+        else {
+            panic!("Not a function call: {:?}", receipts[0].actions[0]);
+        };
+        let args: near_sdk::serde_json::Value = near_sdk::serde_json::from_slice(args).unwrap();
+        assert_eq!(args["timestamp"], recorded_at.to_string());
+
+        switch_context(|context| context.predecessor_account_id = account("alice.testnet"));
+        assert!(!contract.on_average_delivered(consumer, btc(), Err(PromiseError::Failed)));
+        assert_eq!(
+            near_sdk::test_utils::get_logs(),
+            vec!["Failed to deliver BTC average to consumer.testnet"]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "subscriptions")]
+    fn subscriptions() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        let jane = account("jane.testnet");
+        contract.subscribe(vec![btc()], None);
+        assert_eq!(
+            contract.get_subscribers(None, None),
+            vec![SubscriberInfo {
+                account_id: jane.clone(),
+                subscription: Subscription {
+                    symbols: vec![btc()],
+                    gas: U64(Gas::from_tgas(10).as_gas()),
+                    failures: 0,
+                },
+            }]
+        );
+        for value in [1., 2., 3., 4.] {
+            contract.record_price(btc(), price(value), None, None);
+        }
+        assert!(near_sdk::test_utils::get_created_receipts().is_empty());
+        contract.record_price(btc(), price(5.), None, None);
+        assert_eq!(near_sdk::test_utils::get_created_receipts().len(), 2);
+
+        switch_context(|context| context.predecessor_account_id = account("alice.testnet"));
+        for failures in 1..3 {
+            assert!(!contract.on_notified(jane.clone(), Err(PromiseError::Failed)));
+            assert_eq!(
+                contract.get_subscription(jane.clone()).unwrap().failures,
+                failures
+            );
+        }
+        assert!(contract.on_notified(jane.clone(), Ok(())));
+        assert_eq!(contract.get_subscription(jane.clone()).unwrap().failures, 0);
+        for _ in 0..3 {
+            contract.on_notified(jane.clone(), Err(PromiseError::Failed));
+        }
+        assert_eq!(contract.get_subscription(jane.clone()), None);
+
+        switch_context(|_| {});
+        contract.subscribe(vec![btc()], Some(U64(Gas::from_tgas(20).as_gas())));
+        assert!(contract.unsubscribe());
+        assert!(!contract.unsubscribe());
+        assert!(contract.get_subscribers(None, None).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "subscriptions")]
+    fn notifications_out_of_gas() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        let eth = "ETH".to_string();
+        contract.register_asset(eth.clone(), 8, metadata("Ether", "ETH"), None);
+        for subscriber in ["bob.testnet", "carol.testnet", "dave.testnet"] {
+            switch_context(|context| {
+                context.predecessor_account_id = account(subscriber);
+                context.attached_deposit = NearToken::from_near(1);
+            });
+            contract.storage_deposit(None, None);
+            contract.subscribe(vec![eth.clone()], None);
+        }
+        switch_context(|_| {});
+        for value in [1., 2., 3., 4.] {
+            contract.record_price(btc(), price(value), None, None);
+        }
+        // The subscribers of another asset aren't walked past the reserve,
+        // the rest of them are left to the next batch.
+        switch_context(|context| context.prepaid_gas = Gas::from_tgas(12));
+        contract.record_price(btc(), price(5.), None, None);
+        let cursor = contract.get_notification_cursor(btc()).unwrap();
+        assert!(cursor.0 < 3);
+        assert!(near_sdk::test_utils::get_created_receipts().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "subscriptions")]
+    fn batched_notifications() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for subscriber in ["jane.testnet", "bob.testnet", "carol.testnet"] {
+            switch_context(|context| {
+                context.predecessor_account_id = account(subscriber);
+                context.attached_deposit = NearToken::from_near(1);
+            });
+            contract.storage_deposit(None, None);
+            contract.subscribe(vec![btc()], None);
+        }
+        switch_context(|_| {});
+        for value in [1., 2., 3., 4.] {
+            contract.record_price(btc(), price(value), None, None);
+        }
+        switch_context(|context| context.prepaid_gas = Gas::from_tgas(40));
+        contract.record_price(btc(), price(5.), None, None);
+        assert_eq!(near_sdk::test_utils::get_created_receipts().len(), 2);
+        assert_eq!(contract.get_notification_cursor(btc()), Some(U64(1)));
+
+        switch_context(|context| context.prepaid_gas = Gas::from_tgas(40));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.continue_notifications(btc(), U64(0))
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.continue_notifications(btc(), U64(1)), Some(U64(2)));
+        switch_context(|_| {});
+        assert_eq!(contract.continue_notifications(btc(), U64(2)), None);
+        assert_eq!(contract.get_notification_cursor(btc()), None);
+    }
+
+    #[test]
+    #[cfg(feature = "rounds")]
+    fn poke() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_asset_config(
+            btc(),
+            AssetConfig {
+                depth: 1,
+                heartbeat: 20.into(),
+                ..AssetConfig::default()
+            },
+        );
+        contract.set_payment_token(Some(account("usdc.testnet")), U128(3));
+        contract.set_reward_config(RewardConfig {
+            keeper_reward: U128(7),
+            ..RewardConfig::default()
+        });
+        contract.add_reporter(account("bob.testnet"), ReporterScope::All);
+        contract.set_quorum(1, 2, Some(10.into()));
+        switch_context(|context| context.predecessor_account_id = account("usdc.testnet"));
+        let refund =
+            contract.ft_on_transfer(account("jane.testnet"), U128(10), "fund_rewards".into());
+        assert!(matches!(refund, PromiseOrValue::Value(U128(0))));
+
+        switch_context(|_| {});
+        contract.record_price(btc(), price(100.), None, None);
+        switch_context(|context| context.block_timestamp = 5);
+        assert_eq!(contract.poke(), Housekeeping::default());
+        switch_context(|context| context.block_timestamp = 10);
+        assert_eq!(
+            contract.poke(),
+            Housekeeping {
+                rounds_closed: 1,
+                reward: U128(7),
+                ..Housekeeping::default()
+            }
+        );
+        assert_eq!(contract.depth_so_far(btc()), 1);
+
+        // The event is emitted whatever the log level.
+        contract.set_log_level(LogLevel::Error);
+        switch_context(|context| context.block_timestamp = 31);
+        assert_eq!(
+            contract.poke(),
+            Housekeeping {
+                heartbeats_missed: 1,
+                reward: U128(3),
+                ..Housekeeping::default()
+            }
+        );
+        assert!(near_sdk::test_utils::get_logs()
+            .iter()
+            .any(|log| log.contains("\"event\":\"heartbeat_missed\"")));
+        assert_eq!(contract.poke(), Housekeeping::default());
+        assert_eq!(
+            contract.get_token_rewards(account("jane.testnet")),
+            U128(10)
+        );
+    }
+
+    #[test]
+    fn attestation() {
+        use secp256k1::{Message, Secp256k1, SecretKey};
+
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_asset_config(
+            btc(),
+            AssetConfig {
+                depth: 1,
+                max_timestamp_skew: 60_000_000_000.into(),
+                ..AssetConfig::default()
+            },
+        );
+        let secp = Secp256k1::new();
+        let keys =
+            [[1u8; 32], [2u8; 32], [3u8; 32]].map(|key| SecretKey::from_slice(&key).unwrap());
+        let address = |key: &SecretKey| {
+            let public_key = key.public_key(&secp).serialize_uncompressed();
+            let hash = env::keccak256_array(&public_key[1..]);
+            hash[12..]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>()
+        };
+        contract.set_bridge_signers(Some(BridgeSigners {
+            addresses: keys.iter().take(2).map(address).collect(),
+            threshold: 2,
+        }));
+
+        let attestation = Attestation {
+            symbol: btc(),
+            answer: U128(4_200_050_000_000),
+            decimals: 8,
+            timestamp: U64(30),
+        };
+        let digest = contract.get_attestation_digest(attestation.clone());
+        let sign = |key: &SecretKey| {
+            let message = Message::from_slice(&digest.0).unwrap();
+            let (recovery_id, signature) = secp
+                .sign_ecdsa_recoverable(&message, key)
+                .serialize_compact();
+            AttestationSignature {
+                signature: signature.to_vec().into(),
+                v: recovery_id.to_i32() as u8 + 27,
+            }
+        };
+        switch_context(|context| context.block_timestamp = 60_000_000_000);
+        // The same signer twice, and an unknown one.
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.record_attestation(
+                attestation.clone(),
+                vec![sign(&keys[0]), sign(&keys[0]), sign(&keys[2])],
+            )
+        }));
+        assert!(result.is_err());
+
+        let signatures = vec![sign(&keys[1]), sign(&keys[0])];
+        contract.record_attestation(attestation.clone(), signatures.clone());
+        assert_eq!(contract.get_average(btc(), None), 42_000.5);
+        let submissions = contract.get_submissions(btc(), U64(1)).unwrap();
+        assert_eq!(submissions[0].provenance, Provenance::Bridged);
+        assert_eq!(submissions[0].timestamp, U64(30_000_000_000));
+        // Replayed in another block.
+        switch_context(|context| context.block_index += 1);
+        let replayed = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.record_attestation(attestation.clone(), signatures.clone())
+        }));
+        assert!(replayed.is_err());
+    }
+
+    #[test]
+    fn signed_price() {
+        use secp256k1::{Message, Secp256k1, SecretKey};
+
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_asset_config(
+            btc(),
+            AssetConfig {
+                depth: 1,
+                ..AssetConfig::default()
+            },
+        );
+        let secp = Secp256k1::new();
+        let key = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let public_key = key.public_key(&secp).serialize_uncompressed();
+        let hash = env::keccak256_array(&public_key[1..]);
+        let address = hash[12..]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<String>();
+        contract.add_evm_reporter(address.clone(), ReporterScope::All);
+        let reporter: AccountId = format!("0x{}", address.to_ascii_lowercase())
+            .parse()
+            .unwrap();
+        assert!(contract.is_reporter(reporter.clone()));
+
+        let report = PriceReport {
+            symbol: btc(),
+            price: price(4.),
+            timestamp: None,
+            confidence: None,
+            nonce: U64(1),
+        };
+        let sign = |contract: &PriceHistory, report: &PriceReport| {
+            let digest = contract.get_report_digest(report.clone());
+            let message = Message::from_slice(&digest.0).unwrap();
+            let (recovery_id, signature) = secp
+                .sign_ecdsa_recoverable(&message, &key)
+                .serialize_compact();
+            AttestationSignature {
+                signature: signature.to_vec().into(),
+                v: recovery_id.to_i32() as u8,
+            }
+        };
+        let signature = sign(&contract, &report);
+        // Relayed by anyone.
+        switch_context(|context| context.signer_account_id = account("relayer"));
+        contract.record_signed_price(report.clone(), signature.clone());
+        assert_eq!(contract.get_average(btc(), None), 4.);
+        let submissions = contract.get_submissions(btc(), U64(1)).unwrap();
+        assert_eq!(submissions[0].reporter, reporter);
+        assert_eq!(contract.get_evm_nonce(address.clone()), Some(U64(1)));
+
+        // A replay.
+        switch_context(|context| context.block_timestamp = 10);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.record_signed_price(report.clone(), signature)
+        }));
+        assert!(result.is_err());
+        // A tampered report.
+        let tampered = PriceReport {
+            nonce: U64(2),
+            ..report.clone()
+        };
+        let signature = sign(&contract, &report);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.record_signed_price(tampered, signature)
+        }));
+        assert!(result.is_err());
+
+        let report = PriceReport {
+            price: price(2.),
+            nonce: U64(5),
+            ..report
+        };
+        let signature = sign(&contract, &report);
+        contract.record_signed_price(report, signature);
+        assert_eq!(contract.get_evm_nonce(address), Some(U64(5)));
+    }
+
+    #[test]
+    fn gas_profile() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        let eth = "ETH".to_string();
+        contract.register_asset(eth.clone(), 8, metadata("Ether", "ETH"), None);
+        // Hint: a benchmark of the records, the gas of which mustn't grow
+        // with the window.
+        let mut profile = |symbol: &String, depth: u16| {
+            contract.set_asset_config(
+                symbol.clone(),
+                AssetConfig {
+                    depth,
+                    ..AssetConfig::default()
+                },
+            );
+            for value in 0..u64::from(depth) * 2 {
+                switch_context(|context| context.block_timestamp = value);
+                contract.record_price(symbol.clone(), price((value % 7 + 1) as f64), None, None);
+            }
+            contract.get_gas_profile(symbol.clone())
+        };
+        let small = profile(&btc(), 8);
+        let large = profile(&eth, 128);
+        assert_eq!((small.depth, large.depth), (8, 128));
+        assert_eq!(large.history_len, U64(128));
+        assert!(large.latest_record.0 <= large.peak_record.0);
+        assert!(large.peak_record.0 < small.peak_record.0 * 2);
+        assert_eq!(large.views.len(), 4);
+    }
+
+    #[test]
+    #[cfg(feature = "indicators")]
+    fn price_cumulative() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.record_price(btc(), price(2.), None, None);
+        contract.reset(btc());
+        switch_context(|context| context.block_timestamp = 10);
+        contract.record_price(btc(), price(3.), None, None);
+        switch_context(|context| context.block_timestamp = 15);
+
+        assert_eq!(
+            contract.get_price_cumulative(btc()),
+            CumulativePrice {
+                price_cumulative: 35.,
+                timestamp: 15.into(),
+            }
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "indicators")]
+    fn observe() {
+        const SECOND: u64 = 1_000_000_000;
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        switch_context(|context| context.block_timestamp = 10 * SECOND);
+        contract.record_price(btc(), price(2.), None, None);
+        switch_context(|context| context.block_timestamp = 20 * SECOND);
+        contract.record_price(btc(), price(4.), None, None);
+        switch_context(|context| context.block_timestamp = 30 * SECOND);
+
+        let observed = contract.observe(btc(), vec![20, 15, 0]);
+        let cumulatives: Vec<_> = observed.iter().map(|c| c.price_cumulative).collect();
+        assert_eq!(
+            cumulatives,
+            vec![0., 10. * SECOND as f64, 60. * SECOND as f64]
+        );
+        let twap = (observed[2].price_cumulative - observed[0].price_cumulative)
+            / (observed[2].timestamp.0 - observed[0].timestamp.0) as f64;
+        assert_eq!(twap, 3.);
+
+        let result = std::panic::catch_unwind(|| contract.observe(btc(), vec![21]));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "indicators")]
+    fn grow_observation_cardinality() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.grow_observation_cardinality(btc(), MAX_CARDINALITY + 1)
+        }));
+        assert!(result.is_err());
+
+        // The added observations are paid by the caller, and credited to
+        // the account which pays for the records.
+        let jane = account("jane.testnet");
+        let available = contract
+            .storage_balance_of(jane.clone())
+            .unwrap()
+            .available
+            .0;
+        let cost = 2 * u128::from(OBSERVATION_BYTES) * env::storage_byte_cost().as_yoctonear();
+        switch_context(|context| {
+            context.predecessor_account_id = account("bob.testnet");
+            context.attached_deposit = NearToken::from_yoctonear(cost);
+        });
+        assert_eq!(contract.grow_observation_cardinality(btc(), 10), 10);
+        assert_eq!(contract.get_observation_cardinality(btc()), 10);
+        assert!(near_sdk::test_utils::get_created_receipts().is_empty());
+        assert_eq!(
+            contract.storage_balance_of(jane).unwrap().available.0,
+            available + cost
+        );
+
+        switch_context(|context| {
+            context.predecessor_account_id = account("bob.testnet");
+            context.attached_deposit = NearToken::from_yoctonear(cost - 1);
+        });
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.grow_observation_cardinality(btc(), 12)
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn proxy() {
+        testing_env!(get_context(false));
+        let mut contract = PriceHistory::new(None);
+        let result = std::panic::catch_unwind(|| contract.proxy_get_average(btc(), None));
+        assert!(result.is_err());
+
+        let implementation = account("v2.testnet");
+        contract.set_implementation(Some(implementation));
+        assert_eq!(contract.get_implementation(), Some(account("v2.testnet")));
+        contract.proxy_get_average(btc(), None).detach();
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert_eq!(receipts.len(), 1);
+        assert!(format!("{:?}", receipts[0]).contains("v2.testnet"));
+    }
+
+    #[test]
+    fn version() {
+        testing_env!(get_context(false));
+        let contract = PriceHistory::new(None);
+        assert_eq!(contract.get_version().version, env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn freeze() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.freeze_asset(btc());
+        assert!(contract.is_frozen(btc()));
+        assert!(contract.list_assets(None, None)[0].frozen);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.record_price(btc(), price(40_000.), None, None)
+        }));
+        assert!(result.is_err());
+
+        contract.unfreeze_asset(btc());
+        contract.record_price(btc(), price(40_000.), None, None);
+        assert_eq!(contract.depth_so_far(btc()), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_STALE: The price is stale")]
+    fn stale() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_asset_config(
+            btc(),
+            AssetConfig {
+                depth: 1,
+                heartbeat: 10.into(),
+                max_deviation_bps: 0,
+                archive: false,
+                dedup_tolerance_bps: None,
+                archive_encoding: ArchiveEncoding::Full,
+                one_per_block: false,
+                max_timestamp_skew: 0.into(),
+                alert_deviation_bps: 0,
+                short_depth: None,
+                min_report_interval: 0.into(),
+                archive_retention: 0.into(),
+                allow_zero: false,
+                quarantine_rounds: 0,
+                quarantine_deviation_bps: 0,
+                finality_blocks: 0,
+                log_returns: false,
+                kalman: None,
+            },
+        );
+        contract.record_price(btc(), price(1.), None, None);
+        assert_eq!(contract.get_average(btc(), None), 1.);
+
+        switch_context(|context| context.block_timestamp = 11);
+        assert!(contract.is_stale(btc()));
+        contract.get_average(btc(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_OUT_OF_BOUNDS: The price must not be zero")]
+    fn zero_price() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.update_asset_config(
+            btc(),
+            AssetConfigPatch {
+                allow_zero: Some(true),
+                ..AssetConfigPatch::default()
+            },
+        );
+        contract.record_price(btc(), price(0.), None, None);
+        assert_eq!(contract.depth_so_far(btc()), 1);
+        contract.update_asset_config(
+            btc(),
+            AssetConfigPatch {
+                allow_zero: Some(false),
+                ..AssetConfigPatch::default()
+            },
+        );
+        // Rounded to zero by the fixed-point representation.
+        contract.record_price(btc(), price(1e-20), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_SAME_BLOCK: A price has already been recorded in this block")]
+    fn same_block() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.record_price(btc(), price(1.), None, None);
+        contract.record_price(btc(), price(2.), None, None);
+        contract.set_asset_config(
+            btc(),
+            AssetConfig {
+                one_per_block: true,
+                ..AssetConfig::default()
+            },
+        );
+        switch_context(|context| context.block_index = 1);
+        contract.record_price(btc(), price(3.), None, None);
+        assert_eq!(contract.depth_so_far(btc()), 3);
+        contract.record_price(btc(), price(4.), None, None);
+    }
+
+    #[test]
+    #[cfg(feature = "rounds")]
+    fn same_block_quorum() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_asset_config(
+            btc(),
+            AssetConfig {
+                one_per_block: true,
+                ..AssetConfig::default()
+            },
+        );
+        contract.add_reporter(account("bob.testnet"), ReporterScope::All);
+        contract.set_quorum(2, 2, None);
+        // The submissions to the same round share the block.
+        contract.record_price(btc(), price(1.), None, None);
+        sign_as("bob.testnet");
+        contract.record_price(btc(), price(2.), None, None);
+        assert_eq!(contract.depth_so_far(btc()), 1);
+        // A new round waits for the next block.
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.record_price(btc(), price(3.), None, None)
+        }));
+        assert!(result.is_err());
+        switch_context(|context| context.block_index = 1);
+        contract.record_price(btc(), price(3.), None, None);
+        assert_eq!(contract.pending_submissions(btc()), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_OUT_OF_BOUNDS: The timestamp must be within the allowed skew")]
+    fn reporter_timestamp() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_asset_config(
+            btc(),
+            AssetConfig {
+                max_timestamp_skew: 10.into(),
+                ..AssetConfig::default()
+            },
+        );
+        switch_context(|context| context.block_timestamp = 100);
+        contract.record_price(btc(), price(1.), Some(95.into()), None);
+        contract.record_price(btc(), price(2.), Some(95.into()), None);
+        assert_eq!(
+            contract.get_all_latest_prices(None, None)[0].timestamp,
+            Some(95.into())
+        );
+        contract.record_price(btc(), price(3.), Some(105.into()), None);
+    }
+
+    #[test]
+    #[cfg(feature = "archive")]
+    #[should_panic(expected = "ERR_OUT_OF_BOUNDS: The timestamp must be within the allowed skew")]
+    fn rewound_timestamp() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_asset_config(
+            btc(),
+            AssetConfig {
+                max_timestamp_skew: 100.into(),
+                archive: true,
+                ..AssetConfig::default()
+            },
+        );
+        switch_context(|context| context.block_timestamp = 100);
+        contract.record_price(btc(), price(1.), Some(95.into()), None);
+        contract.rewind(btc(), 1);
+        // The archive still keeps the rewound record.
+        contract.record_price(btc(), price(2.), Some(90.into()), None);
+    }
+
+    #[test]
+    fn gaps() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for timestamp in [0, 10, 20, 50, 60, 100] {
+            switch_context(|context| context.block_timestamp = timestamp);
+            contract.record_price(btc(), price(1.), None, None);
+        }
+        let gaps = contract.get_gaps(btc(), 10.into(), None, None, None);
+        assert_eq!(
+            gaps,
+            vec![
+                Gap {
+                    from: 20.into(),
+                    to: 50.into(),
+                },
+                Gap {
+                    from: 60.into(),
+                    to: 100.into(),
+                },
+            ]
+        );
+        assert_eq!(
+            contract
+                .get_gaps(btc(), 10.into(), None, None, Some(1))
+                .len(),
+            1
+        );
+        assert!(contract
+            .get_gaps(btc(), 40.into(), None, None, None)
+            .is_empty());
+        // Only the intervals overlapping the range.
+        assert_eq!(
+            contract.get_gaps(btc(), 10.into(), Some(50.into()), None, None),
+            gaps[1..]
+        );
+        assert_eq!(
+            contract.get_gaps(btc(), 10.into(), Some(49.into()), Some(60.into()), None),
+            gaps[..1]
+        );
+    }
+
+    #[test]
+    fn discrete_views() {
+        testing_env!(get_context(false));
+        let contract = with_btc();
+        assert_eq!(contract.description(btc()), "BTC / USD");
+        assert_eq!(contract.decimals(btc()), 8);
+        assert_eq!(contract.version(), contract.get_version().version);
+    }
+
+    #[test]
+    fn pyth_price() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        switch_context(|context| context.block_timestamp = 2_500_000_000);
+        contract.record_price(btc(), price(40_000.5), None, Some(price(1.25)));
+        assert_eq!(
+            contract.get_pyth_price(btc()),
+            PythPrice {
+                price: 4_000_050_000_000.into(),
+                conf: 125_000_000.into(),
+                expo: -8,
+                publish_time: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn price_at_time() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for (timestamp, value) in [(10, 10.), (20, 20.), (30, 5.)] {
+            switch_context(|context| context.block_timestamp = timestamp);
+            contract.record_price(btc(), price(value), None, None);
+        }
+        let at = |timestamp: u64| {
+            let price = contract.get_price_at_time(btc(), timestamp.into());
+            (price.price.get(), price.timestamp.0, price.nearest)
+        };
+        assert_eq!(at(15), (15., 15, false));
+        assert_eq!(at(20), (20., 20, false));
+        assert_eq!(at(26), (11., 26, false));
+        assert_eq!(at(5), (10., 10, true));
+        assert_eq!(at(35), (5., 30, true));
+    }
+
+    #[test]
+    #[should_panic(expected = "History depth must be within 1..=4096")]
+    fn too_deep() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_asset_config(
+            btc(),
+            AssetConfig {
+                depth: 4097,
+                ..AssetConfig::default()
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_REGISTERED: The asset is not registered")]
+    fn unregistered() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut counter = with_btc();
+        counter.record_price("ETH".to_string(), price(1.), None, None);
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn schema() {
+        use near_sdk::borsh::schema::BorshSchemaContainer;
+
+        let state = BorshSchemaContainer::for_type::<PriceHistory>();
+        assert_eq!(state.validate(), Ok(()));
+        for declaration in ["Quorum", "RewardConfig", "UnorderedMap", "LookupMap"] {
+            assert!(
+                state.get_definition(declaration).is_some(),
+                "{}",
+                declaration
+            );
+        }
+        // The values of the collections are described separately.
+        let asset = BorshSchemaContainer::for_type::<Asset>();
+        assert_eq!(asset.validate(), Ok(()));
+        for declaration in [
+            "AssetConfig",
+            "Ring<PriceRecord>",
+            "Ring<String>",
+            "Archive",
+        ] {
+            assert!(
+                asset.get_definition(declaration).is_some(),
+                "{}",
+                declaration
+            );
+        }
+    }
+
+    #[cfg(feature = "debug")]
+    #[test]
+    fn debug_state() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.set_access_fee(U128(10));
+        let state = contract.debug_state();
+        let snapshot = PriceHistory::try_from_slice(&state.0).unwrap();
+        assert_eq!(snapshot.get_access_fee(), U128(10));
+        assert_eq!(snapshot.get_log_level(), contract.get_log_level());
+        assert_eq!(snapshot.assets.len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "rounds")]
+    fn stats() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.add_reporter(account("bob.testnet"), ReporterScope::All);
+        contract.record_price(btc(), price(1.), None, None);
+        contract.record_price(btc(), price(1.), None, None);
+        // The repeated price only refreshes the record.
+        contract.update_asset_config(
+            btc(),
+            near_sdk::serde_json::from_str(r#"{"dedup_tolerance_bps": 0}"#).unwrap(),
+        );
+        contract.record_price(btc(), price(1.), None, None);
+        contract.set_quorum(2, 3, Some(10.into()));
+        contract.record_price(btc(), price(2.), None, None);
+        // The expired round is discarded by the next submission, and the
+        // latter by the housekeeping.
+        switch_context(|context| {
+            context.block_timestamp = 10;
+            context.signer_account_id = account("bob.testnet");
+        });
+        contract.record_price(btc(), price(3.), None, None);
+        switch_context(|context| context.block_timestamp = 20);
+        contract.poke();
+        contract.reset(btc());
+        assert_eq!(
+            contract.get_stats(),
+            Stats {
+                records_accepted: U64(2),
+                submissions_rejected: U64(2),
+                resets: U64(1),
+                last_reset: Some(U64(20)),
+            }
+        );
+    }
+
+    #[test]
+    fn source_prices() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for (timestamp, source_id, value) in [
+            (1, "coinmarketcap", 10.),
+            (2, "coingecko", 12.),
+            (3, "binance", 11.),
+            (4, "coinmarketcap", 13.),
+        ] {
+            switch_context(|context| context.block_timestamp = timestamp * 1_000_000_000);
+            contract.record_source_price(btc(), source_id.to_string(), price(value), None, None);
+        }
+        let mut sources = contract.get_source_prices(btc());
+        sources.sort_by(|a, b| a.source_id.cmp(&b.source_id));
+        assert_eq!(
+            sources,
+            [
+                ("binance", 11., 3),
+                ("coingecko", 12., 2),
+                ("coinmarketcap", 13., 4)
+            ]
+            .map(|(source_id, value, timestamp)| SourcePrice {
+                source_id: source_id.to_string(),
+                price: price(value),
+                timestamp: U64(timestamp * 1_000_000_000),
+            })
+        );
+        // A single series.
+        assert_eq!(contract.depth_so_far(btc()), 4);
+        assert_eq!(
+            contract.get_cross_source_median(btc(), None),
+            Some(price(12.))
+        );
+        assert_eq!(
+            contract.get_cross_source_median(btc(), Some(U64(1_000_000_000))),
+            Some(price(11.))
+        );
+
+        assert_eq!(
+            contract.get_cross_source_mean(btc(), None),
+            Some(price(12.))
+        );
+        let mut divergence = contract.get_source_divergence(btc());
+        divergence.sort_by(|a, b| a.source_id.cmp(&b.source_id));
+        let divergence: Vec<_> = divergence
+            .iter()
+            .map(|source| {
+                (
+                    source.source_id.as_str(),
+                    source.age.0,
+                    source.deviation_bps,
+                )
+            })
+            .collect();
+        assert_eq!(
+            divergence,
+            [
+                ("binance", 1_000_000_000, 833),
+                ("coingecko", 2_000_000_000, 0),
+                ("coinmarketcap", 0, 833)
+            ]
+        );
+
+        // A trusted source dominates the median, not the mean.
+        contract.set_source_weight("binance".to_string(), 3);
+        assert_eq!(contract.get_source_weight("binance".to_string()), 3);
+        assert_eq!(
+            contract.get_cross_source_median(btc(), None),
+            Some(price(11.))
+        );
+        assert_eq!(
+            contract.get_cross_source_mean(btc(), None),
+            Some(price(11.6))
+        );
+        contract.set_source_weight("binance".to_string(), 0);
+        contract.set_source_weight("coingecko".to_string(), 0);
+        assert_eq!(
+            contract.get_cross_source_median(btc(), None),
+            Some(price(13.))
+        );
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.record_source_price(btc(), String::new(), price(1.), None, None)
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn source_quarantine() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.update_asset_config(
+            btc(),
+            AssetConfigPatch {
+                quarantine_rounds: Some(2),
+                quarantine_deviation_bps: Some(1_000),
+                ..AssetConfigPatch::default()
+            },
+        );
+        // The events are emitted whatever the log level.
+        contract.set_log_level(LogLevel::Off);
+        let record = |contract: &mut PriceHistory, source_id: &str, value: f64| {
+            contract.record_source_price(btc(), source_id.to_string(), price(value), None, None)
+        };
+        record(&mut contract, "a", 10.);
+        record(&mut contract, "b", 10.);
+        // A price within the threshold breaks the streak.
+        record(&mut contract, "c", 20.);
+        record(&mut contract, "c", 10.5);
+        record(&mut contract, "c", 20.);
+        assert!(contract.get_quarantined_sources(btc()).is_empty());
+        record(&mut contract, "c", 20.);
+        assert_eq!(contract.get_quarantined_sources(btc()), ["c"]);
+        assert!(near_sdk::test_utils::get_logs()
+            .iter()
+            .any(|log| log.contains("\"event\":\"source_quarantined\"")));
+        assert_eq!(
+            contract.get_cross_source_mean(btc(), None),
+            Some(price(10.))
+        );
+        let divergence = contract.get_source_divergence(btc());
+        let quarantined = divergence
+            .iter()
+            .find(|source| source.source_id == "c")
+            .unwrap();
+        assert!(quarantined.quarantined);
+        assert_eq!(quarantined.deviation_bps, 10_000);
+
+        contract.reinstate_source(btc(), "c".to_string());
+        assert!(contract.get_quarantined_sources(btc()).is_empty());
+        assert_eq!(
+            contract.get_cross_source_median(btc(), None),
+            Some(price(10.))
+        );
+    }
+
+    #[test]
+    fn official_answer() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.update_asset_config(
+            btc(),
+            AssetConfigPatch {
+                depth: Some(1),
+                finality_blocks: Some(3),
+                ..AssetConfigPatch::default()
+            },
+        );
+        let at_block = |height: u64| switch_context(|context| context.block_index = height);
+        at_block(10);
+        contract.record_price(btc(), price(1.), None, None);
+        assert_eq!(contract.get_official_answer(btc()), None);
+        assert_eq!(contract.get_proposed_answer(btc()).unwrap().average, 1.);
+        at_block(13);
+        assert_eq!(contract.get_official_answer(btc()).unwrap().average, 1.);
+
+        // A flash manipulation is disputed before it becomes official.
+        contract.record_price(btc(), price(100.), None, None);
+        at_block(14);
+        contract.dispute_answer(btc());
+        at_block(20);
+        assert_eq!(contract.get_official_answer(btc()).unwrap().average, 1.);
+        assert_eq!(contract.get_proposed_answer(btc()), None);
+    }
+
+    #[test]
+    fn answers() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for (seconds, value) in [(0, 2.), (10, 4.), (40, 1.)] {
+            switch_context(|context| context.block_timestamp = seconds * 1_000_000_000);
+            contract.record_price(btc(), price(value), None, None);
+        }
+        switch_context(|context| context.block_timestamp = 50_000_000_000);
+        let answers = contract.get_answers(btc(), Some(50));
+        assert_eq!(answers.spot, Some(price(1.)));
+        assert_eq!(answers.average, None);
+        assert_eq!(answers.twap, Some(3.));
+        assert_eq!(answers.twap_period, 50);
+        // Before the oldest observation.
+        assert_eq!(contract.get_answers(btc(), None).twap, None);
+    }
+
+    #[test]
+    fn price_band() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for value in [8., 12., 8., 12., 10.] {
+            contract.record_price(btc(), price(value), None, None);
+        }
+        let band = contract.get_price_band(btc(), None);
+        assert_eq!(band.mid, 10.);
+        assert!((band.volatility - 3.2f64.sqrt()).abs() < 1e-12);
+        assert!((band.conservative - (10. - 2. * 3.2f64.sqrt())).abs() < 1e-12);
+        assert!((band.aggressive - (10. + 2. * 3.2f64.sqrt())).abs() < 1e-12);
+        // Never below zero.
+        assert_eq!(contract.get_price_band(btc(), Some(100)).conservative, 0.);
+    }
+
+    #[test]
+    #[cfg(feature = "indicators")]
+    fn volatility_annualized() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.record_price(btc(), price(100.), None, None);
+        assert_eq!(contract.get_volatility_annualized(btc()), None);
+        // A year apart: the volatility is the log return.
+        switch_context(|context| context.block_timestamp = 31_557_600_000_000_000);
+        contract.record_price(btc(), price(200.), None, None);
+        let volatility = contract.get_volatility_annualized(btc()).unwrap();
+        assert!((volatility - 2f64.ln()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn smoothed_price() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.record_price(btc(), price(100.), None, None);
+        assert_eq!(contract.get_smoothed_price(btc()), None);
+        contract.update_asset_config(
+            btc(),
+            AssetConfigPatch {
+                kalman: Some(Some(KalmanConfig {
+                    process_noise: 1.,
+                    measurement_noise: 1.,
+                })),
+                ..AssetConfigPatch::default()
+            },
+        );
+        contract.record_price(btc(), price(110.), None, None);
+        assert_eq!(contract.get_smoothed_price(btc()).unwrap().price, 110.);
+        // Two thirds of the way to the record.
+        contract.record_price(btc(), price(113.), None, None);
+        let smoothed = contract.get_smoothed_price(btc()).unwrap();
+        assert!((smoothed.price - 112.).abs() < 1e-9);
+        let patch: AssetConfigPatch =
+            near_sdk::serde_json::from_str(r#"{"kalman": null}"#).unwrap();
+        contract.update_asset_config(btc(), patch);
+        assert_eq!(contract.get_smoothed_price(btc()), None);
+    }
+
+    #[test]
+    fn winsorized_mean() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.update_asset_config(
+            btc(),
+            AssetConfigPatch {
+                depth: Some(10),
+                ..AssetConfigPatch::default()
+            },
+        );
+        for value in [1., 2., 3., 4., 5., 6., 7., 8., 9., 100.] {
+            contract.record_price(btc(), price(value), None, None);
+        }
+        assert_eq!(contract.get_winsorized_mean(btc(), 0), 14.5);
+        // 1 and 100 clamped to 2 and 9.
+        assert_eq!(contract.get_winsorized_mean(btc(), 10), 5.5);
+        assert_eq!(contract.get_winsorized_mean(btc(), 49), 5.5);
+        let invalid = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.get_winsorized_mean(btc(), 50)
+        }));
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "indicators")]
+    fn forecast() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for (step, value) in [100., 110., 120.].into_iter().enumerate() {
+            switch_context(|context| context.block_timestamp = step as u64 * 1_000_000_000);
+            contract.record_price(btc(), price(value), None, None);
+        }
+        // On the line: no error.
+        let forecast = contract.get_forecast(btc(), U64(3_000_000_000), None);
+        assert_eq!(forecast.timestamp, U64(5_000_000_000));
+        assert!((forecast.estimate - 150.).abs() < 1e-9);
+        assert!((forecast.slope - 10.).abs() < 1e-9);
+        assert!(forecast.standard_error.abs() < 1e-9);
+
+        switch_context(|context| context.block_timestamp = 3_000_000_000);
+        contract.record_price(btc(), price(110.), None, None);
+        // The least squares line of 100, 110, 120 and 110 is 104 + 4 * x,
+        // off by 4, 2, 8 and 6, so s^2 = 120 / 2.
+        let forecast = contract.get_forecast(btc(), U64(1_000_000_000), Some(1));
+        assert!((forecast.estimate - 120.).abs() < 1e-9);
+        let standard_error = (60f64 * (1. + 1. / 4. + 2.5f64.powi(2) / 5.)).sqrt();
+        assert!((forecast.standard_error - standard_error).abs() < 1e-9);
+        assert!((forecast.upper - forecast.estimate - standard_error).abs() < 1e-9);
+    }
+
+    #[test]
+    #[cfg(feature = "indicators")]
+    fn macd() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for value in [1., 2., 3.] {
+            contract.record_price(btc(), price(value), None, None);
+        }
+        let incomplete = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.get_macd(btc(), 1, 2, 3)
+        }));
+        assert!(incomplete.is_err());
+        contract.record_price(btc(), price(5.), None, None);
+        // The fast EMAs are the prices, the slow ones are of 2/3 and 1/3:
+        // 1.5, 2.5, 4.167, so the MACD is 0.5, 0.5, 0.833 and the signal is
+        // their average.
+        let macd = contract.get_macd(btc(), 1, 2, 3);
+        assert!((macd.macd - 5. / 6.).abs() < 1e-9);
+        assert!((macd.signal - 11. / 18.).abs() < 1e-9);
+        assert!((macd.histogram - 2. / 9.).abs() < 1e-9);
+    }
+
+    #[test]
+    #[cfg(feature = "indicators")]
+    fn standard_macd() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for value in 1..=34 {
+            contract.record_price(btc(), price(f64::from(value)), None, None);
+        }
+        // The EMAs of a linear trend lag by half of their periods less one:
+        // 5.5 and 12.5 records.
+        let macd = contract.get_macd(btc(), 12, 26, 9);
+        assert!((macd.macd - 7.).abs() < 1e-9);
+        assert!((macd.signal - 7.).abs() < 1e-9);
+        assert!(macd.histogram.abs() < 1e-9);
+    }
+
+    #[test]
+    #[cfg(feature = "indicators")]
+    fn period_return() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for (step, value) in [100., 200., 300.].into_iter().enumerate() {
+            switch_context(|context| {
+                context.block_timestamp = (2 * step as u64 + 1) * 1_000_000_000
+            });
+            contract.record_price(btc(), price(value), None, None);
+        }
+        // From the interpolated 150 to the latest 300.
+        let period = contract.get_return(btc(), U64(3_000_000_000));
+        assert_eq!(period.start.price, price(150.));
+        assert!(!period.start.nearest);
+        assert_eq!(period.end.price, price(300.));
+        assert!((period.simple - 1.).abs() < 1e-12);
+        assert!((period.log - 2f64.ln()).abs() < 1e-12);
+        // Outside of the recorded moments: of the nearest records.
+        switch_context(|context| context.block_timestamp = 7_000_000_000);
+        let period = contract.get_return(btc(), U64(10_000_000_000));
+        assert!(period.start.nearest && period.end.nearest);
+        assert!((period.simple - 2.).abs() < 1e-12);
+    }
+
+    #[test]
+    #[cfg(feature = "indicators")]
+    fn beta() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.register_asset("ETH".to_string(), 8, metadata("Ether", "ETH"), None);
+        let eth = || "ETH".to_string();
+        assert_eq!(contract.get_beta(eth(), btc()), None);
+        // The reference is recorded twice as often: the prices of the asset
+        // are interpolated in between.
+        for (step, (btc_price, eth_price)) in [
+            (100., Some(100.)),
+            (200., None),
+            (100., Some(100.)),
+            (400., None),
+            (200., Some(400.)),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            switch_context(|context| context.block_timestamp = step as u64 * 1_000_000_000);
+            contract.record_price(btc(), price(btc_price), None, None);
+            if let Some(eth_price) = eth_price {
+                contract.record_price(eth(), price(eth_price), None, None);
+            }
+        }
+        let beta = contract.get_beta(eth(), btc()).unwrap();
+        let ln = |ratio: f64| ratio.ln();
+        let returns = [ln(1.), ln(1.), ln(2.5), ln(1.6)];
+        let reference = [ln(2.), ln(0.5), ln(4.), ln(0.5)];
+        let mean = returns.iter().sum::<f64>() / 4.;
+        let reference_mean = reference.iter().sum::<f64>() / 4.;
+        let covariance: f64 = returns
+            .iter()
+            .zip(&reference)
+            .map(|(value, reference)| (value - mean) * (reference - reference_mean))
+            .sum();
+        let variance: f64 = reference
+            .iter()
+            .map(|reference| (reference - reference_mean).powi(2))
+            .sum();
+        assert!((beta - covariance / variance).abs() < 1e-9);
+        assert!((contract.get_beta(btc(), btc()).unwrap() - 1.).abs() < 1e-12);
+    }
+
+    #[test]
+    #[cfg(feature = "indicators")]
+    fn log_returns() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        contract.update_asset_config(
+            btc(),
+            AssetConfigPatch {
+                log_returns: Some(true),
+                ..AssetConfigPatch::default()
+            },
+        );
+        let year = 31_557_600_000_000_000;
+        for (step, value) in [100., 200., 100.].into_iter().enumerate() {
+            switch_context(|context| context.block_timestamp = step as u64 * year);
+            contract.record_price(btc(), price(value), None, None);
+        }
+        let stored = contract.get_log_returns(btc()).unwrap();
+        assert_eq!(stored.len(), 2);
+        assert!((stored[0].value - 2f64.ln()).abs() < 1e-12);
+        assert!((stored[1].value + 2f64.ln()).abs() < 1e-12);
+        assert_eq!(stored[1].elapsed, U64(year));
+        let volatility = contract.get_volatility_annualized(btc()).unwrap();
+        // The same as calculated of the prices.
+        contract.update_asset_config(
+            btc(),
+            AssetConfigPatch {
+                log_returns: Some(false),
+                ..AssetConfigPatch::default()
+            },
+        );
+        switch_context(|context| context.block_timestamp = 3 * year);
+        contract.record_price(btc(), price(100.), None, None);
+        contract.rewind(btc(), 1);
+        assert_eq!(contract.get_log_returns(btc()).unwrap(), stored);
+        assert!((contract.get_volatility_annualized(btc()).unwrap() - volatility).abs() < 1e-12);
+    }
+
+    #[test]
+    fn history_order() {
+        testing_env!(get_context(false));
+        let mut contract = with_btc();
+        for value in 1..=3 {
+            contract.record_price(btc(), price(f64::from(value)), None, None);
+        }
+        let prices = |order| {
+            contract
+                .get_history(btc(), order)
+                .iter()
+                .map(|entry| entry.price.get())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(prices(None), [1., 2., 3.]);
+        assert_eq!(prices(Some(Order::NewestFirst)), [3., 2., 1.]);
+        let order: Order = near_sdk::serde_json::from_str("\"newest_first\"").unwrap();
+        assert_eq!(order, Order::NewestFirst);
     }
 }
@@ -0,0 +1,58 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+
+/// Verbosity of the contract logs.
+///
+/// Every log costs gas, so an operator might want to tune it down.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Default,
+    BorshDeserialize,
+    BorshSerialize,
+    Serialize,
+    Deserialize,
+)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum LogLevel {
+    /// Nothing is logged.
+    Off,
+    /// Only errors are logged.
+    Error,
+    /// Events and other notable changes are logged.
+    #[default]
+    Info,
+    /// Everything is logged.
+    Debug,
+}
+
+impl LogLevel {
+    /// Checks whether a message of the given `level` should be logged.
+    pub fn allows(self, level: LogLevel) -> bool {
+        level != LogLevel::Off && level <= self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn allows() {
+        assert!(!LogLevel::Off.allows(LogLevel::Off));
+        assert!(!LogLevel::Off.allows(LogLevel::Error));
+        assert!(LogLevel::Error.allows(LogLevel::Error));
+        assert!(!LogLevel::Error.allows(LogLevel::Info));
+        assert!(LogLevel::Info.allows(LogLevel::Info));
+        assert!(!LogLevel::Info.allows(LogLevel::Debug));
+        assert!(LogLevel::Debug.allows(LogLevel::Error));
+        assert!(LogLevel::Debug.allows(LogLevel::Debug));
+    }
+}
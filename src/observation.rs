@@ -0,0 +1,203 @@
+//! A ring buffer of the cumulative price observations in the fashion of
+//! Uniswap V3, allowing to calculate time-weighted average prices over
+//! flexible periods.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::cumulative::Accumulator;
+use crate::fixed::{Rounding, WideFixed};
+
+/// Amount of observations kept per asset by default.
+pub const DEFAULT_CARDINALITY: u16 = 8;
+
+/// Maximum amount of observations kept per asset: all of them are loaded on
+/// every record.
+#[cfg(feature = "indicators")]
+pub const MAX_CARDINALITY: u16 = 1024;
+
+/// Storage occupied by a single observation.
+#[cfg(feature = "indicators")]
+pub const OBSERVATION_BYTES: u64 = 40;
+
+/// The cumulative price as of a timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Observation {
+    /// Timestamp, in nanoseconds.
+    pub timestamp: u64,
+    pub price_cumulative: WideFixed,
+}
+
+/// The observations ring buffer.
+#[derive(Debug, Clone, PartialEq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Observations {
+    observations: Vec<Observation>,
+    /// Position of the newest observation.
+    index: u16,
+    /// Maximum amount of observations.
+    cardinality: u16,
+}
+
+impl Default for Observations {
+    fn default() -> Self {
+        Self {
+            observations: Vec::new(),
+            index: 0,
+            cardinality: DEFAULT_CARDINALITY,
+        }
+    }
+}
+
+impl Observations {
+    /// Writes an observation, overwriting the oldest one when the buffer is
+    /// full. Only one observation per timestamp is kept.
+    pub fn write(&mut self, observation: Observation) {
+        let len = self.observations.len();
+        if len == 0 {
+            self.observations.push(observation);
+            self.index = 0;
+            return;
+        }
+        let index = usize::from(self.index);
+        if self.observations[index].timestamp == observation.timestamp {
+            return;
+        }
+        // Hint: the buffer only grows while the newest observation is the
+        // last one, so the order of the ring is kept.
+        if index == len - 1 && len < usize::from(self.cardinality) {
+            self.observations.push(observation);
+            self.index += 1;
+        } else {
+            let index = (index + 1) % len;
+            self.observations[index] = observation;
+            self.index = index as u16;
+        }
+    }
+
+    /// Returns the maximum amount of observations.
+    pub fn cardinality(&self) -> u16 {
+        self.cardinality
+    }
+
+    /// Raises the maximum amount of observations. The buffer grows once the
+    /// newest observation reaches its end.
+    pub fn grow(&mut self, cardinality: u16) {
+        self.cardinality = self.cardinality.max(cardinality);
+    }
+
+    /// Returns the cumulative price as of the `target` timestamp, or `None`
+    /// when the target predates the oldest observation. Targets past the
+    /// newest observation are extrapolated with the `accumulator`.
+    pub fn observe(&self, target: u64, accumulator: &Accumulator) -> Option<WideFixed> {
+        let newest = self.get(self.observations.len().checked_sub(1)?);
+        if target >= newest.timestamp {
+            return Some(accumulator.cumulative_at(target));
+        }
+        if target < self.get(0).timestamp {
+            return None;
+        }
+        // Binary search for the first observation past the target; it's
+        // neither the first nor beyond the newest one, as checked above.
+        let (mut low, mut high) = (0, self.observations.len() - 1);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if self.get(middle).timestamp <= target {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        let before = self.get(low - 1);
+        let after = self.get(low);
+        // Hint: the price is constant between the observations, so the
+        // linear interpolation is exact.
+        let delta = after
+            .price_cumulative
+            .saturating_sub(before.price_cumulative)
+            .mul_div(
+                target - before.timestamp,
+                after.timestamp - before.timestamp,
+                Rounding::Down,
+            )?;
+        Some(before.price_cumulative.saturating_add(delta))
+    }
+
+    /// Returns an observation by its chronological position.
+    fn get(&self, position: usize) -> &Observation {
+        let len = self.observations.len();
+        &self.observations[(usize::from(self.index) + 1 + position) % len]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixed::Fixed;
+    use crate::price::Price;
+
+    #[test]
+    fn observe() {
+        let mut accumulator = Accumulator::default();
+        let mut observations = Observations::default();
+        assert_eq!(observations.observe(0, &accumulator), None);
+
+        // Prices 1, 2, 3, ... recorded every 10 nanoseconds.
+        for step in 0..20u64 {
+            let timestamp = step * 10;
+            accumulator.record(Price::new((step + 1) as f64).unwrap(), timestamp);
+            observations.write(Observation {
+                timestamp,
+                price_cumulative: accumulator.cumulative_at(timestamp),
+            });
+        }
+
+        // Only the latest 8 observations (since 120) are kept.
+        assert_eq!(observations.observe(119, &accumulator), None);
+        let observe = |target| {
+            observations
+                .observe(target, &accumulator)
+                .map(WideFixed::to_f64)
+        };
+        assert_eq!(observations.observations.len(), 8);
+        let cumulative = |timestamp: u64| {
+            let complete = timestamp / 10;
+            let full: u64 = (1..=complete).map(|price| price * 10).sum();
+            (full + (complete + 1) * (timestamp % 10)) as f64
+        };
+        for target in [120, 125, 130, 150, 155, 189, 190, 195] {
+            assert_eq!(observe(target), Some(cumulative(target)), "{}", target);
+        }
+        // Extrapolated with the latest price.
+        assert_eq!(observe(250), Some(cumulative(190) + 20. * 60.));
+    }
+
+    #[test]
+    fn grow() {
+        let observation = |timestamp| Observation {
+            timestamp,
+            price_cumulative: WideFixed::product(Fixed::ONE, timestamp),
+        };
+        let mut observations = Observations::default();
+        observations.grow(1);
+        assert_eq!(observations.cardinality(), DEFAULT_CARDINALITY);
+        for timestamp in 0..10 {
+            observations.write(observation(timestamp));
+        }
+        // The newest observation is in the middle, so the buffer keeps the
+        // size until the ring comes to its end.
+        observations.grow(10);
+        for timestamp in 10..16 {
+            observations.write(observation(timestamp));
+            assert_eq!(observations.observations.len(), 8);
+        }
+        for timestamp in 16..20 {
+            observations.write(observation(timestamp));
+        }
+        assert_eq!(observations.observations.len(), 10);
+        assert_eq!(observations.get(0).timestamp, 10);
+        assert_eq!(observations.get(9).timestamp, 19);
+    }
+}
@@ -0,0 +1,129 @@
+//! Validated prices.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::fixed::Fixed;
+
+/// Maximum supported price: along with [`crate::asset::MAX_DECIMALS`] keeps
+/// the fixed-point representation of any price within `u128`.
+pub const MAX_PRICE: f64 = 1e14;
+
+/// A price of an asset: a finite number within `0..=1e14`, not subnormal.
+///
+/// Invalid values are rejected when a price is constructed, including the
+/// JSON deserialization, so the rest of the contract never checks them. The
+/// price is kept as a [`Fixed`], the float is only used in JSON.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    BorshDeserialize,
+    BorshSerialize,
+    Serialize,
+    Deserialize,
+)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde", try_from = "f64", into = "f64")]
+pub struct Price(Fixed);
+
+impl Price {
+    /// Validates the `value`.
+    pub fn new(value: f64) -> Result<Self, &'static str> {
+        if !value.is_finite() {
+            Err("The price must be finite")
+        } else if !(0. ..=MAX_PRICE).contains(&value) {
+            Err("The price must be within 0..=1e14")
+        } else if value.is_subnormal() {
+            Err("The price must not be subnormal")
+        } else {
+            Fixed::from_f64(value)
+                .map(Self)
+                .ok_or("The price must be within 0..=1e14")
+        }
+    }
+
+    /// Validates a fixed-point `value`.
+    pub fn from_fixed(value: Fixed) -> Result<Self, &'static str> {
+        match Fixed::from_int(MAX_PRICE as u128) {
+            Some(max) if value <= max => Ok(Self(value)),
+            _ => Err("The price must be within 0..=1e14"),
+        }
+    }
+
+    /// Returns the price as a float, for the views.
+    pub fn get(self) -> f64 {
+        self.0.to_f64()
+    }
+
+    /// Returns the price as a fixed-point number.
+    pub fn fixed(self) -> Fixed {
+        self.0
+    }
+}
+
+impl TryFrom<f64> for Price {
+    type Error = &'static str;
+
+    fn try_from(value: f64) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl From<Price> for f64 {
+    fn from(price: Price) -> Self {
+        price.get()
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.get().fmt(f)
+    }
+}
+
+/// Any valid price, shrinking towards zero, for the property tests.
+#[cfg(test)]
+impl quickcheck::Arbitrary for Price {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        // Hint: the prices of few digits are as likely as the large ones, and
+        // the raw representation of any price is below `10^32`.
+        let digits = u32::from(u8::arbitrary(g)) % 33;
+        Self(Fixed::from_raw(u128::arbitrary(g) % 10u128.pow(digits)))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        Box::new(self.0.raw().shrink().map(|raw| Self(Fixed::from_raw(raw))))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn validation() {
+        assert_eq!(Price::new(0.).map(Price::get), Ok(0.));
+        assert_eq!(Price::new(42_000.5).map(Price::get), Ok(42_000.5));
+        assert_eq!(Price::new(MAX_PRICE).map(Price::get), Ok(MAX_PRICE));
+        assert!(Price::new(-1.).is_err());
+        assert!(Price::new(MAX_PRICE * 2.).is_err());
+        assert!(Price::new(f64::NAN).is_err());
+        assert!(Price::new(f64::INFINITY).is_err());
+        assert!(Price::new(f64::MIN_POSITIVE / 2.).is_err());
+
+        let price: Price = near_sdk::serde_json::from_str("1.5").unwrap();
+        assert_eq!(price.get(), 1.5);
+        assert!(near_sdk::serde_json::from_str::<Price>("-1.5").is_err());
+        assert_eq!(near_sdk::serde_json::to_string(&price).unwrap(), "1.5");
+    }
+}
@@ -0,0 +1,97 @@
+//! The proxy mode: consumers bind to a stable account, while the aggregator
+//! behind it is replaced across major versions.
+//!
+//! Views can't make cross-contract calls, so the forwarding counterparts of
+//! the views are calls resolving to the implementation's answer.
+
+use near_sdk::serde_json::json;
+use near_sdk::{ext_contract, log, near_bindgen, AccountId, FunctionError, Gas, Promise};
+
+use crate::error::ContractError;
+use crate::events;
+use crate::{LogLevel, PriceHistory, PriceHistoryExt};
+
+/// Gas attached to a forwarded view.
+const GAS_FOR_FORWARDING: Gas = Gas::from_tgas(10);
+
+#[allow(dead_code)]
+#[ext_contract(ext_implementation)]
+trait Implementation {
+    fn get_average(&self, symbol: String, quote: Option<String>);
+    fn get_averages(&self, symbols: Vec<String>);
+    fn get_all_latest_prices(&self, from_index: Option<u64>, limit: Option<u64>);
+}
+
+#[near_bindgen]
+impl PriceHistory {
+    /// Returns the aggregator the views are forwarded to, if any.
+    pub fn get_implementation(&self) -> Option<AccountId> {
+        self.implementation.clone()
+    }
+
+    /// Sets the aggregator the views are forwarded to, or disables the
+    /// forwarding.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract.
+    pub fn set_implementation(&mut self, account_id: Option<AccountId>) {
+        self.assert_owner("change the implementation");
+        let previous = std::mem::replace(&mut self.implementation, account_id);
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Implementation changed to {:?}", self.implementation);
+        }
+        events::emit_config_changes(
+            None,
+            &json!({ "implementation": previous }),
+            &json!({ "implementation": self.implementation }),
+        );
+    }
+
+    /// Forwards the [`Self::get_average`] to the implementation.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when no implementation is set.
+    pub fn proxy_get_average(&self, symbol: String, quote: Option<String>) -> Promise {
+        self.forward().get_average(symbol, quote)
+    }
+
+    /// Forwards the [`Self::get_averages`] to the implementation.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when no implementation is set.
+    pub fn proxy_get_averages(&self, symbols: Vec<String>) -> Promise {
+        self.forward().get_averages(symbols)
+    }
+
+    /// Forwards the [`Self::get_all_latest_prices`] to the implementation.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when no implementation is set.
+    pub fn proxy_get_all_latest_prices(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Promise {
+        self.forward().get_all_latest_prices(from_index, limit)
+    }
+}
+
+impl PriceHistory {
+    /// Prepares a call to the implementation.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when no implementation is set.
+    fn forward(&self) -> ext_implementation::ImplementationExt {
+        let implementation = self
+            .implementation
+            .clone()
+            .unwrap_or_else(|| ContractError::NoImplementation.panic());
+        ext_implementation::ext(implementation).with_static_gas(GAS_FOR_FORWARDING)
+    }
+}
@@ -0,0 +1,94 @@
+//! Re-quoting of the prices into other currencies.
+//!
+//! Every asset is quoted in the currency of its metadata. The owner configures
+//! a registered asset as the conversion feed from one quote currency to
+//! another, either of the `from / to` pair or of the inverse `to / from` one,
+//! so the same asset is served in several denominations.
+
+use near_sdk::serde_json::json;
+use near_sdk::{log, near_bindgen, FunctionError};
+
+use crate::error::{ensure, ContractError};
+use crate::events;
+use crate::fixed::{Fixed, Rounding};
+use crate::{LogLevel, PriceHistory, PriceHistoryExt};
+
+/// Identifies the conversion from the `from` quote currency to the `to` one.
+fn conversion_key(from: &str, to: &str) -> String {
+    format!("{}/{}", from, to)
+}
+
+#[near_bindgen]
+impl PriceHistory {
+    /// Returns the asset converting the prices quoted in the `from` currency
+    /// into the `to` one, if any.
+    pub fn get_conversion_feed(&self, from: String, to: String) -> Option<String> {
+        self.conversions.get(&conversion_key(&from, &to))
+    }
+
+    /// Sets the asset converting the prices quoted in the `from` currency into
+    /// the `to` one, a pair of either `from / to` or `to / from`, or removes
+    /// the conversion.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, when the asset is not registered, or when it's not a pair
+    /// of the currencies.
+    pub fn set_conversion_feed(&mut self, from: String, to: String, symbol: Option<String>) {
+        self.assert_owner("configure the conversions");
+        let key = conversion_key(&from, &to);
+        let previous = match &symbol {
+            Some(symbol) => {
+                let metadata = self.asset(symbol).metadata();
+                ensure(
+                    (metadata.base == from && metadata.quote == to)
+                        || (metadata.base == to && metadata.quote == from),
+                    ContractError::OutOfBounds("The feed must be a pair of the currencies"),
+                );
+                self.conversions.insert(&key, symbol)
+            }
+            None => self.conversions.remove(&key),
+        };
+        events::emit_config_changes(
+            None,
+            &json!({ "conversions": { key.as_str(): previous } }),
+            &json!({ "conversions": { key.as_str(): symbol } }),
+        );
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Conversion {} changed to {:?}", key, symbol);
+        }
+    }
+}
+
+impl PriceHistory {
+    /// Converts the `price` quoted in the `from` currency into the `to` one
+    /// with the configured conversion feed.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when no conversion is configured, when not enough
+    /// historical data has been collected for the feed, when its price is
+    /// stale, or when the price overflows.
+    pub(crate) fn requote(&self, price: Fixed, from: &str, to: &str) -> Fixed {
+        if from == to {
+            return price;
+        }
+        let symbol = self
+            .conversions
+            .get(&conversion_key(from, to))
+            .unwrap_or_else(|| {
+                ContractError::OutOfBounds("No conversion between the currencies").panic()
+            });
+        let feed = self.asset(&symbol);
+        let rate = Self::require_average(&feed);
+        if feed.metadata().base == from {
+            price.checked_mul(rate, Rounding::Nearest)
+        } else {
+            price.checked_div(rate, Rounding::Nearest)
+        }
+        .unwrap_or_else(|| {
+            ContractError::OutOfBounds("The price can't be represented in the currency").panic()
+        })
+    }
+}
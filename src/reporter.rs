@@ -0,0 +1,74 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::AccountId;
+
+/// Assets a reporter is allowed to record prices of.
+#[derive(Debug, Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum ReporterScope {
+    /// Any registered asset.
+    All,
+    /// Only the listed assets.
+    Assets(Vec<String>),
+}
+
+/// A reporter, as returned by the views.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReporterInfo {
+    pub account_id: AccountId,
+    pub scope: ReporterScope,
+}
+
+impl ReporterScope {
+    /// Checks whether the scope covers the asset.
+    pub fn covers(&self, symbol: &str) -> bool {
+        match self {
+            ReporterScope::All => true,
+            ReporterScope::Assets(symbols) => symbols.iter().any(|allowed| allowed == symbol),
+        }
+    }
+
+    /// Replaces the `old_symbol` with the `new_symbol`, returning whether the
+    /// scope has changed.
+    pub fn rename(&mut self, old_symbol: &str, new_symbol: &str) -> bool {
+        let mut changed = false;
+        if let ReporterScope::Assets(symbols) = self {
+            for symbol in symbols.iter_mut().filter(|symbol| *symbol == old_symbol) {
+                *symbol = new_symbol.to_string();
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// Returns the indices of the `size` reporters, out of `len` ones, on duty in
+/// the `epoch`: the window moves by its size every epoch, so the reporters
+/// take turns.
+pub fn on_duty(len: u64, size: u16, epoch: u64) -> impl Iterator<Item = u64> {
+    let size = u64::from(size).min(len);
+    let offset = if len == 0 {
+        0
+    } else {
+        // Hint: the product of two `u64` always fits.
+        (u128::from(epoch) * u128::from(size) % u128::from(len)) as u64
+    };
+    (0..size).map(move |index| (offset + index) % len)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rotation() {
+        assert_eq!(on_duty(5, 2, 0).collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(on_duty(5, 2, 1).collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(on_duty(5, 2, 2).collect::<Vec<_>>(), [4, 0]);
+        assert_eq!(on_duty(2, 3, 7).collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(on_duty(0, 3, 7).count(), 0);
+    }
+}
@@ -0,0 +1,165 @@
+//! Rewards of the reporters for their submissions.
+//!
+//! Every submission of a recorded round earns a fixed reward, along with a
+//! bonus when it's close enough to the median, out of the reward pool in the
+//! payment token (see the [`crate::token`]).
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{log, near_bindgen, AccountId};
+
+use crate::asset::within_bps;
+use crate::events;
+use crate::round::{Provenance, Submission};
+use crate::{Balance, LogLevel, PriceHistory, PriceHistoryExt};
+
+/// Rewards per submission, in the payment token units.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct RewardConfig {
+    /// Earned by every submission of a recorded round.
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<u128>",
+        definitions = "crate::schema::definitions::<u128>",
+    ))))]
+    pub per_submission: U128,
+    /// Earned on top by a submission within the `bonus_tolerance_bps` of the
+    /// median.
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<u128>",
+        definitions = "crate::schema::definitions::<u128>",
+    ))))]
+    pub median_bonus: U128,
+    /// Distance from the median, in basis points of it, earning the bonus.
+    pub bonus_tolerance_bps: u32,
+    /// Earned by the caller of a [`crate::PriceHistory::poke`] which has
+    /// done any housekeeping.
+    #[serde(default)]
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<u128>",
+        definitions = "crate::schema::definitions::<u128>",
+    ))))]
+    pub keeper_reward: U128,
+}
+
+/// Rewards of a reporter.
+#[derive(Debug, Default, Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReporterRewards {
+    /// Accrued and not claimed yet, in the payment token units.
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<u128>",
+        definitions = "crate::schema::definitions::<u128>",
+    ))))]
+    pub unclaimed: U128,
+    /// Everything accrued over the lifetime, in the payment token units.
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<u128>",
+        definitions = "crate::schema::definitions::<u128>",
+    ))))]
+    pub accrued: U128,
+    /// Amount of the rewarded submissions.
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<u64>",
+        definitions = "crate::schema::definitions::<u64>",
+    ))))]
+    pub submissions: U64,
+    /// Amount of the submissions which have earned the bonus.
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<u64>",
+        definitions = "crate::schema::definitions::<u64>",
+    ))))]
+    pub bonuses: U64,
+}
+
+#[near_bindgen]
+impl PriceHistory {
+    /// Returns the rewards per submission.
+    pub fn get_reward_config(&self) -> RewardConfig {
+        self.reward_config
+    }
+
+    /// Sets the rewards per submission; zero ones disable the rewards.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract.
+    pub fn set_reward_config(&mut self, config: RewardConfig) {
+        self.assert_owner("configure the rewards");
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Rewards changed to {:?}", config);
+        }
+        events::emit_config_changes(None, &self.reward_config, &config);
+        self.reward_config = config;
+    }
+
+    /// Returns the rewards of the reporter.
+    pub fn get_reporter_rewards(&self, account_id: AccountId) -> ReporterRewards {
+        self.token_rewards.get(&account_id).unwrap_or_default()
+    }
+}
+
+impl PriceHistory {
+    /// Rewards the keeper out of the reward pool, as long as it lasts, and
+    /// returns the reward.
+    pub(crate) fn reward_keeper(&mut self, account_id: &AccountId) -> Balance {
+        let reward = self
+            .reward_config
+            .keeper_reward
+            .0
+            .min(self.token_reward_pool);
+        if reward == 0 {
+            return 0;
+        }
+        self.token_reward_pool -= reward;
+        let mut rewards = self.token_rewards.get(account_id).unwrap_or_default();
+        rewards.unclaimed.0 += reward;
+        rewards.accrued.0 += reward;
+        self.token_rewards.insert(account_id, &rewards);
+        reward
+    }
+
+    /// Rewards the `submissions` of a recorded round with the given `median`
+    /// out of the reward pool, as long as it lasts.
+    pub(crate) fn reward_round(&mut self, submissions: &[Submission], median: &Submission) {
+        let config = self.reward_config;
+        if config.per_submission.0 == 0 && config.median_bonus.0 == 0 {
+            return;
+        }
+        // Hint: the relayer of an attestation hasn't observed the price, and
+        // may relay it at will.
+        for submission in submissions
+            .iter()
+            .filter(|submission| submission.provenance != Provenance::Bridged)
+        {
+            let bonus = within_bps(
+                submission.price.fixed(),
+                median.price.fixed(),
+                config.bonus_tolerance_bps,
+            );
+            let reward: Balance = config
+                .per_submission
+                .0
+                .saturating_add(if bonus { config.median_bonus.0 } else { 0 })
+                .min(self.token_reward_pool);
+            self.token_reward_pool -= reward;
+            let mut rewards = self
+                .token_rewards
+                .get(&submission.reporter)
+                .unwrap_or_default();
+            rewards.unclaimed.0 += reward;
+            rewards.accrued.0 += reward;
+            rewards.submissions.0 += 1;
+            rewards.bonuses.0 += u64::from(bonus);
+            self.token_rewards.insert(&submission.reporter, &rewards);
+        }
+    }
+}
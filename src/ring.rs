@@ -0,0 +1,345 @@
+//! A ring buffer kept in the contract storage.
+//!
+//! Unlike an inline array, only the accessed slots are ever read or written,
+//! so the buffer may hold thousands of items without loading all of them on
+//! every call.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::collections::Vector;
+#[cfg(feature = "serde")]
+use near_sdk::serde::ser::{Serialize, SerializeSeq, Serializer};
+use near_sdk::IntoStorageKey;
+
+/// A ring buffer of up to `capacity` items, overwriting the oldest one when
+/// full.
+#[derive(BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Ring<T> {
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<crate::schema::Vector>",
+            definitions = "crate::schema::definitions::<crate::schema::Vector>",
+        )))
+    )]
+    slots: Vector<T>,
+    /// Position of the newest item.
+    head: u64,
+    /// Maximum amount of items.
+    capacity: u64,
+}
+
+impl<T> Ring<T>
+where
+    T: BorshSerialize + BorshDeserialize,
+{
+    /// Creates an empty buffer, the slots of which are stored under the
+    /// `prefix`.
+    pub fn new<S: IntoStorageKey>(prefix: S, capacity: u64) -> Self {
+        if capacity == 0 {
+            near_sdk::env::panic_str("Zero-length ring buffers are not supported");
+        }
+        Self {
+            slots: Vector::new(prefix),
+            head: 0,
+            capacity,
+        }
+    }
+
+    /// Raises the maximum amount of items. The buffer grows lazily, once the
+    /// newest item is in the last slot, so the order of the ring is kept.
+    pub fn reserve(&mut self, capacity: u64) {
+        self.capacity = self.capacity.max(capacity);
+    }
+
+    /// Returns the amount of the occupied slots.
+    pub fn len(&self) -> u64 {
+        self.slots.len()
+    }
+
+    /// Checks whether no slot is occupied.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Adds an item, overwriting the oldest one when the buffer is full.
+    pub fn push(&mut self, item: &T) {
+        let len = self.slots.len();
+        if len == 0 || (self.head == len - 1 && len < self.capacity) {
+            self.slots.push(item);
+            self.head = len;
+        } else {
+            self.head = (self.head + 1) % len;
+            self.slots.replace(self.head, item);
+        }
+    }
+
+    /// Replaces the newest item, if any.
+    pub fn replace_newest(&mut self, item: &T) {
+        if !self.slots.is_empty() {
+            self.slots.replace(self.head, item);
+        }
+    }
+
+    /// Moves the newest item back by `count` items, so the next ones
+    /// overwrite the dropped ones. The dropped slots are left as they are,
+    /// so it's up to the caller not to read them.
+    pub fn rewind(&mut self, count: u64) {
+        let len = self.slots.len();
+        if len != 0 {
+            self.head = (self.head + len - count % len) % len;
+        }
+    }
+
+    /// Returns the item pushed `age` items before the newest one, reading a
+    /// single slot.
+    pub fn get(&self, age: u64) -> Option<T> {
+        let len = self.slots.len();
+        if age >= len {
+            return None;
+        }
+        // Hint: `head < len`, so the sum never overflows.
+        self.slots.get((self.head + len - age) % len)
+    }
+
+    /// Returns up to `count` newest items, always from the oldest to the
+    /// newest, i.e. in the order they have been pushed in. The slots are read
+    /// as the iterator advances.
+    pub fn latest(&self, count: u64) -> impl Iterator<Item = T> + '_ {
+        (0..count.min(self.len()))
+            .rev()
+            .filter_map(move |age| self.get(age))
+    }
+
+    /// Returns up to `count` newest items, from the newest to the oldest,
+    /// the reverse of the [`Self::latest`].
+    pub fn latest_rev(&self, count: u64) -> impl Iterator<Item = T> + '_ {
+        (0..count.min(self.len())).filter_map(move |age| self.get(age))
+    }
+
+    /// Removes every item from the storage.
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.head = 0;
+    }
+
+    /// Turns the buffer into up to `count` newest items, so the rewound slots
+    /// are skipped.
+    #[cfg(feature = "serde")]
+    pub fn into_latest(self, count: u64) -> Latest<T> {
+        Latest {
+            count: count.min(self.len()),
+            ring: self,
+        }
+    }
+}
+
+/// Serialized as every occupied slot, from the oldest to the newest, read as
+/// the serialization advances.
+#[cfg(feature = "serde")]
+impl<T> Serialize for Ring<T>
+where
+    T: BorshSerialize + BorshDeserialize + Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_latest(self, self.len(), serializer)
+    }
+}
+
+/// Up to `count` newest items of a [`Ring`], so a view returns them without
+/// collecting them first.
+#[cfg(feature = "serde")]
+pub struct Latest<T> {
+    ring: Ring<T>,
+    count: u64,
+}
+
+#[cfg(feature = "serde")]
+impl<T> Latest<T>
+where
+    T: BorshSerialize + BorshDeserialize,
+{
+    /// Returns the amount of the items.
+    pub fn len(&self) -> u64 {
+        self.count
+    }
+
+    /// Checks whether there are no items.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the items, from the oldest to the newest.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.ring.latest(self.count)
+    }
+}
+
+/// Serialized from the oldest to the newest item, read as the serialization
+/// advances.
+#[cfg(feature = "serde")]
+impl<T> Serialize for Latest<T>
+where
+    T: BorshSerialize + BorshDeserialize + Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_latest(&self.ring, self.count, serializer)
+    }
+}
+
+/// Serializes up to `count` newest items of the `ring` as a sequence.
+#[cfg(feature = "serde")]
+fn serialize_latest<T, S>(ring: &Ring<T>, count: u64, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: BorshSerialize + BorshDeserialize + Serialize,
+    S: Serializer,
+{
+    let count = count.min(ring.len());
+    let mut seq = serializer.serialize_seq(Some(count as usize))?;
+    for item in ring.latest(count) {
+        seq.serialize_element(&item)?;
+    }
+    seq.end()
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+    use quickcheck::TestResult;
+    use quickcheck_macros::quickcheck;
+
+    fn items(ring: &Ring<u8>) -> Vec<u8> {
+        ring.latest(u64::MAX).collect()
+    }
+
+    #[test]
+    fn simple_test() {
+        let mut ring = Ring::new(b"r".to_vec(), 3);
+        assert!(items(&ring).is_empty());
+        assert_eq!(ring.get(0), None);
+
+        ring.push(&1);
+        ring.push(&2);
+        assert_eq!(items(&ring), vec![1, 2]);
+        assert_eq!(ring.get(0), Some(2));
+
+        ring.push(&3);
+        ring.push(&4);
+        assert_eq!(items(&ring), vec![2, 3, 4]);
+        assert_eq!(ring.latest(2).collect::<Vec<_>>(), vec![3, 4]);
+        assert_eq!(ring.latest_rev(2).collect::<Vec<_>>(), vec![4, 3]);
+        assert_eq!(ring.get(2), Some(2));
+        assert_eq!(ring.get(3), None);
+
+        ring.clear();
+        assert_eq!(ring.len(), 0);
+    }
+
+    #[test]
+    fn reserve() {
+        let mut ring = Ring::new(b"r".to_vec(), 3);
+        for item in 1..=4 {
+            ring.push(&item);
+        }
+        ring.reserve(5);
+        // Doesn't grow until the newest item is in the last slot.
+        ring.push(&5);
+        assert_eq!(items(&ring), vec![3, 4, 5]);
+        ring.push(&6);
+        ring.push(&7);
+        assert_eq!(items(&ring), vec![4, 5, 6, 7]);
+        ring.push(&8);
+        ring.push(&9);
+        assert_eq!(items(&ring), vec![5, 6, 7, 8, 9]);
+        assert_eq!(ring.len(), 5);
+    }
+
+    #[test]
+    fn rewind() {
+        let mut ring = Ring::new(b"r".to_vec(), 3);
+        for item in 1..=4 {
+            ring.push(&item);
+        }
+        ring.rewind(2);
+        assert_eq!(ring.get(0), Some(2));
+        ring.push(&5);
+        ring.push(&6);
+        assert_eq!(items(&ring), vec![2, 5, 6]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize() {
+        let mut ring = Ring::new(b"r".to_vec(), 3);
+        for item in 1..=4u8 {
+            ring.push(&item);
+        }
+        assert_eq!(near_sdk::serde_json::to_string(&ring).unwrap(), "[2,3,4]");
+        ring.rewind(1);
+        let latest = ring.into_latest(2);
+        assert_eq!(latest.len(), 2);
+        assert_eq!(near_sdk::serde_json::to_string(&latest).unwrap(), "[2,3]");
+    }
+
+    #[quickcheck]
+    fn extensive_test(input: Vec<u8>) -> TestResult {
+        if input.len() < 5 {
+            return TestResult::discard();
+        }
+
+        // Hint: a fresh context for every case, so the gas isn't exhausted.
+        testing_env!(VMContextBuilder::new().build());
+        let mut reference_queue = VecDeque::with_capacity(5);
+        let mut testing_queue = Ring::new(b"r".to_vec(), 5);
+        for item in input {
+            if reference_queue.len() >= 5 {
+                reference_queue.pop_front();
+            }
+            reference_queue.push_back(item);
+            testing_queue.push(&item);
+        }
+
+        let reference: Vec<_> = reference_queue.into_iter().collect();
+        assert_eq!(reference, items(&testing_queue));
+
+        TestResult::passed()
+    }
+
+    #[quickcheck]
+    fn any_capacity(capacity: u8, input: Vec<u8>, rewound: u8) -> bool {
+        // Hint: a fresh context for every case, so the gas isn't exhausted.
+        testing_env!(VMContextBuilder::new().build());
+        let capacity = usize::from(capacity % 16 + 1);
+        let mut reference = VecDeque::with_capacity(capacity);
+        let mut ring = Ring::new(b"r".to_vec(), capacity as u64);
+        // Partially filled, wrapped around, or both.
+        let filled = input.iter().all(|&item| {
+            if reference.len() == capacity {
+                reference.pop_front();
+            }
+            reference.push_back(item);
+            ring.push(&item);
+            ring.len() == reference.len() as u64
+                && ring.latest(u64::MAX).eq(reference.iter().copied())
+                && ring
+                    .latest_rev(u64::MAX)
+                    .eq(reference.iter().rev().copied())
+        });
+
+        // Only the items kept by the rewind are read.
+        let rewound = usize::from(rewound).min(reference.len());
+        ring.rewind(rewound as u64);
+        reference.truncate(reference.len() - rewound);
+        filled
+            && reference
+                .iter()
+                .rev()
+                .enumerate()
+                .all(|(age, &item)| ring.get(age as u64) == Some(item))
+    }
+}
@@ -0,0 +1,305 @@
+//! Rounds of submissions aggregated into a single record.
+//!
+//! With a quorum configured, the reporters submit their prices into the
+//! pending round of an asset, which is recorded as the median submission once
+//! enough of them have been collected. The default quorum of a single
+//! submission records every price at once.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::AccountId;
+
+use crate::error::ContractError;
+use crate::fixed::{self, Fixed, Rounding};
+use crate::price::Price;
+
+/// How many submissions make a round.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, BorshDeserialize, BorshSerialize, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Quorum {
+    /// Submissions required to record a round.
+    pub min_submissions: u16,
+    /// Submissions after which a round is recorded at once.
+    pub max_submissions: u16,
+    /// Nanoseconds the submissions of a round are collected for, since the
+    /// first one, before it's recorded with at least the minimum of them or
+    /// discarded. Zero records a round as soon as the minimum is collected.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<u64>",
+            definitions = "crate::schema::definitions::<u64>",
+        )))
+    )]
+    pub submission_window: U64,
+}
+
+impl Default for Quorum {
+    fn default() -> Self {
+        Self {
+            min_submissions: 1,
+            max_submissions: 1,
+            submission_window: U64(0),
+        }
+    }
+}
+
+impl Quorum {
+    /// Checks whether the quorum is valid with the given amount of accounts
+    /// allowed to report.
+    pub fn validate(&self, reporters: u64) -> Result<(), &'static str> {
+        if self.min_submissions == 0 || self.min_submissions > self.max_submissions {
+            Err("The submissions must be within 1..=min_submissions..=max_submissions")
+        } else if u64::from(self.min_submissions) > reporters {
+            Err("The quorum must not exceed the amount of the reporters")
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Where a submitted price comes from.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, BorshDeserialize, BorshSerialize, Serialize,
+)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum Provenance {
+    /// Reported by the submitter.
+    #[default]
+    Reporter,
+    /// Attested by the signers of a foreign chain and relayed by the
+    /// submitter, see the [`crate::bridge`].
+    Bridged,
+}
+
+/// A price submitted by a reporter.
+#[derive(Debug, Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Submission {
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<String>",
+            definitions = "crate::schema::definitions::<String>",
+        )))
+    )]
+    pub reporter: AccountId,
+    pub price: Price,
+    /// Timestamp of the price, in nanoseconds.
+    #[cfg_attr(
+        feature = "schema",
+        borsh(schema(with_funcs(
+            declaration = "crate::schema::declaration::<u64>",
+            definitions = "crate::schema::definitions::<u64>",
+        )))
+    )]
+    pub timestamp: U64,
+    /// Uncertainty of the price, as the reporter estimates it.
+    pub confidence: Option<Price>,
+    pub provenance: Provenance,
+}
+
+/// The submissions of the round not recorded yet.
+#[derive(Debug, Default, Clone, PartialEq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct PendingRound {
+    /// Block timestamp of the first submission, in nanoseconds.
+    started: u64,
+    submissions: Vec<Submission>,
+}
+
+impl PendingRound {
+    /// Checks whether the `reporter` has submitted a price to the round.
+    pub fn has_submitted(&self, reporter: &AccountId) -> bool {
+        self.submissions
+            .iter()
+            .any(|submission| submission.reporter == *reporter)
+    }
+
+    /// Returns the amount of the submissions.
+    pub fn submitted(&self) -> usize {
+        self.submissions.len()
+    }
+
+    /// Adds the `submission` at the `now` block timestamp, and returns the
+    /// submissions of a round to record, if any. A round outlasting
+    /// the submission window is closed first, so the `submission` starts a
+    /// new one.
+    ///
+    /// Fails when the reporter has already submitted a price to the round.
+    pub fn submit(
+        &mut self,
+        quorum: &Quorum,
+        submission: Submission,
+        now: u64,
+    ) -> Result<Option<Vec<Submission>>, ContractError> {
+        let window = quorum.submission_window.0;
+        if self.is_expired(quorum, now) {
+            // Hint: a round is never left complete, so at most one of the
+            // rounds is recorded.
+            let expired = self.close(quorum.min_submissions);
+            self.start(submission, now);
+            return Ok(expired);
+        }
+        if self.has_submitted(&submission.reporter) {
+            return Err(ContractError::AlreadySubmitted);
+        }
+        if self.submissions.is_empty() {
+            self.start(submission, now);
+        } else {
+            self.submissions.push(submission);
+        }
+        let len = self.submissions.len();
+        if len >= usize::from(quorum.max_submissions)
+            || (window == 0 && len >= usize::from(quorum.min_submissions))
+        {
+            Ok(self.close(quorum.min_submissions))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Checks whether the round has outlasted the submission window as of
+    /// the `now` block timestamp.
+    pub fn is_expired(&self, quorum: &Quorum, now: u64) -> bool {
+        let window = quorum.submission_window.0;
+        !self.submissions.is_empty() && window != 0 && now >= self.started.saturating_add(window)
+    }
+
+    fn start(&mut self, submission: Submission, now: u64) {
+        self.started = now;
+        self.submissions = vec![submission];
+    }
+
+    /// Empties the round, and returns its submissions if there are at least
+    /// `min_submissions` of them.
+    pub fn close(&mut self, min_submissions: u16) -> Option<Vec<Submission>> {
+        let submissions = std::mem::take(&mut self.submissions);
+        (submissions.len() >= usize::from(min_submissions)).then_some(submissions)
+    }
+}
+
+/// Returns the combined standard error of the `submissions` which carry a
+/// confidence, unless none of them do.
+pub fn confidence(submissions: &[Submission]) -> Option<Fixed> {
+    let confidences: Vec<Fixed> = submissions
+        .iter()
+        .filter_map(|submission| submission.confidence.map(Price::fixed))
+        .collect();
+    if confidences.is_empty() {
+        return None;
+    }
+    // Hint: the standard error of the mean of independent estimates.
+    fixed::quadrature(confidences.iter().copied())
+        .checked_div_int(confidences.len() as u128, Rounding::Nearest)
+}
+
+/// Returns the median of the `submissions`, at the newest timestamp, unless
+/// there are none.
+pub fn median(submissions: &[Submission]) -> Option<Submission> {
+    let timestamp = submissions
+        .iter()
+        .map(|submission| submission.timestamp)
+        .max()?;
+    let mut sorted = submissions.to_vec();
+    sorted.sort_by_key(|submission| submission.price);
+    // Hint: the lower median is an actual submission, so the record is
+    // attributed to its reporter.
+    let mut median = sorted.swap_remove((sorted.len() - 1) / 2);
+    median.timestamp = timestamp;
+    Some(median)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn submission(reporter: &str, price: f64, timestamp: u64) -> Submission {
+        Submission {
+            reporter: reporter.parse().unwrap(),
+            price: Price::new(price).unwrap(),
+            timestamp: timestamp.into(),
+            confidence: None,
+            provenance: Provenance::Reporter,
+        }
+    }
+
+    #[test]
+    fn rounds() {
+        let median = |round: Option<Vec<Submission>>| round.as_deref().and_then(super::median);
+        let quorum = Quorum {
+            min_submissions: 2,
+            max_submissions: 3,
+            submission_window: U64(10),
+        };
+        let mut round = PendingRound::default();
+        assert_eq!(
+            round.submit(&quorum, submission("a.near", 3., 0), 0),
+            Ok(None)
+        );
+        assert_eq!(
+            round.submit(&quorum, submission("b.near", 1., 1), 1),
+            Ok(None)
+        );
+        assert_eq!(
+            round.submit(&quorum, submission("b.near", 2., 1), 1),
+            Err(ContractError::AlreadySubmitted)
+        );
+        assert_eq!(
+            round
+                .submit(&quorum, submission("c.near", 2., 2), 2)
+                .map(median),
+            Ok(Some(submission("c.near", 2., 2)))
+        );
+        assert_eq!(round.submitted(), 0);
+
+        // Not enough submissions within the window.
+        assert_eq!(
+            round.submit(&quorum, submission("a.near", 3., 3), 3),
+            Ok(None)
+        );
+        assert_eq!(
+            round.submit(&quorum, submission("a.near", 1., 13), 13),
+            Ok(None)
+        );
+        assert_eq!(round.submitted(), 1);
+        assert_eq!(
+            round.submit(&quorum, submission("c.near", 5., 14), 14),
+            Ok(None)
+        );
+        assert_eq!(
+            round
+                .submit(&quorum, submission("b.near", 4., 23), 23)
+                .map(median),
+            Ok(Some(submission("a.near", 1., 14)))
+        );
+        assert_eq!(round.submitted(), 1);
+    }
+
+    #[test]
+    fn confidence() {
+        let with_confidence = |value: f64| Submission {
+            confidence: Some(Price::new(value).unwrap()),
+            ..submission("a.near", 1., 0)
+        };
+        assert_eq!(super::confidence(&[submission("a.near", 1., 0)]), None);
+        assert_eq!(
+            super::confidence(&[
+                with_confidence(6.),
+                with_confidence(8.),
+                submission("b.near", 1., 0)
+            ]),
+            Fixed::from_int(5)
+        );
+    }
+}
@@ -0,0 +1,68 @@
+//! The Borsh schemas of the SDK types, which the SDK only provides along
+//! with its ABI.
+//!
+//! A field of such a type refers to a type of the same layout, e.g.
+//! `String` for an `AccountId` or `u64` for a `U64`:
+//! ```ignore
+//! #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+//!     declaration = "crate::schema::declaration::<String>",
+//!     definitions = "crate::schema::definitions::<String>",
+//! ))))]
+//! ```
+
+// Hint: the layouts are only described, never constructed.
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+
+use near_sdk::borsh::schema::{Declaration, Definition};
+use near_sdk::borsh::BorshSchema;
+
+/// Returns the declaration of the `T`.
+pub fn declaration<T: BorshSchema>() -> Declaration {
+    T::declaration()
+}
+
+/// Adds the definitions of the `T`.
+pub fn definitions<T: BorshSchema>(definitions: &mut BTreeMap<Declaration, Definition>) {
+    T::add_definitions_recursively(definitions)
+}
+
+/// The layout of a `Vector`: its items are stored under the prefix.
+#[derive(BorshSchema)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Vector {
+    len: u64,
+    prefix: Vec<u8>,
+}
+
+/// The layout of a `LookupMap`: its entries are stored under the prefix.
+#[derive(BorshSchema)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct LookupMap {
+    key_prefix: Vec<u8>,
+}
+
+/// The layout of an `UnorderedMap`.
+#[derive(BorshSchema)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct UnorderedMap {
+    key_index_prefix: Vec<u8>,
+    keys: Vector,
+    values: Vector,
+}
+
+/// The layout of an `UnorderedSet`.
+#[derive(BorshSchema)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct UnorderedSet {
+    element_index_prefix: Vec<u8>,
+    elements: Vector,
+}
+
+/// The layout of a `LazyOption`: its value is stored under the key.
+#[derive(BorshSchema)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct LazyOption {
+    storage_key: Vec<u8>,
+}
@@ -0,0 +1,155 @@
+//! Proposals to slash a reporter for a bad submission.
+//!
+//! Anyone may propose to slash a reporter for a retained round, the
+//! submissions of which are stored along with the proposal as the evidence.
+//! The owner approves the proposal, revoking the reporter, or rejects it.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::serde::Serialize;
+use near_sdk::{env, log, near_bindgen, AccountId, FunctionError};
+
+use crate::error::ContractError;
+use crate::round::Submission;
+use crate::{LogLevel, PriceHistory, PriceHistoryExt};
+
+/// Where a slash proposal stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshDeserialize, BorshSerialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum SlashStatus {
+    Pending,
+    /// The reporter has been revoked.
+    Approved,
+    Rejected,
+}
+
+/// A proposal to slash a reporter.
+#[derive(Debug, Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct SlashProposal {
+    pub symbol: String,
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<String>",
+        definitions = "crate::schema::definitions::<String>",
+    ))))]
+    pub reporter: AccountId,
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<u64>",
+        definitions = "crate::schema::definitions::<u64>",
+    ))))]
+    pub round: U64,
+    pub reason: String,
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<String>",
+        definitions = "crate::schema::definitions::<String>",
+    ))))]
+    pub proposer: AccountId,
+    /// Every submission of the round.
+    pub evidence: Vec<Submission>,
+    pub status: SlashStatus,
+    /// Block timestamp of the proposal, in nanoseconds.
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<u64>",
+        definitions = "crate::schema::definitions::<u64>",
+    ))))]
+    pub timestamp: U64,
+}
+
+#[near_bindgen]
+impl PriceHistory {
+    /// Proposes to slash the `reporter` for its submission to the `round_id`
+    /// of the asset, and returns the identifier of the proposal.
+    ///
+    /// The storage is paid from the caller's storage balance, see
+    /// [`Self::storage_deposit`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the asset is not registered, when the round is not
+    /// retained or has no submission of the reporter, or when the caller's
+    /// storage balance is not enough.
+    pub fn propose_slash(
+        &mut self,
+        symbol: String,
+        reporter: AccountId,
+        round_id: U64,
+        reason: String,
+    ) -> u64 {
+        let evidence = self
+            .asset(&symbol)
+            .round_submissions(round_id.0)
+            .filter(|submissions| {
+                submissions
+                    .iter()
+                    .any(|submission| submission.reporter == reporter)
+            })
+            .unwrap_or_else(|| {
+                ContractError::OutOfBounds(
+                    "The round must be retained and have a submission of the reporter",
+                )
+                .panic()
+            });
+        let proposer = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        let proposal_id = self.slash_proposals.len();
+        self.slash_proposals.push(&SlashProposal {
+            symbol,
+            reporter,
+            round: round_id,
+            reason,
+            proposer: proposer.clone(),
+            evidence,
+            status: SlashStatus::Pending,
+            timestamp: env::block_timestamp().into(),
+        });
+        self.settle_storage(&proposer, initial_storage_usage);
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Slash proposal {} submitted by {}", proposal_id, proposer);
+        }
+        proposal_id
+    }
+
+    /// Approves the pending proposal, revoking the reporter's permission to
+    /// record prices, or rejects it.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract, when there is no such pending proposal, or when the
+    /// remaining reporters wouldn't make the quorum.
+    pub fn resolve_slash(&mut self, proposal_id: u64, approve: bool) {
+        self.assert_owner("resolve slash proposals");
+        let mut proposal = self
+            .slash_proposals
+            .get(proposal_id)
+            .filter(|proposal| proposal.status == SlashStatus::Pending)
+            .unwrap_or_else(|| {
+                ContractError::OutOfBounds("No such pending slash proposal").panic()
+            });
+        if approve {
+            proposal.status = SlashStatus::Approved;
+            self.reporters.remove(&proposal.reporter);
+            self.validate_quorum(&self.quorum);
+        } else {
+            proposal.status = SlashStatus::Rejected;
+        }
+        self.slash_proposals.replace(proposal_id, &proposal);
+        if self.log_level.allows(LogLevel::Info) {
+            log!(
+                "Slash proposal {} against {} {:?}",
+                proposal_id,
+                proposal.reporter,
+                proposal.status
+            );
+        }
+    }
+
+    /// Returns the slash proposal, if any.
+    pub fn get_slash_proposal(&self, proposal_id: u64) -> Option<SlashProposal> {
+        self.slash_proposals.get(proposal_id)
+    }
+}
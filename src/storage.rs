@@ -0,0 +1,366 @@
+//! Storage management in the [NEP-145] fashion: accounts deposit NEAR to
+//! cover the storage they occupy, e.g. by registering assets, and get the
+//! deposit back once the storage is released.
+//!
+//! [NEP-145]: https://nomicon.io/Standards/StorageManagement
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::UnorderedMap;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::Serialize;
+use near_sdk::{env, log, near_bindgen, AccountId, NearToken, Promise, StorageUsage};
+
+use crate::asset::{PriceRecord, Snapshot};
+use crate::error::{ensure, ContractError};
+use crate::{Balance, LogLevel, PriceHistory, PriceHistoryExt, StorageKey};
+
+/// Amount of bytes the runtime charges for each storage record on top of its
+/// key and value, see `num_extra_bytes_record` of the runtime configuration.
+const STORAGE_RECORD_OVERHEAD: u64 = 40;
+
+/// Storage balance of an account.
+#[derive(Debug, Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    /// Everything deposited by the account.
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<u128>",
+        definitions = "crate::schema::definitions::<u128>",
+    ))))]
+    pub total: U128,
+    /// Part of the deposit not locked by the storage the account occupies.
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<u128>",
+        definitions = "crate::schema::definitions::<u128>",
+    ))))]
+    pub available: U128,
+}
+
+impl Default for StorageBalance {
+    fn default() -> Self {
+        Self {
+            total: U128(0),
+            available: U128(0),
+        }
+    }
+}
+
+/// Limits of the storage balance.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+/// Storage occupied by the contract.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageReport {
+    /// Everything the account occupies, including the contract code.
+    pub total_bytes: U64,
+    /// Amount of NEAR (in yocto) staked for the `total_bytes`.
+    pub total_cost: U128,
+    /// Estimated storage of the assets and their price histories.
+    pub assets_bytes: U64,
+    /// Estimated storage of the reporter permissions.
+    pub reporters_bytes: U64,
+    /// The rest: the contract code, storage balances and the like.
+    pub other_bytes: U64,
+}
+
+#[near_bindgen]
+impl PriceHistory {
+    /// Deposits the attached amount to the storage balance of the
+    /// `account_id`, or of the caller if no account is provided. A new
+    /// balance locks the minimum of the [`Self::storage_balance_bounds`] for
+    /// its own storage. When `registration_only` is set, the deposit except
+    /// for that minimum is refunded.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the deposit doesn't cover the minimum for a new
+    /// balance.
+    #[payable]
+    pub fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let mut deposit = env::attached_deposit().as_yoctonear();
+        let mut balance = match self.storage_balances.get(&account_id) {
+            Some(balance) => balance,
+            None => {
+                let min = Self::storage_balance_min();
+                ensure(
+                    deposit >= min,
+                    ContractError::InsufficientDeposit {
+                        funds: "attached deposit",
+                        required: min,
+                        available: deposit,
+                    },
+                );
+                deposit -= min;
+                StorageBalance {
+                    total: U128(min),
+                    available: U128(0),
+                }
+            }
+        };
+        if registration_only == Some(true) {
+            if deposit != 0 {
+                Promise::new(env::predecessor_account_id())
+                    .transfer(NearToken::from_yoctonear(deposit))
+                    .detach();
+            }
+        } else {
+            balance.total.0 += deposit;
+            balance.available.0 += deposit;
+        }
+        self.storage_balances.insert(&account_id, &balance);
+        balance
+    }
+
+    /// Withdraws the `amount`, or everything available if no amount is
+    /// provided, from the caller's storage balance.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the `amount` exceeds the available balance.
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        Self::assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut balance = self.storage_balances.get(&account_id).unwrap_or_default();
+        let amount = amount.map_or(balance.available.0, |amount| amount.0);
+        ensure(
+            amount <= balance.available.0,
+            ContractError::OutOfBounds("The amount exceeds the available storage balance"),
+        );
+        balance.total.0 -= amount;
+        balance.available.0 -= amount;
+        self.storage_balances.insert(&account_id, &balance);
+        if amount > 0 {
+            Promise::new(account_id)
+                .transfer(NearToken::from_yoctonear(amount))
+                .detach();
+        }
+        balance
+    }
+
+    /// Removes the caller's storage balance, refunding it, and returns
+    /// whether there was any balance.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when a part of the balance is still in use, besides the
+    /// minimum locked for the balance itself, regardless of the `force`
+    /// flag: the storage occupied by assets can only be released by removing
+    /// them.
+    #[payable]
+    #[allow(unused_variables)]
+    pub fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        Self::assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let balance = match self.storage_balances.get(&account_id) {
+            Some(balance) => balance,
+            None => return false,
+        };
+        ensure(
+            balance.total.0 - balance.available.0 <= Self::storage_balance_min(),
+            ContractError::StorageInUse,
+        );
+        self.storage_balances.remove(&account_id);
+        if balance.total.0 > 0 {
+            Promise::new(account_id)
+                .transfer(NearToken::from_yoctonear(balance.total.0))
+                .detach();
+        }
+        true
+    }
+
+    /// Returns the storage balance limits.
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(Self::storage_balance_min()),
+            max: None,
+        }
+    }
+
+    /// Returns the storage balance of the account, if any.
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.storage_balances.get(&account_id)
+    }
+
+    /// Returns the storage occupied by the contract and its cost.
+    pub fn get_storage_report(&self) -> StorageReport {
+        let total_bytes = env::storage_usage();
+        let assets_bytes = map_bytes(&self.assets, StorageKey::Assets) + self.detached_bytes();
+        let reporters_bytes = map_bytes(&self.reporters, StorageKey::Reporters);
+        StorageReport {
+            total_bytes: total_bytes.into(),
+            total_cost: (Balance::from(total_bytes) * env::storage_byte_cost().as_yoctonear())
+                .into(),
+            assets_bytes: assets_bytes.into(),
+            reporters_bytes: reporters_bytes.into(),
+            other_bytes: total_bytes
+                .saturating_sub(assets_bytes)
+                .saturating_sub(reporters_bytes)
+                .into(),
+        }
+    }
+}
+
+/// Estimates the storage occupied by the entries of the `map`.
+///
+/// Every entry of an [`UnorderedMap`] takes three storage records: the key
+/// to index mapping, the key and the value; the last two are stored by the
+/// index.
+fn map_bytes<K, V>(map: &UnorderedMap<K, V>, prefix: StorageKey) -> u64
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+{
+    // Hint: the sub-collections are prefixed with an extra byte.
+    let prefix_len = borsh::to_vec(&prefix).map_or(0, |prefix| prefix.len() as u64) + 1;
+    let index_len = std::mem::size_of::<u64>() as u64;
+    map.iter()
+        .map(|(key, value)| {
+            let key_len = borsh::to_vec(&key).map_or(0, |key| key.len() as u64);
+            let value_len = borsh::to_vec(&value).map_or(0, |value| value.len() as u64);
+            3 * (STORAGE_RECORD_OVERHEAD + prefix_len)
+                + (key_len + index_len)
+                + (index_len + key_len)
+                + (index_len + value_len)
+        })
+        .sum()
+}
+
+impl PriceHistory {
+    /// Estimates the storage the assets occupy under their own prefixes: the
+    /// records of the price histories along with their reporters,
+    /// submissions and rounds, the candidates for the extremes of the windows
+    /// and the epoch snapshots, each of which is stored by its index, the
+    /// archives and the rarely needed data.
+    fn detached_bytes(&self) -> u64 {
+        // Hint: the values are prefixed with an extra byte, see `Asset::new`.
+        let prefix_len = borsh::to_vec(&StorageKey::History { asset_id: 0 })
+            .map_or(0, |prefix| prefix.len() as u64)
+            + 1;
+        let index_len = std::mem::size_of::<u64>() as u64;
+        let record_len =
+            borsh::to_vec(&PriceRecord::default()).map_or(0, |record| record.len() as u64);
+        let record_bytes = STORAGE_RECORD_OVERHEAD + prefix_len + index_len + record_len;
+        // Hint: the reporter of a record is stored in a slot of its own, the
+        // accounts themselves are measured.
+        let reporter_bytes = STORAGE_RECORD_OVERHEAD + prefix_len + index_len;
+        // Hint: so are the submissions of a record.
+        let submissions_bytes = reporter_bytes;
+        let round_bytes = reporter_bytes + std::mem::size_of::<u64>() as u64;
+        // Hint: a candidate is a price along with its round, just like a
+        // record is a price along with its timestamp.
+        let candidate_bytes = record_bytes;
+        // Hint: a shard is stored along with an entry of the index, both by
+        // the index of the shard; the shards themselves are measured.
+        let shard_bytes = 2 * (STORAGE_RECORD_OVERHEAD + prefix_len + 1 + index_len) + index_len;
+        let snapshot_bytes = STORAGE_RECORD_OVERHEAD
+            + prefix_len
+            + index_len
+            + borsh::to_vec(&Snapshot::default()).map_or(0, |snapshot| snapshot.len() as u64);
+        self.assets
+            .values()
+            .map(|asset| {
+                let metadata_len =
+                    borsh::to_vec(&asset.metadata()).map_or(0, |value| value.len() as u64);
+                let observations_len =
+                    borsh::to_vec(&asset.observations()).map_or(0, |value| value.len() as u64);
+                let (submission_slots, submissions_len) = asset.submissions_len();
+                asset.history_len() * (record_bytes + reporter_bytes + round_bytes)
+                    + asset.reporters_len()
+                    + submission_slots * submissions_bytes
+                    + submissions_len
+                    + asset.extreme_candidates() * candidate_bytes
+                    + asset.archive.shards() * shard_bytes
+                    + asset.archive.shards_len()
+                    + asset.snapshots_len() * snapshot_bytes
+                    + 2 * (STORAGE_RECORD_OVERHEAD + prefix_len)
+                    + metadata_len
+                    + observations_len
+            })
+            .sum()
+    }
+
+    /// Charges the `account_id` for the storage occupied since the
+    /// `initial_usage`, or refunds it for the storage released.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the available storage balance is not enough.
+    pub(crate) fn settle_storage(&mut self, account_id: &AccountId, initial_usage: StorageUsage) {
+        let usage = env::storage_usage();
+        let mut balance = self.storage_balances.get(account_id).unwrap_or_default();
+        if usage > initial_usage {
+            let cost =
+                Balance::from(usage - initial_usage) * env::storage_byte_cost().as_yoctonear();
+            ensure(
+                cost <= balance.available.0,
+                ContractError::InsufficientDeposit {
+                    funds: "storage balance",
+                    required: cost,
+                    available: balance.available.0,
+                },
+            );
+            balance.available.0 -= cost;
+        } else {
+            let refund =
+                Balance::from(initial_usage - usage) * env::storage_byte_cost().as_yoctonear();
+            // Hint: never release more than has been locked.
+            balance.available.0 = (balance.available.0 + refund).min(balance.total.0);
+        }
+        if self.log_level.allows(LogLevel::Debug) {
+            log!(
+                "Storage usage changed from {} to {} bytes, {} yoctoNEAR available to {}",
+                initial_usage,
+                usage,
+                balance.available.0,
+                account_id
+            );
+        }
+        self.storage_balances.insert(account_id, &balance);
+    }
+
+    /// Credits the `amount` paid in advance for some storage, e.g. the
+    /// records yet to be stored, to the storage balance of the `account_id`.
+    #[cfg(feature = "indicators")]
+    pub(crate) fn prepay_storage(&mut self, account_id: &AccountId, amount: Balance) {
+        let mut balance = self.storage_balances.get(account_id).unwrap_or_default();
+        balance.total.0 += amount;
+        balance.available.0 += amount;
+        self.storage_balances.insert(account_id, &balance);
+    }
+
+    /// Returns the cost of the storage balance of an account with the longest
+    /// id, which is locked by the balance itself.
+    fn storage_balance_min() -> Balance {
+        let prefix_len =
+            borsh::to_vec(&StorageKey::StorageBalances).map_or(0, |prefix| prefix.len() as u64);
+        // Hint: an account id is serialized as a string of up to 64 bytes.
+        let key_len = (std::mem::size_of::<u32>() + 64) as u64;
+        let value_len =
+            borsh::to_vec(&StorageBalance::default()).map_or(0, |value| value.len() as u64);
+        Balance::from(STORAGE_RECORD_OVERHEAD + prefix_len + key_len + value_len)
+            * env::storage_byte_cost().as_yoctonear()
+    }
+
+    /// Panics unless exactly one yoctoNEAR is attached, which requires a
+    /// full access key confirmation.
+    pub(crate) fn assert_one_yocto() {
+        ensure(
+            env::attached_deposit() == NearToken::from_yoctonear(1),
+            ContractError::OneYoctoRequired,
+        );
+    }
+}
@@ -0,0 +1,328 @@
+//! Pushing the averages to the subscribed consumer contracts on every record.
+//!
+//! A subscriber pays for the storage of its subscription and chooses the gas
+//! attached to its notifications. A subscriber whose notifications keep
+//! failing is evicted, so the list stays healthy.
+//!
+//! The notifications of a record are sent in batches bounded by the gas of
+//! the call; the rest of them are continued by anyone, e.g. a keeper, with
+//! [`PriceHistory::continue_notifications`].
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::serde::Serialize;
+use near_sdk::AccountId;
+#[cfg(feature = "subscriptions")]
+use near_sdk::{env, log, near_bindgen, FunctionError, Gas, PromiseError};
+
+use crate::fixed::Fixed;
+#[cfg(feature = "subscriptions")]
+use crate::{
+    delivery::ext_consumer,
+    error::{ensure, ContractError},
+    LogLevel, PriceHistory, PriceHistoryExt, DEFAULT_PAGE_LIMIT,
+};
+
+/// Gas attached to a notification unless the subscriber chooses otherwise.
+#[cfg(feature = "subscriptions")]
+const DEFAULT_GAS_FOR_NOTIFICATION: Gas = Gas::from_tgas(10);
+/// The most gas a subscriber may choose for a notification.
+#[cfg(feature = "subscriptions")]
+const MAX_GAS_FOR_NOTIFICATION: Gas = Gas::from_tgas(50);
+/// Gas attached to the notification callback.
+#[cfg(feature = "subscriptions")]
+const GAS_FOR_NOTIFICATION_CALLBACK: Gas = Gas::from_tgas(5);
+/// Amount of the consecutive failed notifications evicting a subscriber.
+#[cfg(feature = "subscriptions")]
+const MAX_NOTIFICATION_FAILURES: u8 = 3;
+/// Gas left to finish the call after a batch of notifications.
+#[cfg(feature = "subscriptions")]
+const GAS_RESERVE: Gas = Gas::from_tgas(10);
+
+/// The `msg` of the notifications, so a consumer tells them apart from the
+/// prices it has requested, see the [`PriceHistory::get_average_and_call`].
+#[cfg(feature = "subscriptions")]
+const NOTIFICATION_MSG: &str = "subscription";
+
+/// A subscription to the averages of some assets.
+#[derive(Debug, Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Subscription {
+    pub symbols: Vec<String>,
+    /// Gas attached to every notification.
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<u64>",
+        definitions = "crate::schema::definitions::<u64>",
+    ))))]
+    pub gas: U64,
+    /// Amount of the consecutive failed notifications.
+    pub failures: u8,
+}
+
+impl Subscription {
+    /// Replaces the `old_symbol` with the `new_symbol`, returning whether the
+    /// subscription has changed.
+    pub fn rename(&mut self, old_symbol: &str, new_symbol: &str) -> bool {
+        let mut changed = false;
+        for symbol in self.symbols.iter_mut().filter(|symbol| *symbol == old_symbol) {
+            *symbol = new_symbol.to_string();
+            changed = true;
+        }
+        changed
+    }
+}
+
+/// The notifications of the latest record of an asset yet to be sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshDeserialize, BorshSerialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct PendingNotifications {
+    pub average: Fixed,
+    pub timestamp: u64,
+    /// Index of the next subscriber to notify.
+    pub cursor: u64,
+}
+
+/// A subscriber, as returned by the views.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SubscriberInfo {
+    pub account_id: AccountId,
+    pub subscription: Subscription,
+}
+
+#[cfg(feature = "subscriptions")]
+#[near_bindgen]
+impl PriceHistory {
+    /// Subscribes the caller to the averages of the assets, replacing its
+    /// previous subscription, if any. On every record of any of the assets
+    /// its `on_price_average` is called with the `gas` (10 TGas by default)
+    /// and the "subscription" `msg`, see the [`crate::PriceConsumer`].
+    ///
+    /// The storage is paid from the caller's storage balance, see
+    /// [`Self::storage_deposit`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic when the caller is not an allowed consumer, when no asset is
+    /// listed or any of them is not registered, when the `gas` exceeds 50
+    /// TGas, or when the caller's storage balance is not enough.
+    pub fn subscribe(&mut self, symbols: Vec<String>, gas: Option<U64>) {
+        let subscriber = self.assert_consumer();
+        ensure(
+            !symbols.is_empty(),
+            ContractError::OutOfBounds("At least one asset must be subscribed to"),
+        );
+        for symbol in &symbols {
+            self.asset(symbol);
+        }
+        let gas = gas.unwrap_or(U64(DEFAULT_GAS_FOR_NOTIFICATION.as_gas()));
+        ensure(
+            gas.0 <= MAX_GAS_FOR_NOTIFICATION.as_gas(),
+            ContractError::OutOfBounds("The gas of a notification must not exceed 50 TGas"),
+        );
+        let initial_storage_usage = env::storage_usage();
+        self.subscribers.insert(
+            &subscriber,
+            &Subscription {
+                symbols,
+                gas,
+                failures: 0,
+            },
+        );
+        self.settle_storage(&subscriber, initial_storage_usage);
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Subscribed {}", subscriber);
+        }
+    }
+
+    /// Cancels the caller's subscription, refunding its storage, and returns
+    /// whether there has been one.
+    pub fn unsubscribe(&mut self) -> bool {
+        let subscriber = env::predecessor_account_id();
+        self.evict(&subscriber, "Unsubscribed")
+    }
+
+    /// Returns the subscription of the account, if any.
+    pub fn get_subscription(&self, account_id: AccountId) -> Option<Subscription> {
+        self.subscribers.get(&account_id)
+    }
+
+    /// Returns up to `limit` (50 by default) subscribers, starting from the
+    /// `from_index`.
+    pub fn get_subscribers(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<SubscriberInfo> {
+        let accounts = self.subscribers.keys_as_vector();
+        let subscriptions = self.subscribers.values_as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let to_index = from_index
+            .saturating_add(limit.unwrap_or(DEFAULT_PAGE_LIMIT))
+            .min(accounts.len());
+        (from_index..to_index)
+            .filter_map(|index| {
+                Some(SubscriberInfo {
+                    account_id: accounts.get(index)?,
+                    subscription: subscriptions.get(index)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the index of the next subscriber to notify of the latest
+    /// record of the asset, if the notifications are not complete.
+    pub fn get_notification_cursor(&self, symbol: String) -> Option<U64> {
+        self.notifications
+            .get(&symbol)
+            .map(|pending| pending.cursor.into())
+    }
+
+    /// Sends the next batch of the notifications of the latest record of the
+    /// asset, starting from the `cursor` returned by the
+    /// [`Self::get_notification_cursor`], and returns the cursor of the next
+    /// batch, if any.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when there are no pending notifications of the asset, when
+    /// they don't continue from the `cursor`, or when the gas is not enough
+    /// for any notification.
+    pub fn continue_notifications(&mut self, symbol: String, cursor: U64) -> Option<U64> {
+        let pending = self
+            .notifications
+            .get(&symbol)
+            .filter(|pending| pending.cursor == cursor.0)
+            .unwrap_or_else(|| {
+                ContractError::OutOfBounds("No pending notifications of the asset at the cursor")
+                    .panic()
+            });
+        let next = self.notify_batch(&symbol, pending);
+        ensure(
+            next != Some(cursor.0),
+            ContractError::OutOfBounds("Not enough gas for a notification"),
+        );
+        next.map(U64)
+    }
+
+    /// Counts the failed notifications of the subscriber, evicting it after
+    /// too many of them in a row.
+    #[private]
+    pub fn on_notified(
+        &mut self,
+        account_id: AccountId,
+        #[callback_result] result: Result<(), PromiseError>,
+    ) -> bool {
+        let delivered = result.is_ok();
+        let Some(mut subscription) = self.subscribers.get(&account_id) else {
+            return delivered;
+        };
+        if delivered {
+            if subscription.failures != 0 {
+                subscription.failures = 0;
+                self.subscribers.insert(&account_id, &subscription);
+            }
+            return delivered;
+        }
+        subscription.failures += 1;
+        if subscription.failures >= MAX_NOTIFICATION_FAILURES {
+            self.evict(&account_id, "Evicted");
+        } else {
+            self.subscribers.insert(&account_id, &subscription);
+            if self.log_level.allows(LogLevel::Error) {
+                log!("Failed to notify {}", account_id);
+            }
+        }
+        delivered
+    }
+}
+
+#[cfg(feature = "subscriptions")]
+impl PriceHistory {
+    /// Starts notifying the subscribers of the asset about its new
+    /// `average`, superseding the notifications of the previous record.
+    pub(crate) fn notify_subscribers(&mut self, symbol: &String, average: Fixed, timestamp: u64) {
+        let pending = PendingNotifications {
+            average,
+            timestamp,
+            cursor: 0,
+        };
+        self.notify_batch(symbol, pending);
+    }
+
+    /// Notifies the subscribers of the asset from the cursor as long as the
+    /// gas lasts, stores where the notifications stopped, and returns the
+    /// cursor of the next batch, if any.
+    // Hint: an eviction moves the last subscriber to the place of the evicted
+    // one, which may be missed by the pending notifications.
+    fn notify_batch(&mut self, symbol: &String, mut pending: PendingNotifications) -> Option<u64> {
+        let accounts = self.subscribers.keys_as_vector();
+        let subscriptions = self.subscribers.values_as_vector();
+        while pending.cursor < accounts.len() {
+            // Hint: every subscriber is read, whatever its assets, so the gas
+            // is checked on every step.
+            if env::prepaid_gas().saturating_sub(env::used_gas()) < GAS_RESERVE {
+                break;
+            }
+            let (Some(account_id), Some(subscription)) = (
+                accounts.get(pending.cursor),
+                subscriptions.get(pending.cursor),
+            ) else {
+                break;
+            };
+            if subscription.symbols.contains(symbol) {
+                let required = Gas::from_gas(subscription.gas.0)
+                    .saturating_add(GAS_FOR_NOTIFICATION_CALLBACK)
+                    .saturating_add(GAS_RESERVE);
+                if env::prepaid_gas().saturating_sub(env::used_gas()) < required {
+                    break;
+                }
+                ext_consumer::ext(account_id.clone())
+                    .with_static_gas(Gas::from_gas(subscription.gas.0))
+                    .on_price_average(
+                        symbol.clone(),
+                        pending.average.to_f64(),
+                        pending.timestamp.into(),
+                        NOTIFICATION_MSG.to_string(),
+                    )
+                    .then(
+                        Self::ext(env::current_account_id())
+                            .with_static_gas(GAS_FOR_NOTIFICATION_CALLBACK)
+                            .on_notified(account_id),
+                    )
+                    .detach();
+            }
+            pending.cursor += 1;
+        }
+        if pending.cursor >= accounts.len() {
+            self.notifications.remove(symbol);
+            return None;
+        }
+        self.notifications.insert(symbol, &pending);
+        if self.log_level.allows(LogLevel::Info) {
+            log!(
+                "Notifications of {} continue from {}",
+                symbol,
+                pending.cursor
+            );
+        }
+        Some(pending.cursor)
+    }
+
+    /// Removes the subscription of the account, refunding its storage, and
+    /// returns whether there has been one.
+    fn evict(&mut self, account_id: &AccountId, reason: &str) -> bool {
+        let initial_storage_usage = env::storage_usage();
+        if self.subscribers.remove(account_id).is_none() {
+            return false;
+        }
+        self.settle_storage(account_id, initial_storage_usage);
+        if self.log_level.allows(LogLevel::Info) {
+            log!("{} {}", reason, account_id);
+        }
+        true
+    }
+}
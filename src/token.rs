@@ -0,0 +1,283 @@
+//! Payments with a [NEP-141] fungible token.
+//!
+//! Transferring the configured token to the contract with `ft_transfer_call`
+//! credits the sender, and the credit is spent on the premium views when the
+//! attached deposit and the NEAR allowance don't cover the access fee.
+//!
+//! The collected fees make the reward pool of the reporters, which may also
+//! be funded directly, so the reporters are paid in the token with
+//! `claim_rewards`.
+//!
+//! [NEP-141]: https://nomicon.io/Standards/Tokens/FungibleToken/Core
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::Serialize;
+use near_sdk::serde_json::json;
+use near_sdk::{
+    env, ext_contract, log, near_bindgen, AccountId, FunctionError, Gas, NearToken, Promise,
+    PromiseError, PromiseOrValue,
+};
+
+use crate::error::{ensure, ContractError};
+use crate::events;
+use crate::{Balance, LogLevel, PriceHistory, PriceHistoryExt};
+
+/// The `msg` of a transfer funding the reward pool.
+pub const FUND_REWARDS_MSG: &str = "fund_rewards";
+
+/// Gas attached to the reward transfer.
+const GAS_FOR_REWARD_TRANSFER: Gas = Gas::from_tgas(10);
+/// Gas attached to the reward transfer callback.
+const GAS_FOR_REWARD_CALLBACK: Gas = Gas::from_tgas(5);
+
+#[allow(dead_code)]
+#[ext_contract(ext_ft)]
+trait FungibleToken {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+/// The token accepted as a payment.
+#[derive(Debug, Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(near_sdk::borsh::BorshSchema))]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct PaymentToken {
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<String>",
+        definitions = "crate::schema::definitions::<String>",
+    ))))]
+    pub token_id: AccountId,
+    /// Fee charged for a premium view, in the token units.
+    #[cfg_attr(feature = "schema", borsh(schema(with_funcs(
+        declaration = "crate::schema::declaration::<u128>",
+        definitions = "crate::schema::definitions::<u128>",
+    ))))]
+    pub access_fee: U128,
+}
+
+#[near_bindgen]
+impl PriceHistory {
+    /// Returns the token accepted as a payment, if any.
+    pub fn get_payment_token(&self) -> Option<PaymentToken> {
+        self.payment_token.clone()
+    }
+
+    /// Sets the token accepted as a payment, or stops accepting tokens.
+    ///
+    /// The existing credits are not converted, so the token should only be
+    /// replaced once they are spent.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when called not from the account which was used to deployed
+    /// the contract.
+    pub fn set_payment_token(&mut self, token_id: Option<AccountId>, access_fee: U128) {
+        self.assert_owner("change the payment token");
+        let payment_token = token_id.map(|token_id| PaymentToken {
+            token_id,
+            access_fee,
+        });
+        if self.log_level.allows(LogLevel::Info) {
+            log!("Payment token changed to {:?}", payment_token);
+        }
+        events::emit_config_changes(
+            None,
+            &json!({ "payment_token": self.payment_token }),
+            &json!({ "payment_token": payment_token }),
+        );
+        self.payment_token = payment_token;
+    }
+
+    /// Returns the token credit of the account.
+    pub fn get_token_credit(&self, account_id: AccountId) -> U128 {
+        self.token_credits.get(&account_id).unwrap_or(0).into()
+    }
+
+    /// Transfers the `amount`, or the whole credit if no amount is provided,
+    /// of the caller's token credit back to the caller, e.g. once the
+    /// contract is decommissioned, and resolves to whether the transfer has
+    /// succeeded; the credit is restored otherwise.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when no payment token is set, when the caller has no
+    /// credit, or when the `amount` exceeds it.
+    pub fn withdraw_token_credit(&mut self, amount: Option<U128>) -> Promise {
+        let token_id = self
+            .payment_token
+            .as_ref()
+            .map(|token| token.token_id.clone())
+            .unwrap_or_else(|| ContractError::OutOfBounds("No payment token is set").panic());
+        let account_id = env::predecessor_account_id();
+        let credit = self.token_credits.get(&account_id).unwrap_or(0);
+        let amount = amount.map_or(credit, |amount| amount.0);
+        ensure(
+            amount > 0 && amount <= credit,
+            ContractError::OutOfBounds("The amount must be within the credit"),
+        );
+        if amount == credit {
+            self.token_credits.remove(&account_id);
+        } else {
+            self.token_credits.insert(&account_id, &(credit - amount));
+        }
+        ext_ft::ext(token_id)
+            .with_attached_deposit(NearToken::from_yoctonear(1))
+            .with_static_gas(GAS_FOR_REWARD_TRANSFER)
+            .ft_transfer(account_id.clone(), amount.into(), None)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_REWARD_CALLBACK)
+                    .on_credit_withdrawn(account_id, amount.into()),
+            )
+    }
+
+    /// Restores the credit unless the transfer has succeeded.
+    #[private]
+    pub fn on_credit_withdrawn(
+        &mut self,
+        account_id: AccountId,
+        amount: U128,
+        #[callback_result] result: Result<(), PromiseError>,
+    ) -> bool {
+        let withdrawn = result.is_ok();
+        if withdrawn {
+            if self.log_level.allows(LogLevel::Info) {
+                log!("{} withdrew {} of the credit", account_id, amount.0);
+            }
+        } else {
+            let credit = self.token_credits.get(&account_id).unwrap_or(0) + amount.0;
+            self.token_credits.insert(&account_id, &credit);
+            if self.log_level.allows(LogLevel::Error) {
+                log!(
+                    "Failed to transfer {} of the credit to {}",
+                    amount.0,
+                    account_id
+                );
+            }
+        }
+        withdrawn
+    }
+
+    /// Credits the `amount` of the payment token to the `sender_id`, or to
+    /// the account provided with the `msg`; the `fund_rewards` message adds
+    /// the amount to the reward pool instead. Tokens other than the
+    /// configured one are refunded.
+    pub fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        let accepted = self
+            .payment_token
+            .as_ref()
+            .is_some_and(|token| token.token_id == env::predecessor_account_id());
+        if !accepted {
+            return PromiseOrValue::Value(amount);
+        }
+        if msg == FUND_REWARDS_MSG {
+            self.token_reward_pool += amount.0;
+            if self.log_level.allows(LogLevel::Info) {
+                log!("Reward pool funded with {} by {}", amount.0, sender_id);
+            }
+            return PromiseOrValue::Value(U128(0));
+        }
+        let account_id = if msg.is_empty() {
+            sender_id
+        } else {
+            match msg.parse::<AccountId>() {
+                Ok(account_id) => account_id,
+                Err(_) => return PromiseOrValue::Value(amount),
+            }
+        };
+        let credit = self.token_credits.get(&account_id).unwrap_or(0) + amount.0;
+        self.token_credits.insert(&account_id, &credit);
+        PromiseOrValue::Value(U128(0))
+    }
+
+    /// Returns the rewards accrued to the reporter, in the payment token
+    /// units.
+    pub fn get_token_rewards(&self, account_id: AccountId) -> U128 {
+        self.get_reporter_rewards(account_id).unclaimed
+    }
+
+    /// Transfers the caller's accrued rewards in the payment token, and
+    /// resolves to whether the transfer has succeeded; the rewards are
+    /// restored otherwise.
+    ///
+    /// # Panics
+    ///
+    /// Will panic when no payment token is set, or when the caller has no
+    /// rewards accrued.
+    pub fn claim_rewards(&mut self) -> Promise {
+        let token_id = self
+            .payment_token
+            .as_ref()
+            .map(|token| token.token_id.clone())
+            .unwrap_or_else(|| ContractError::OutOfBounds("No payment token is set").panic());
+        let account_id = env::predecessor_account_id();
+        let mut rewards = self.token_rewards.get(&account_id).unwrap_or_default();
+        let amount = std::mem::take(&mut rewards.unclaimed.0);
+        ensure(
+            amount > 0,
+            ContractError::OutOfBounds("No rewards to claim"),
+        );
+        self.token_rewards.insert(&account_id, &rewards);
+        ext_ft::ext(token_id)
+            .with_attached_deposit(NearToken::from_yoctonear(1))
+            .with_static_gas(GAS_FOR_REWARD_TRANSFER)
+            .ft_transfer(account_id.clone(), amount.into(), None)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_REWARD_CALLBACK)
+                    .on_rewards_claimed(account_id, amount.into()),
+            )
+    }
+
+    /// Restores the rewards unless the transfer has succeeded.
+    #[private]
+    pub fn on_rewards_claimed(
+        &mut self,
+        account_id: AccountId,
+        amount: U128,
+        #[callback_result] result: Result<(), PromiseError>,
+    ) -> bool {
+        let claimed = result.is_ok();
+        if claimed {
+            if self.log_level.allows(LogLevel::Info) {
+                log!("{} claimed {} in rewards", account_id, amount.0);
+            }
+        } else {
+            let mut rewards = self.token_rewards.get(&account_id).unwrap_or_default();
+            rewards.unclaimed.0 += amount.0;
+            self.token_rewards.insert(&account_id, &rewards);
+            if self.log_level.allows(LogLevel::Error) {
+                log!(
+                    "Failed to transfer {} in rewards to {}",
+                    amount.0,
+                    account_id
+                );
+            }
+        }
+        claimed
+    }
+}
+
+impl PriceHistory {
+    /// Spends the token credit of the `account_id` on the access fee and
+    /// returns whether the credit was enough.
+    pub(crate) fn charge_token_fee(&mut self, account_id: &AccountId) -> bool {
+        let fee: Balance = match &self.payment_token {
+            Some(token) => token.access_fee.0,
+            None => return false,
+        };
+        let credit = self.token_credits.get(account_id).unwrap_or(0);
+        if credit < fee {
+            return false;
+        }
+        self.token_credits.insert(account_id, &(credit - fee));
+        self.token_reward_pool += fee;
+        true
+    }
+}